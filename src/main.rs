@@ -2,7 +2,7 @@ use std::io;
 use sudoku_rust::sudoku::cached_option_finder::CachedOptionFinder;
 use sudoku_rust::sudoku::grid_diff::GridDiff;
 use sudoku_rust::sudoku::option_finder::StandardOptionFinder;
-use sudoku_rust::sudoku::{grid::Grid, solver::DepthFirstSolver};
+use sudoku_rust::sudoku::{grid::Grid, solver::{DepthFirstSolver, Solver}};
 
 fn main() {
     let puzzles: [[[Option<u8>; 9]; 9]; 3] = [
@@ -100,7 +100,7 @@ fn main() {
         let solution = solver.solve().get_solution();
 
         if let Some(solution) = solution {
-            println!("{}", GridDiff::new(&grid, &solution));
+            println!("{}", GridDiff::new(&grid, solution));
         } else {
             println!("No solution found");
         }
@@ -1,7 +1,34 @@
+pub mod batch;
+pub mod cage;
+pub mod canonical;
+pub mod cdcl;
+pub mod chess;
+pub mod clue_mask;
+pub mod clue_suggester;
+pub mod comparison;
+pub mod constraint;
 pub mod error;
+pub mod formula;
+pub mod generator;
 pub mod grid;
 pub mod grid_diff;
+pub mod kropki;
+pub mod line;
+pub mod minimiser;
+pub mod multi_grid;
 pub mod option_finder;
+pub mod outside;
+pub mod pair;
+pub mod parity;
+pub mod random;
+pub mod rater;
 pub mod reference;
+pub mod region_map;
+pub mod sandwich;
+pub mod sat;
+pub mod shape;
 pub mod solver;
+pub mod symmetry;
+pub mod transform;
+pub mod xv;
 pub mod cached_option_finder;
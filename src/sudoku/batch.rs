@@ -0,0 +1,255 @@
+use crate::sudoku::cdcl::CdclSolver;
+use crate::sudoku::error::PuzzleFailure;
+use crate::sudoku::grid::Grid;
+use crate::sudoku::multi_grid::MultiGridSolver;
+use crate::sudoku::rater::{Rater, Rating};
+use crate::sudoku::solver::{DepthFirstSolver, SolverBuilder};
+use std::any::Any;
+use std::error::Error;
+use std::num::NonZeroUsize;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+
+/// What to do when one of the puzzles in a batch fails
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ErrorHandling {
+    /// Stop handing out puzzles and report the failure
+    FailFast,
+    /// Carry on with the rest of the batch, and report each puzzle's outcome
+    CollectAll,
+}
+
+/*
+ * Works through a batch of puzzles on a pool of worker threads, each taking the next puzzle in the
+ * list as soon as it's finished with the last.  Results come back in the same order as the puzzles,
+ * however long each one took.
+ *
+ * A task that panics, as the depth-first solver does if it hits an error partway through, counts as
+ * a failure of that puzzle rather than bringing the whole batch down.  The panic still goes through
+ * the process's panic hook first, which by default prints the message to stderr.  The hook is
+ * shared by the whole process, so we leave it alone; callers who want a quiet batch can swap in
+ * their own with std::panic::set_hook around the run
+ */
+#[derive(Debug, Clone)]
+pub struct BatchRunner {
+    threads: usize,
+    error_handling: ErrorHandling,
+    solver: SolverBuilder,
+}
+
+impl BatchRunner {
+    /// Collects every puzzle's outcome by default.  A batch always runs on at least one thread, so
+    /// asking for none gives one
+    pub fn new(threads: usize) -> Self {
+        Self {
+            threads: threads.max(1),
+            error_handling: ErrorHandling::CollectAll,
+            solver: DepthFirstSolver::builder(),
+        }
+    }
+
+    pub fn threads(&self) -> usize {
+        self.threads
+    }
+
+    pub fn error_handling(&self) -> ErrorHandling {
+        self.error_handling
+    }
+
+    pub fn set_error_handling(&mut self, error_handling: ErrorHandling) -> &mut Self {
+        self.error_handling = error_handling;
+        self
+    }
+
+    /// The solver that solve_all uses, which is the depth-first solver unless told otherwise
+    pub fn set_solver(&mut self, solver: SolverBuilder) -> &mut Self {
+        self.solver = solver;
+        self
+    }
+
+    pub fn solver(&self) -> SolverBuilder {
+        self.solver
+    }
+
+    /*
+     * Runs the task on every puzzle, returning each puzzle's outcome in order.  When failing fast
+     * the first failure is returned instead, which is the one earliest in the list if more than one
+     * puzzle failed before the workers stopped
+     */
+    pub fn run<T, F>(&self, puzzles: &[Grid], task: F) -> Result<Vec<Result<T, PuzzleFailure>>, PuzzleFailure>
+    where
+        T: Send,
+        F: Fn(&Grid) -> Result<T, Box<dyn Error>> + Sync,
+    {
+        let next = AtomicUsize::new(0);
+        let stopped = AtomicBool::new(false);
+        let (sender, receiver) = mpsc::channel();
+
+        thread::scope(|scope| {
+            for _ in 0 .. self.threads.min(puzzles.len()) {
+                let sender = sender.clone();
+                let (next, stopped, task) = (&next, &stopped, &task);
+
+                scope.spawn(move || {
+                    while !stopped.load(Ordering::Relaxed) {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(puzzle) = puzzles.get(index) else {
+                            break;
+                        };
+
+                        let outcome = match panic::catch_unwind(AssertUnwindSafe(|| task(puzzle))) {
+                            Ok(Ok(result)) => Ok(result),
+                            Ok(Err(err)) => Err(PuzzleFailure::new(index, err.to_string())),
+                            Err(payload) => Err(PuzzleFailure::new(index, panic_message(payload))),
+                        };
+
+                        if outcome.is_err() && self.error_handling == ErrorHandling::FailFast {
+                            stopped.store(true, Ordering::Relaxed);
+                        }
+                        sender.send((index, outcome)).expect("The receiver outlives the workers");
+                    }
+                });
+            }
+        });
+        drop(sender);
+
+        let mut outcomes: Vec<Option<Result<T, PuzzleFailure>>> = puzzles.iter().map(|_| None).collect();
+        for (index, outcome) in receiver {
+            outcomes[index] = Some(outcome);
+        }
+
+        // Puzzles are handed out in order, so the only ones missing are those after the workers
+        // stopped, which is past any failure
+        let mut results = Vec::with_capacity(puzzles.len());
+        for outcome in outcomes.into_iter().flatten() {
+            match outcome {
+                Err(failure) if self.error_handling == ErrorHandling::FailFast => return Err(failure),
+                outcome => results.push(outcome),
+            }
+        }
+
+        Ok(results)
+    }
+
+    /// Solves every puzzle, giving None for those with no solution
+    pub fn solve_all(&self, puzzles: &[Grid]) -> Result<Vec<Result<Option<Grid>, PuzzleFailure>>, PuzzleFailure> {
+        self.run(puzzles, |puzzle| Ok((self.solver)(puzzle)?.solve().get_solution().clone()))
+    }
+
+    /// Rates every puzzle, as the Rater would one at a time
+    pub fn rate_all(&self, puzzles: &[Grid]) -> Result<Vec<Result<Rating, PuzzleFailure>>, PuzzleFailure> {
+        self.run(puzzles, |puzzle| Ok(Rater::new(puzzle).rate()))
+    }
+}
+
+/// One thread for each the machine can run at once
+impl Default for BatchRunner {
+    fn default() -> Self {
+        Self::new(thread::available_parallelism().map_or(1, NonZeroUsize::get))
+    }
+}
+
+/// Panics carry whatever was passed to panic!, which is almost always a string of some kind
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => match payload.downcast::<&str>() {
+            Ok(message) => message.to_string(),
+            Err(_) => "the task panicked".to_string(),
+        },
+    }
+}
+
+// The batch shares grids between its workers and builds solvers on them, so these all have to be
+// safe to move between threads
+const _: fn() = || {
+    fn assert_send<T: Send>() {}
+    fn assert_sync<T: Sync>() {}
+
+    assert_send::<Grid>();
+    assert_sync::<Grid>();
+    assert_send::<DepthFirstSolver>();
+    assert_send::<CdclSolver>();
+    assert_send::<MultiGridSolver>();
+    assert_send::<Rater>();
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sudoku::solver::Solver;
+
+    const PUZZLE: &str = "53..7....6..195....98....6.8...6...34..8.3..17...2...6.6....28....419..5....8..79";
+
+    fn puzzles(count: usize) -> Vec<Grid> {
+        (0 .. count).map(|_| PUZZLE.parse().unwrap()).collect()
+    }
+
+    // Fails on every third puzzle, so the batch has both failures and successes in it
+    fn every_third(puzzle: &Grid, index: &AtomicUsize) -> Result<usize, Box<dyn Error>> {
+        let index = index.fetch_add(1, Ordering::Relaxed);
+        if index % 3 == 1 {
+            Err(format!("puzzle {} was rejected", index).into())
+        } else {
+            Ok(puzzle.clue_count())
+        }
+    }
+
+    #[test]
+    fn collecting_all_reports_every_outcome_in_order() {
+        let solutions = BatchRunner::new(4).solve_all(&puzzles(6)).unwrap();
+        let puzzle = PUZZLE.parse().unwrap();
+        let expected = DepthFirstSolver::for_problem(&puzzle).solve().get_solution().clone().unwrap().to_string();
+
+        assert_eq!(solutions.len(), 6);
+        assert!(solutions.iter().all(|solution| solution.as_ref().unwrap().as_ref().unwrap().to_string() == expected));
+
+        let seen = AtomicUsize::new(0);
+        let outcomes = BatchRunner::new(1).run(&puzzles(6), |puzzle| every_third(puzzle, &seen)).unwrap();
+        let failed: Vec<usize> = outcomes.iter().filter_map(|outcome| outcome.as_ref().err()).map(PuzzleFailure::index).collect();
+
+        assert_eq!(outcomes.len(), 6);
+        assert_eq!(failed, [1, 4]);
+    }
+
+    #[test]
+    fn failing_fast_returns_the_first_failure() {
+        let seen = AtomicUsize::new(0);
+        let mut runner = BatchRunner::new(1);
+        runner.set_error_handling(ErrorHandling::FailFast);
+        let failure = runner.run(&puzzles(6), |puzzle| every_third(puzzle, &seen)).unwrap_err();
+
+        assert_eq!(failure.index(), 1);
+        assert_eq!(failure.to_string(), "Puzzle 1 in the batch failed: puzzle 1 was rejected");
+        // A single worker stops as soon as it hits the failure
+        assert_eq!(seen.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn a_panicking_task_fails_only_its_puzzle() {
+        let seen = AtomicUsize::new(0);
+        let outcomes = BatchRunner::new(2).run(&puzzles(3), |_| -> Result<usize, Box<dyn Error>> {
+            if seen.fetch_add(1, Ordering::Relaxed) == 0 {
+                panic!("the first puzzle blew up");
+            }
+            Ok(9)
+        }).unwrap();
+        let failures: Vec<String> = outcomes.iter().filter_map(|outcome| outcome.as_ref().err()).map(ToString::to_string).collect();
+
+        assert_eq!(outcomes.len(), 3);
+        assert_eq!(failures.len(), 1);
+        assert!(failures[0].ends_with("failed: the first puzzle blew up"));
+    }
+
+    #[test]
+    fn asking_for_no_threads_runs_on_one() {
+        let runner = BatchRunner::new(0);
+        assert_eq!(runner.threads(), 1);
+
+        let ratings = runner.rate_all(&puzzles(2)).unwrap();
+        assert_eq!(ratings.len(), 2);
+        assert_eq!(ratings[0].as_ref().unwrap().difficulty(), ratings[1].as_ref().unwrap().difficulty());
+    }
+}
@@ -19,10 +19,11 @@ impl<'problem> CachedOptionFinder<'problem> {
 
 impl<'problem> OptionFinder for CachedOptionFinder<'problem> {
     fn find_for_cell(&mut self, grid_ref: &GridReference) -> OptionList {
-        if !self.option_cache.contains_key(&grid_ref) {
-            self.option_cache.insert(grid_ref.clone(), self.finder.find_for_cell(&grid_ref));
+        if !self.option_cache.contains_key(grid_ref) {
+            let options = self.finder.find_for_cell(grid_ref);
+            self.option_cache.insert(grid_ref.clone(), options);
         }
 
-        self.option_cache[&grid_ref].clone()
+        self.option_cache[grid_ref].clone()
     }
 }
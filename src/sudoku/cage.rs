@@ -0,0 +1,258 @@
+use crate::sudoku::constraint::{CandidateSet, Constraint};
+use crate::sudoku::error::{CageSumError, InvalidCage, UniquenessConstraint, UniquenessError};
+use crate::sudoku::formula::{Formula, Term};
+use crate::sudoku::grid::Grid;
+use crate::sudoku::reference::GridReference;
+use crate::sudoku::sat::{Cnf, Literal};
+use crate::sudoku::shape::GridShape;
+use crate::sudoku::transform::Transform;
+use std::error::Error;
+use std::sync::Arc;
+
+/*
+ * A Killer Sudoku cage: a group of cells whose values must add up to the cage's total, without any
+ * value repeating.  Cages sit on top of the standard rules, so a Killer puzzle is a standard grid
+ * with a cage constraint added for each cage
+ */
+#[derive(Debug, Clone)]
+pub struct Cage {
+    total: usize,
+    cells: Vec<GridReference>,
+}
+
+impl Cage {
+    pub fn new(total: usize, cells: Vec<GridReference>) -> Result<Self, InvalidCage> {
+        let repeated = cells.iter().enumerate().any(|(position, cell)| cells[.. position].contains(cell));
+        if cells.is_empty() || repeated {
+            return Err(InvalidCage::new(total, cells.len()));
+        }
+
+        Ok(Self { total, cells })
+    }
+
+    pub fn total(&self) -> usize {
+        self.total
+    }
+
+    pub fn cells(&self) -> &[GridReference] {
+        &self.cells
+    }
+
+    /// The values placed in the cage so far, as a set, and what they add up to
+    fn placed(&self, grid: &Grid) -> (CandidateSet, usize) {
+        self.cells.iter()
+            .filter_map(|grid_ref| *grid.cell(grid_ref))
+            .fold((0, 0), |(placed, sum), value| (placed | (1 << (value - 1)), sum + value as usize))
+    }
+}
+
+impl Constraint for Cage {
+    fn affected_cells(&self, _shape: &GridShape) -> Vec<GridReference> {
+        self.cells.clone()
+    }
+
+    fn check(&self, grid: &Grid, changed: &GridReference) -> Result<(), Box<dyn Error>> {
+        let Some(value) = *grid.cell(changed) else {
+            return Ok(());
+        };
+
+        let repeated = self.cells.iter().any(|grid_ref| grid_ref != changed && *grid.cell(grid_ref) == Some(value));
+        if repeated {
+            return Err(UniquenessError::new(changed.row(), changed.column(), value, UniquenessConstraint::Cage).into());
+        }
+
+        // The values still to be placed have to make up the rest of the total between them
+        let (placed, sum) = self.placed(grid);
+        let empty_count = self.cells.iter().filter(|grid_ref| grid.cell(grid_ref).is_none()).count();
+        let unused = all_values(grid.shape()) & !placed;
+        let reachable = sum <= self.total && !sums_to(empty_count, self.total - sum, unused).is_empty();
+        if !reachable {
+            return Err(CageSumError::new(changed.row(), changed.column(), value, self.total).into());
+        }
+
+        Ok(())
+    }
+
+    /*
+     * Only values that appear in some combination making up the rest of the total can go in the
+     * empty cells.  Combinations are limited to the values that at least one of the empty cells
+     * could still take, which is what narrows things down as the cage fills up
+     */
+    fn prune(&self, grid: &Grid, candidates: &mut [CandidateSet]) {
+        let shape = grid.shape();
+        let empty: Vec<usize> = self.cells.iter()
+            .filter(|grid_ref| grid.cell(grid_ref).is_none())
+            .map(|grid_ref| grid_ref.to_index(shape))
+            .collect();
+        if empty.is_empty() {
+            return;
+        }
+
+        let (placed, sum) = self.placed(grid);
+        let open = empty.iter().fold(0, |open, index| open | candidates[*index]) & !placed;
+        let possible = match self.total.checked_sub(sum) {
+            Some(remaining) => sums_to(empty.len(), remaining, open).into_iter().fold(0, |possible, values| possible | values),
+            None => 0,
+        };
+
+        for index in empty {
+            candidates[index] &= possible;
+        }
+    }
+
+    /*
+     * No value repeats, and there's an extra variable for each combination of values that makes
+     * up the total.  One of those combinations has to be chosen, and every cell then takes one of
+     * its values.  A cage with no combinations at all gets the empty clause, which can't be met
+     */
+    fn encode(&self, shape: &GridShape, cnf: &mut Cnf) -> bool {
+        for value in 1 ..= shape.max_value() {
+            let holding: Vec<Literal> = self.cells.iter().map(|grid_ref| cnf.variable(grid_ref, value)).collect();
+            cnf.at_most_one(&holding);
+        }
+
+        let mut chosen = Vec::new();
+        for combination in combinations(self.cells.len(), self.total, shape.max_value()) {
+            let variable = cnf.new_variable();
+            chosen.push(variable);
+
+            for grid_ref in self.cells.iter() {
+                let mut clause = vec![-variable];
+                clause.extend(combination.iter().map(|value| cnf.variable(grid_ref, *value)));
+                cnf.add_clause(clause);
+            }
+        }
+        cnf.add_clause(chosen);
+
+        true
+    }
+
+    fn formulas(&self, _shape: &GridShape) -> Option<Vec<Formula>> {
+        Some(vec![
+            Formula::AllDifferent(self.cells.iter().cloned().map(Term::Cell).collect()),
+            Formula::Equal(Term::sum_of(&self.cells), Term::Number(self.total)),
+        ])
+    }
+
+    fn is_house(&self, shape: &GridShape) -> bool {
+        self.cells.len() == shape.size()
+    }
+
+    fn outline(&self, _shape: &GridShape) -> Option<(Vec<GridReference>, String)> {
+        Some((self.cells.clone(), self.total.to_string()))
+    }
+
+    /// The total only holds for the digits as they are
+    fn transformed(&self, _shape: &GridShape, transform: &Transform) -> Option<Arc<dyn Constraint>> {
+        if !transform.keeps_digits() {
+            return None;
+        }
+
+        let cage = Self::new(self.total, transform.move_cells(&self.cells)).ok()?;
+        Some(Arc::new(cage))
+    }
+}
+
+/// Every set of size different values from 1 up to max_value that adds up to total, each in
+/// ascending order.  For example there are two ways for a cage of 3 cells to total 8 in the
+/// standard grid: 1 + 2 + 5 and 1 + 3 + 4
+pub fn combinations(size: usize, total: usize, max_value: u8) -> Vec<Vec<u8>> {
+    let values: CandidateSet = (1 << max_value) - 1;
+
+    sums_to(size, total, values)
+        .into_iter()
+        .map(|combination| (1 ..= max_value).filter(|value| combination & (1 << (value - 1)) != 0).collect())
+        .collect()
+}
+
+fn all_values(shape: &GridShape) -> CandidateSet {
+    (1 << shape.size()) - 1
+}
+
+/// The sets of count different values, taken from the given set, that add up to total
+fn sums_to(count: usize, total: usize, values: CandidateSet) -> Vec<CandidateSet> {
+    let mut found = Vec::new();
+    collect_sums(count, total, values, 0, &mut found);
+
+    found
+}
+
+/*
+ * Works up through the values, either using or skipping the smallest value left.  There's no way
+ * to finish once even the smallest values left would take us over the total, or the largest ones
+ * couldn't reach it, which cuts the search down to not much more than the combinations found
+ */
+fn collect_sums(count: usize, total: usize, values: CandidateSet, chosen: CandidateSet, found: &mut Vec<CandidateSet>) {
+    if count == 0 {
+        if total == 0 {
+            found.push(chosen);
+        }
+        return;
+    }
+
+    if values.count_ones() < count as u32 {
+        return;
+    }
+
+    let smallest = values.trailing_zeros() as usize + 1;
+    let largest = (CandidateSet::BITS - values.leading_zeros()) as usize;
+    if smallest * count > total || largest * count < total {
+        return;
+    }
+
+    let value_bit = values & values.wrapping_neg();
+    let rest = values & !value_bit;
+    collect_sums(count - 1, total - smallest, rest, chosen | value_bit, found);
+    collect_sums(count, total, rest, chosen, found);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sudoku::grid::CellValue;
+
+    fn cell(row: usize, column: usize) -> GridReference {
+        GridReference::from_numbers(row, column).unwrap()
+    }
+
+    #[test]
+    fn lists_the_combinations_for_a_total() {
+        assert_eq!(combinations(3, 8, 9), vec![vec![1, 2, 5], vec![1, 3, 4]]);
+        assert_eq!(combinations(2, 17, 9), vec![vec![8, 9]]);
+        assert_eq!(combinations(9, 45, 9).len(), 1);
+        assert!(combinations(2, 18, 9).is_empty());
+    }
+
+    #[test]
+    fn rejects_values_that_break_the_total() {
+        let mut grid = Grid::new();
+        grid.add_constraint(Cage::new(10, vec![cell(0, 0), cell(1, 0), cell(1, 1)]).unwrap()).unwrap();
+
+        grid.set_cell(&cell(0, 0), &CellValue::new(6).unwrap()).unwrap();
+        assert!(grid.set_cell(&cell(1, 0), &CellValue::new(5).unwrap()).unwrap_err().is::<CageSumError>());
+
+        grid.set_cell(&cell(1, 0), &CellValue::new(1).unwrap()).unwrap();
+        assert!(grid.set_cell(&cell(1, 1), &CellValue::new(2).unwrap()).unwrap_err().is::<CageSumError>());
+        assert!(grid.set_cell(&cell(1, 1), &CellValue::new(3).unwrap()).is_ok());
+    }
+
+    #[test]
+    fn prunes_values_that_cant_reach_the_total() {
+        let grid = Grid::new();
+        let cage = Cage::new(4, vec![cell(0, 0), cell(0, 1)]).unwrap();
+
+        let mut candidates = vec![0b1_1111_1111; 81];
+        cage.prune(&grid, &mut candidates);
+
+        // Only 1 + 3 adds up to 4 without repeating
+        assert_eq!(candidates[0], 0b101);
+        assert_eq!(candidates[1], 0b101);
+        assert_eq!(candidates[2], 0b1_1111_1111);
+    }
+
+    #[test]
+    fn cages_need_distinct_cells() {
+        assert!(Cage::new(5, Vec::new()).is_err());
+        assert!(Cage::new(5, vec![cell(0, 0), cell(0, 0)]).is_err());
+    }
+}
@@ -0,0 +1,398 @@
+use crate::sudoku::constraint::{standard_constraints, Constraint};
+use crate::sudoku::error::{NonStandardRules, NonStandardShape};
+use crate::sudoku::grid::Grid;
+use crate::sudoku::reference::GridReference;
+use crate::sudoku::shape::GridShape;
+use crate::sudoku::transform::Transform;
+use std::cmp::Ordering;
+use std::error::Error;
+use std::sync::Arc;
+
+/*
+ * The canonical (minlex) form of a grid: of all the grids that can be reached from it with
+ * validity-preserving transforms, the one that comes first when read row by row as a string, with
+ * empty cells sorting before any digit.  Two grids are equivalent exactly when their canonical
+ * forms match, so the canonical form's key() can be used to find duplicates in a collection.
+ *
+ * Trying every transform would mean checking over three million line orderings per grid, so
+ * instead we build the canonical grid a row at a time.  At each step we only keep the partial
+ * transforms that give the smallest possible row so far; anything worse can never catch up, as
+ * rows earlier in the string always outweigh later ones.  Relabelling is handled by numbering the
+ * digits in the order they're first seen, which is always the smallest choice for a given cell
+ * order.
+ *
+ * The column order is settled by the first row.  Rather than try all 1296 of them for each
+ * possible first row we only generate the ones that can give the smallest first row, which cuts
+ * out almost all of the work
+ */
+#[derive(Debug, Clone)]
+pub struct CanonicalForm {
+    grid: Grid,
+    transform: Transform,
+}
+
+/// Canonical forms are built from transforms, so they're only defined on the standard grid too
+const SHAPE: GridShape = GridShape::standard();
+
+/// A partial transform that has fixed the first few rows of the canonical grid
+#[derive(Debug, Clone)]
+struct Partial {
+    transpose: bool,
+    rows: Vec<usize>,
+    columns: Vec<usize>,
+    // labels[value] is the canonical digit assigned to value, or 0 if it hasn't been seen yet
+    labels: [u8; 10],
+    next_label: u8,
+}
+
+impl CanonicalForm {
+    /// Canonical forms are built from transforms, so only the standard 9 x 9 grid following the
+    /// standard rules has one, as a transform that keeps to those rules can still break any others
+    pub fn of(grid: &Grid) -> Result<Self, Box<dyn Error>> {
+        if *grid.shape() != SHAPE {
+            return Err(NonStandardShape::new(grid.shape()).into());
+        }
+        if !Self::has_standard_rules(grid) {
+            return Err(NonStandardRules::new().into());
+        }
+
+        let values = Self::values_of(grid);
+
+        // The first row is special, as the candidate column orders depend on which row it is
+        let mut first_rows: Vec<(Partial, usize)> = Vec::new();
+        for transpose in [false, true] {
+            for row in 0 .. SHAPE.rows() {
+                for columns in Self::first_row_column_orders(&values, transpose, row) {
+                    first_rows.push((Partial::new(transpose, columns), row));
+                }
+            }
+        }
+
+        let mut frontier = Self::best_extensions(
+            first_rows.iter().map(|(partial, row)| (partial, *row)),
+            &values,
+        );
+
+        for level in 1 .. SHAPE.rows() {
+            frontier = Self::best_extensions(
+                frontier.iter().flat_map(|partial| {
+                    Self::next_rows(&partial.rows, level).into_iter().map(move |row| (partial, row))
+                }),
+                &values,
+            );
+        }
+
+        // Every partial left standing gives the same grid, so any of them will do
+        let transform = frontier.swap_remove(0).to_transform();
+
+        Ok(Self {
+            grid: transform.apply(grid)?,
+            transform,
+        })
+    }
+
+    pub fn grid(&self) -> &Grid {
+        &self.grid
+    }
+
+    /// The transform that turns the original grid into the canonical one
+    pub fn transform(&self) -> &Transform {
+        &self.transform
+    }
+
+    /// The canonical grid in the one-line format, suitable for use as a lookup key
+    pub fn key(&self) -> String {
+        self.grid.to_line()
+    }
+
+    /// Whether the grid's rules are exactly the rows, columns and subgrids, however they're given
+    fn has_standard_rules(grid: &Grid) -> bool {
+        let groups = |constraints: &[Arc<dyn Constraint>]| {
+            let mut groups: Vec<Vec<usize>> = constraints.iter()
+                .map(|constraint| {
+                    let mut cells: Vec<usize> = constraint.affected_cells(&SHAPE).iter().map(|grid_ref| grid_ref.to_index(&SHAPE)).collect();
+                    cells.sort();
+                    cells
+                })
+                .collect();
+            groups.sort();
+            groups
+        };
+
+        grid.regions().is_standard()
+            && grid.constraints().iter().all(|constraint| constraint.is_house(&SHAPE))
+            && groups(grid.constraints()) == groups(&standard_constraints(&SHAPE))
+    }
+
+    /// Extends each partial with its row, keeping only the ones that give the smallest row
+    fn best_extensions<'a>(candidates: impl Iterator<Item = (&'a Partial, usize)>, values: &[u8]) -> Vec<Partial> {
+        let mut best_row: Option<Vec<u8>> = None;
+        let mut best: Vec<Partial> = Vec::new();
+
+        for (partial, row) in candidates {
+            let Some((row_values, extended)) = partial.extend(row, values, best_row.as_deref()) else {
+                continue;
+            };
+
+            match best_row.as_deref().map(|best_row| row_values.as_slice().cmp(best_row)) {
+                Some(Ordering::Greater) => {},
+                Some(Ordering::Equal) => best.push(extended),
+                _ => {
+                    best_row = Some(row_values);
+                    best.clear();
+                    best.push(extended);
+                },
+            }
+        }
+
+        best
+    }
+
+    fn values_of(grid: &Grid) -> Vec<u8> {
+        let mut values = Vec::with_capacity(SHAPE.rows() * SHAPE.columns());
+
+        for row in 0 .. SHAPE.rows() {
+            for column in 0 .. SHAPE.columns() {
+                let grid_ref = GridReference::from_numbers(row, column)
+                    .expect("Row and column are always in range");
+                values.push(grid.cell(&grid_ref).unwrap_or(0));
+            }
+        }
+
+        values
+    }
+
+    /// The rows that can come next: any row from an unused band when starting a new band, and
+    /// otherwise one of the remaining rows of the current band
+    fn next_rows(chosen: &[usize], level: usize) -> Vec<usize> {
+        let available = |row: &usize| !chosen.contains(row);
+
+        if level.is_multiple_of(SHAPE.subgrid_rows()) {
+            (0 .. SHAPE.rows()).filter(available).collect()
+        } else {
+            let band = chosen[level - 1] / SHAPE.subgrid_rows();
+            (band * SHAPE.subgrid_rows() .. (band + 1) * SHAPE.subgrid_rows()).filter(available).collect()
+        }
+    }
+
+    /*
+     * With no digits labelled yet, the first row's digits are always labelled 1, 2, 3 ... in the
+     * order they appear, so only the positions of the empty cells matter and the smallest first
+     * row is the one with its empty cells as far forward as possible.  That means empty cells first
+     * within each stack, and stacks with more empty cells first.  We generate every column order
+     * that does that (ties can be arranged in any order), as the rows that follow will pick
+     * between them
+     */
+    fn first_row_column_orders(values: &[u8], transpose: bool, row: usize) -> Vec<Vec<usize>> {
+        let is_empty = |column: usize| {
+            let index = if transpose { column * SHAPE.columns() + row } else { row * SHAPE.columns() + column };
+            values[index] == 0
+        };
+
+        let stacks: Vec<usize> = (0 .. SHAPE.stack_count()).collect();
+        let empty_count = |stack: usize| {
+            (stack * SHAPE.subgrid_columns() .. (stack + 1) * SHAPE.subgrid_columns()).filter(|column| is_empty(*column)).count()
+        };
+
+        // Every arrangement of each stack's columns with the empty cells first
+        let stack_arrangements: Vec<Vec<Vec<usize>>> = stacks.iter()
+            .map(|stack| {
+                let columns = *stack * SHAPE.subgrid_columns() .. (*stack + 1) * SHAPE.subgrid_columns();
+                let empty: Vec<usize> = columns.clone().filter(|column| is_empty(*column)).collect();
+                let filled: Vec<usize> = columns.filter(|column| !is_empty(*column)).collect();
+                let filled_permutations = Self::permutations(&filled);
+
+                Self::permutations(&empty)
+                    .iter()
+                    .flat_map(|empty_order| filled_permutations.iter().map(move |filled_order| [empty_order.as_slice(), filled_order].concat()))
+                    .collect()
+            })
+            .collect();
+
+        let mut orders = Vec::new();
+        for stack_order in Self::permutations(&stacks) {
+            let descending = stack_order.windows(2).all(|pair| empty_count(pair[0]) >= empty_count(pair[1]));
+            if !descending {
+                continue;
+            }
+
+            let mut partial_orders: Vec<Vec<usize>> = vec![Vec::new()];
+            for stack in stack_order {
+                partial_orders = partial_orders.iter()
+                    .flat_map(|order| stack_arrangements[stack].iter().map(move |columns| [order.as_slice(), columns].concat()))
+                    .collect();
+            }
+            orders.extend(partial_orders);
+        }
+
+        orders
+    }
+
+    fn permutations(items: &[usize]) -> Vec<Vec<usize>> {
+        if items.len() <= 1 {
+            return vec![items.to_vec()];
+        }
+
+        let mut permutations = Vec::new();
+        for index in 0 .. items.len() {
+            let mut rest = items.to_vec();
+            let first = rest.remove(index);
+            for mut permutation in Self::permutations(&rest) {
+                permutation.insert(0, first);
+                permutations.push(permutation);
+            }
+        }
+
+        permutations
+    }
+}
+
+impl Partial {
+    fn new(transpose: bool, columns: Vec<usize>) -> Self {
+        Self {
+            transpose,
+            rows: Vec::with_capacity(SHAPE.rows()),
+            columns,
+            labels: [0; 10],
+            next_label: 1,
+        }
+    }
+
+    /*
+     * Work out what the next row of the canonical grid would be if it came from the given row,
+     * relabelling any digits we haven't seen before.  We give up as soon as the row is known to
+     * be worse than the best found so far at this level
+     */
+    fn extend(&self, row: usize, values: &[u8], best: Option<&[u8]>) -> Option<(Vec<u8>, Partial)> {
+        let mut labels = self.labels;
+        let mut next_label = self.next_label;
+        let mut row_values = Vec::with_capacity(self.columns.len());
+        let mut still_tied = best.is_some();
+
+        for (position, column) in self.columns.iter().enumerate() {
+            let value = if self.transpose {
+                values[column * SHAPE.columns() + row]
+            } else {
+                values[row * SHAPE.columns() + column]
+            };
+
+            let label = if value == 0 {
+                0
+            } else {
+                if labels[value as usize] == 0 {
+                    labels[value as usize] = next_label;
+                    next_label += 1;
+                }
+                labels[value as usize]
+            };
+
+            if still_tied {
+                match label.cmp(&best.expect("Only tied when there's a best row")[position]) {
+                    Ordering::Greater => return None,
+                    Ordering::Less => still_tied = false,
+                    Ordering::Equal => {},
+                }
+            }
+            row_values.push(label);
+        }
+
+        let mut rows = self.rows.clone();
+        rows.push(row);
+
+        Some((row_values, Partial { rows, labels, next_label, ..self.clone() }))
+    }
+
+    fn to_transform(&self) -> Transform {
+        // Digits that don't appear in the grid can go anywhere, so give them the remaining labels
+        // in order
+        let mut labels = self.labels;
+        let mut next_label = self.next_label;
+        for label in labels.iter_mut().skip(1) {
+            if *label == 0 {
+                *label = next_label;
+                next_label += 1;
+            }
+        }
+
+        Transform::from_parts(self.transpose, self.rows.clone(), self.columns.clone(), labels[1 ..].to_vec())
+    }
+}
+
+/// If the grids are equivalent, returns a transform that turns the first into the second.  Only
+/// standard grids following the standard rules can be compared
+pub fn are_equivalent(first: &Grid, second: &Grid) -> Option<Transform> {
+    if first.clue_count() != second.clue_count() {
+        return None;
+    }
+
+    let first_form = CanonicalForm::of(first).ok()?;
+    let second_form = CanonicalForm::of(second).ok()?;
+    if first_form.key() != second_form.key() {
+        return None;
+    }
+
+    Some(first_form.transform().then(&second_form.transform().inverse()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sudoku::constraint::UniqueGroup;
+    use crate::sudoku::error::UniquenessConstraint;
+    use crate::sudoku::random::SeededRng;
+
+    const PUZZLE: &str = "53..7....6..195....98....6.8...6...34..8.3..17...2...6.6....28....419..5....8..79";
+
+    #[test]
+    fn equivalent_puzzles_share_a_form() {
+        let puzzle: Grid = PUZZLE.parse().unwrap();
+        let key = CanonicalForm::of(&puzzle).unwrap().key();
+        let mut rng = SeededRng::new(1);
+
+        for _ in 0 .. 20 {
+            let transformed = Transform::random(&mut rng).apply(&puzzle).unwrap();
+            assert_eq!(CanonicalForm::of(&transformed).unwrap().key(), key);
+
+            let transform = are_equivalent(&puzzle, &transformed).unwrap();
+            assert_eq!(transform.apply(&puzzle).unwrap().to_line(), transformed.to_line());
+        }
+    }
+
+    #[test]
+    fn the_form_is_reached_by_its_transform() {
+        let puzzle: Grid = PUZZLE.parse().unwrap();
+        let form = CanonicalForm::of(&puzzle).unwrap();
+
+        assert_eq!(form.transform().apply(&puzzle).unwrap().to_line(), form.key());
+    }
+
+    #[test]
+    fn different_puzzles_have_different_forms() {
+        let puzzle: Grid = PUZZLE.parse().unwrap();
+        // Swapping the first two clues keeps the pattern of givens but not the puzzle
+        let changed: Grid = PUZZLE.replacen("53", "35", 1).parse().unwrap();
+
+        assert_ne!(CanonicalForm::of(&puzzle).unwrap().key(), CanonicalForm::of(&changed).unwrap().key());
+        assert!(are_equivalent(&puzzle, &changed).is_none());
+    }
+
+    #[test]
+    fn rejects_other_shapes() {
+        let small = Grid::with_shape(GridShape::new(2, 2).unwrap());
+
+        assert!(CanonicalForm::of(&small).unwrap_err().is::<NonStandardShape>());
+        assert!(are_equivalent(&small, &small).is_none());
+    }
+
+    #[test]
+    fn rejects_grids_with_other_rules() {
+        let puzzle: Grid = PUZZLE.parse().unwrap();
+        let mut grid = puzzle.clone();
+        let part_of_a_row = (0 .. 3).map(|column| GridReference::from_numbers(4, column).unwrap()).collect();
+        grid.add_constraint(UniqueGroup::new(UniquenessConstraint::Row, part_of_a_row)).unwrap();
+
+        assert!(CanonicalForm::of(&grid).unwrap_err().is::<NonStandardRules>());
+        assert!(are_equivalent(&grid, &puzzle).is_none());
+        assert!(CanonicalForm::of(&puzzle).is_ok());
+    }
+}
@@ -0,0 +1,522 @@
+use crate::sudoku::grid::Grid;
+use crate::sudoku::sat::{CardinalityEncoding, Cnf, Literal};
+use crate::sudoku::solver::{Solver, SolverBuilder};
+use std::collections::BinaryHeap;
+use std::error::Error;
+
+/*
+ * Solves puzzles by encoding them as CNF and running a conflict-driven clause-learning search over
+ * the clauses, rather than searching the grid directly like the depth-first solver.  Variants whose
+ * rules are awkward to prune by hand, such as killer cages mixed with thermometers and the
+ * anti-knight rule, are often quicker to solve this way, as every dead end teaches the search a
+ * clause that stops it walking into the same trap again
+ */
+#[derive(Debug)]
+pub struct CdclSolver<'problem> {
+    problem: &'problem Grid,
+    cnf: Cnf,
+    solution: Option<Grid>,
+}
+
+impl<'problem> CdclSolver<'problem> {
+    /// Fails if any of the problem's constraints can't be encoded as CNF
+    pub fn new(problem: &'problem Grid, encoding: CardinalityEncoding) -> Result<Self, Box<dyn Error>> {
+        Ok(Self {
+            problem,
+            cnf: Cnf::from_grid(problem, encoding)?,
+            solution: None,
+        })
+    }
+
+    /// Shorthand for the common case of solving with the sequential encoding, which keeps the
+    /// number of clauses down without adding too many variables
+    pub fn for_problem(problem: &'problem Grid) -> Result<Self, Box<dyn Error>> {
+        Self::new(problem, CardinalityEncoding::Sequential)
+    }
+
+    /// Builds CDCL solvers with the sequential encoding, for handing to the generator and the like
+    pub fn builder() -> SolverBuilder {
+        |problem| Ok(Box::new(CdclSolver::for_problem(problem)?))
+    }
+}
+
+impl Solver for CdclSolver<'_> {
+    fn solve(&mut self) -> &mut dyn Solver {
+        self.solution = self.find_solutions(1).pop();
+        self
+    }
+
+    /*
+     * After each solution is found it's ruled out with a clause saying at least one of the empty
+     * cells must hold a different value, and the search carries on from there, keeping everything
+     * it has learnt so far
+     */
+    fn find_solutions(&mut self, limit: usize) -> Vec<Grid> {
+        let mut engine = Engine::new(self.cnf.variable_count(), self.cnf.clauses());
+        let mut found: Vec<Grid> = Vec::new();
+        let shape = *self.problem.shape();
+
+        while found.len() < limit {
+            let Some(model) = engine.search() else {
+                break;
+            };
+
+            let solution = match self.cnf.solution_from_assignment(self.problem, &model) {
+                Ok(solution) => solution,
+                Err(err) => panic!("{}", err),
+            };
+
+            let blocking: Vec<Literal> = shape.grid_refs()
+                .filter(|grid_ref| self.problem.cell(grid_ref).is_none())
+                .map(|grid_ref| -self.cnf.variable(&grid_ref, solution.cell(&grid_ref).expect("Every cell of a solution is filled")))
+                .collect();
+            engine.add_clause(&blocking);
+            found.push(solution);
+        }
+
+        found
+    }
+
+    fn get_solution(&self) -> &Option<Grid> {
+        &self.solution
+    }
+}
+
+/// How many conflicts make up one unit of the Luby restart sequence
+const RESTART_UNIT: u64 = 100;
+
+/// How quickly the activity of variables that haven't been in conflicts lately fades
+const ACTIVITY_DECAY: f64 = 0.95;
+
+/*
+ * The search itself.  Literals are numbered internally as twice the variable, plus one when
+ * negated, so that a literal and its negation sit next to each other.  It follows the usual shape
+ * of a CDCL solver: two watched literals per clause for propagation, first UIP conflict analysis
+ * with non-chronological backjumping, VSIDS-style activity for picking the next variable, saved
+ * phases, and restarts on the Luby sequence
+ */
+#[derive(Debug)]
+struct Engine {
+    clauses: Vec<Vec<usize>>,
+    // For each literal, the clauses watching it, which need a look when it becomes false
+    watches: Vec<Vec<usize>>,
+    values: Vec<Option<bool>>,
+    levels: Vec<usize>,
+    // The clause that forced each variable's value, or None for decisions and top level facts
+    reasons: Vec<Option<usize>>,
+    trail: Vec<usize>,
+    // Where each decision level starts on the trail
+    trail_limits: Vec<usize>,
+    propagated: usize,
+    activity: Vec<f64>,
+    increment: f64,
+    // Ordered by activity, with stale entries skipped when they come up
+    order: BinaryHeap<(u64, usize)>,
+    phases: Vec<bool>,
+    seen: Vec<bool>,
+    unsatisfiable: bool,
+}
+
+impl Engine {
+    fn new(variable_count: usize, clauses: &[Vec<Literal>]) -> Self {
+        let mut engine = Self {
+            clauses: Vec::new(),
+            watches: vec![Vec::new(); 2 * (variable_count + 1)],
+            values: vec![None; variable_count + 1],
+            levels: vec![0; variable_count + 1],
+            reasons: vec![None; variable_count + 1],
+            trail: Vec::new(),
+            trail_limits: Vec::new(),
+            propagated: 0,
+            activity: vec![0.0; variable_count + 1],
+            increment: 1.0,
+            order: (1 ..= variable_count).map(|variable| (0, variable)).collect(),
+            phases: vec![false; variable_count + 1],
+            seen: vec![false; variable_count + 1],
+            unsatisfiable: false,
+        };
+
+        for clause in clauses {
+            engine.add_clause(clause);
+        }
+
+        engine
+    }
+
+    fn value_of(&self, literal: usize) -> Option<bool> {
+        self.values[literal >> 1].map(|value| value == (literal & 1 == 0))
+    }
+
+    /*
+     * Adds a clause between searches, back at the top level.  Literals already false there are
+     * dropped and a clause with a literal already true is met, so nothing needs adding.  That
+     * leaves the empty clause, which means there's nothing left to find, a unit clause, whose
+     * literal can be set straight away, or a clause to watch
+     */
+    fn add_clause(&mut self, clause: &[Literal]) {
+        self.backtrack(0);
+        if self.unsatisfiable {
+            return;
+        }
+
+        let mut literals: Vec<usize> = Vec::with_capacity(clause.len());
+        for literal in clause.iter().map(|literal| literal.unsigned_abs() as usize * 2 + usize::from(*literal < 0)) {
+            match self.value_of(literal) {
+                Some(true) => return,
+                Some(false) => continue,
+                None if literals.contains(&(literal ^ 1)) => return,
+                None if !literals.contains(&literal) => literals.push(literal),
+                None => {},
+            }
+        }
+
+        match literals.len() {
+            0 => self.unsatisfiable = true,
+            1 => {
+                self.assign(literals[0], None);
+                if self.propagate().is_some() {
+                    self.unsatisfiable = true;
+                }
+            },
+            _ => {
+                self.attach(literals);
+            },
+        }
+    }
+
+    fn attach(&mut self, literals: Vec<usize>) -> usize {
+        let index = self.clauses.len();
+        self.watches[literals[0]].push(index);
+        self.watches[literals[1]].push(index);
+        self.clauses.push(literals);
+
+        index
+    }
+
+    fn assign(&mut self, literal: usize, reason: Option<usize>) {
+        let variable = literal >> 1;
+        self.values[variable] = Some(literal & 1 == 0);
+        self.levels[variable] = self.trail_limits.len();
+        self.reasons[variable] = reason;
+        self.trail.push(literal);
+    }
+
+    /*
+     * Follows the consequences of everything on the trail not yet looked at, returning the clause
+     * that ends up with every literal false if there is one.  The watched literals of each clause
+     * are kept in its first two places, and the literal a clause forces is always moved to the
+     * front, which conflict analysis relies on
+     */
+    fn propagate(&mut self) -> Option<usize> {
+        while self.propagated < self.trail.len() {
+            let false_literal = self.trail[self.propagated] ^ 1;
+            self.propagated += 1;
+
+            let watching = std::mem::take(&mut self.watches[false_literal]);
+            let mut kept = Vec::with_capacity(watching.len());
+            let mut conflict = None;
+
+            for (position, index) in watching.iter().copied().enumerate() {
+                let clause = &mut self.clauses[index];
+                if clause[0] == false_literal {
+                    clause.swap(0, 1);
+                }
+                let other = clause[0];
+
+                if self.value_of(other) == Some(true) {
+                    kept.push(index);
+                    continue;
+                }
+
+                let replacement = (2 .. self.clauses[index].len())
+                    .find(|place| self.value_of(self.clauses[index][*place]) != Some(false));
+                if let Some(place) = replacement {
+                    self.clauses[index].swap(1, place);
+                    let watched = self.clauses[index][1];
+                    self.watches[watched].push(index);
+                    continue;
+                }
+
+                kept.push(index);
+                if self.value_of(other) == Some(false) {
+                    kept.extend_from_slice(&watching[position + 1 ..]);
+                    conflict = Some(index);
+                    break;
+                }
+                self.assign(other, Some(index));
+            }
+
+            self.watches[false_literal] = kept;
+            if conflict.is_some() {
+                return conflict;
+            }
+        }
+
+        None
+    }
+
+    /*
+     * Works back from the conflict through the clauses that forced each value until only one
+     * literal from the current decision level is left (the first unique implication point).  The
+     * learnt clause is that literal's negation, first, along with the negations of the earlier
+     * levels' literals that played a part.  It returns the clause and the level to jump back to,
+     * which is the latest of those earlier levels
+     */
+    fn analyze(&mut self, conflict: usize) -> (Vec<usize>, usize) {
+        let level = self.trail_limits.len();
+        let mut learnt = vec![0];
+        let mut pending = 0;
+        let mut clause = conflict;
+        let mut position = self.trail.len();
+        let mut implied: Option<usize> = None;
+
+        loop {
+            let skip = usize::from(implied.is_some());
+            for place in skip .. self.clauses[clause].len() {
+                let literal = self.clauses[clause][place];
+                let variable = literal >> 1;
+                if self.seen[variable] || self.levels[variable] == 0 {
+                    continue;
+                }
+
+                self.seen[variable] = true;
+                self.bump(variable);
+                if self.levels[variable] == level {
+                    pending += 1;
+                } else {
+                    learnt.push(literal);
+                }
+            }
+
+            // The next literal to resolve on is the latest one seen on the trail
+            loop {
+                position -= 1;
+                if self.seen[self.trail[position] >> 1] {
+                    break;
+                }
+            }
+            let literal = self.trail[position];
+            self.seen[literal >> 1] = false;
+            implied = Some(literal);
+            pending -= 1;
+
+            if pending == 0 {
+                break;
+            }
+            clause = self.reasons[literal >> 1].expect("Only decisions have no reason, and there's one per level");
+        }
+
+        learnt[0] = implied.expect("The conflict always involves the current level") ^ 1;
+        for literal in learnt.iter() {
+            self.seen[literal >> 1] = false;
+        }
+
+        // The literal from the latest earlier level goes second, so it's watched after the jump
+        let mut backjump = 0;
+        if learnt.len() > 1 {
+            let latest = (1 .. learnt.len())
+                .max_by_key(|place| self.levels[learnt[*place] >> 1])
+                .expect("There's more than one literal");
+            learnt.swap(1, latest);
+            backjump = self.levels[learnt[1] >> 1];
+        }
+
+        (learnt, backjump)
+    }
+
+    fn bump(&mut self, variable: usize) {
+        self.activity[variable] += self.increment;
+        if self.activity[variable] > 1e100 {
+            for activity in self.activity.iter_mut() {
+                *activity *= 1e-100;
+            }
+            self.increment *= 1e-100;
+            self.rebuild_order();
+        }
+
+        self.order.push((self.activity[variable].to_bits(), variable));
+    }
+
+    /// Every bump leaves a stale entry behind, so the order is rebuilt from scratch every so often
+    fn rebuild_order(&mut self) {
+        self.order = (1 .. self.values.len())
+            .filter(|variable| self.values[*variable].is_none())
+            .map(|variable| (self.activity[variable].to_bits(), variable))
+            .collect();
+    }
+
+    fn backtrack(&mut self, level: usize) {
+        if self.trail_limits.len() <= level {
+            return;
+        }
+
+        for literal in self.trail.drain(self.trail_limits[level] ..) {
+            let variable = literal >> 1;
+            self.phases[variable] = literal & 1 == 0;
+            self.values[variable] = None;
+            self.reasons[variable] = None;
+            self.order.push((self.activity[variable].to_bits(), variable));
+        }
+
+        self.trail_limits.truncate(level);
+        self.propagated = self.trail.len();
+    }
+
+    /// The most active unassigned variable, set the way it was last time, or None once every
+    /// variable has a value
+    fn pick_branch(&mut self) -> Option<usize> {
+        if self.order.len() > 10 * self.values.len() {
+            self.rebuild_order();
+        }
+
+        while let Some((_, variable)) = self.order.pop() {
+            if self.values[variable].is_none() {
+                return Some(variable * 2 + usize::from(!self.phases[variable]));
+            }
+        }
+
+        None
+    }
+
+    /// Searches for an assignment that meets every clause, returning which variables are true, or
+    /// None if there's no such assignment
+    fn search(&mut self) -> Option<Vec<bool>> {
+        let mut conflicts = 0;
+        let mut restarts = 0;
+        let mut restart_at = luby(restarts) * RESTART_UNIT;
+
+        loop {
+            if self.unsatisfiable {
+                return None;
+            }
+
+            if let Some(conflict) = self.propagate() {
+                if self.trail_limits.is_empty() {
+                    self.unsatisfiable = true;
+                    continue;
+                }
+
+                conflicts += 1;
+                let (learnt, level) = self.analyze(conflict);
+                self.backtrack(level);
+
+                let asserting = learnt[0];
+                let reason = (learnt.len() > 1).then(|| self.attach(learnt));
+                self.assign(asserting, reason);
+                self.increment /= ACTIVITY_DECAY;
+            } else if conflicts >= restart_at {
+                self.backtrack(0);
+                restarts += 1;
+                restart_at = conflicts + luby(restarts) * RESTART_UNIT;
+            } else {
+                let Some(decision) = self.pick_branch() else {
+                    return Some(self.values.iter().map(|value| *value == Some(true)).collect());
+                };
+
+                self.trail_limits.push(self.trail.len());
+                self.assign(decision, None);
+            }
+        }
+    }
+}
+
+/// The Luby sequence 1, 1, 2, 1, 1, 2, 4, 1, 1, 2, ... counting from 0, which mixes in the odd
+/// long run between lots of short ones
+fn luby(mut index: u64) -> u64 {
+    let (mut size, mut power) = (1, 0);
+    while size < index + 1 {
+        power += 1;
+        size = 2 * size + 1;
+    }
+
+    while size - 1 != index {
+        size = (size - 1) >> 1;
+        power -= 1;
+        index %= size;
+    }
+
+    1 << power
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sudoku::cage::Cage;
+    use crate::sudoku::constraint::diagonal_constraints;
+    use crate::sudoku::generator::Generator;
+    use crate::sudoku::minimiser::Minimiser;
+    use crate::sudoku::reference::GridReference;
+    use crate::sudoku::shape::GridShape;
+    use crate::sudoku::solver::DepthFirstSolver;
+
+    const PUZZLE: &str = "53..7....6..195....98....6.8...6...34..8.3..17...2...6.6....28....419..5....8..79";
+    const SOLUTION: &str = "534678912672195348198342567859761423426853791713924856961537284287419635345286179";
+
+    #[test]
+    fn solves_a_unique_puzzle() {
+        let puzzle: Grid = PUZZLE.parse().unwrap();
+        let mut solver = CdclSolver::for_problem(&puzzle).unwrap();
+
+        assert!(solver.has_unique_solution());
+        let solution = solver.solve().get_solution().clone().unwrap();
+        assert_eq!(solution.to_line(), SOLUTION);
+    }
+
+    #[test]
+    fn finds_nothing_for_an_unsatisfiable_puzzle() {
+        // The puzzle above with the 7 in the top row changed to a 4, which breaks nothing at the
+        // start but leaves no solution
+        let puzzle: Grid = PUZZLE.replacen('7', "4", 1).parse().unwrap();
+        let mut solver = CdclSolver::for_problem(&puzzle).unwrap();
+
+        assert_eq!(solver.count_solutions(2), 0);
+        assert!(solver.solve().get_solution().is_none());
+    }
+
+    #[test]
+    fn counts_match_the_depth_first_solver() {
+        let mut sparse: Grid = PUZZLE.parse().unwrap();
+        for grid_ref in sparse.shape().grid_refs().take(18) {
+            sparse.clear_cell(&grid_ref);
+        }
+        let puzzles = [Grid::with_shape(GridShape::new(2, 2).unwrap()), sparse];
+
+        for puzzle in puzzles.iter() {
+            for limit in [1, 7, 300] {
+                assert_eq!(
+                    CdclSolver::for_problem(puzzle).unwrap().count_solutions(limit),
+                    DepthFirstSolver::for_problem(puzzle).count_solutions(limit),
+                );
+            }
+        }
+    }
+    #[test]
+    fn every_encoding_counts_the_same_solutions() {
+        let shape = GridShape::new(2, 2).unwrap();
+        let cell = |row, column| GridReference::for_shape(&shape, row, column).unwrap();
+
+        let mut caged = Grid::with_shape(shape);
+        caged.add_constraint(Cage::new(3, vec![cell(0, 0), cell(0, 1)]).unwrap()).unwrap();
+        caged.add_constraint(Cage::new(7, vec![cell(2, 3), cell(3, 3)]).unwrap()).unwrap();
+        let puzzles = [Grid::with_shape(shape), Grid::with_constraints(shape, diagonal_constraints(&shape)), caged];
+
+        for puzzle in puzzles.iter() {
+            let expected = DepthFirstSolver::for_problem(puzzle).count_solutions(300);
+
+            for encoding in [CardinalityEncoding::Pairwise, CardinalityEncoding::Sequential, CardinalityEncoding::Binary] {
+                let count = CdclSolver::new(puzzle, encoding).unwrap().count_solutions(300);
+                assert_eq!(count, expected, "{:?} encoding of {}", encoding, puzzle.to_line());
+            }
+        }
+    }
+
+    #[test]
+    fn the_generator_and_minimiser_can_use_it() {
+        let mut generator = Generator::new(12);
+        generator.set_shape(GridShape::new(2, 2).unwrap()).set_solver(CdclSolver::builder());
+        let generated = generator.generate().unwrap();
+
+        assert!(CdclSolver::for_problem(generated.puzzle()).unwrap().has_unique_solution());
+        assert!(Minimiser::with_solver(generated.puzzle(), CdclSolver::builder()).unwrap().is_minimal());
+    }
+}
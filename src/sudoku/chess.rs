@@ -0,0 +1,167 @@
+use crate::sudoku::constraint::{CandidateSet, Constraint};
+use crate::sudoku::error::{UniquenessConstraint, UniquenessError};
+use crate::sudoku::formula::{Formula, Term};
+use crate::sudoku::grid::Grid;
+use crate::sudoku::reference::GridReference;
+use crate::sudoku::sat::Cnf;
+use crate::sudoku::shape::GridShape;
+use crate::sudoku::transform::Transform;
+use std::error::Error;
+use std::sync::Arc;
+
+/*
+ * Rules taken from chess pieces: no two cells a single move apart may hold the same value.  These
+ * apply across the whole grid, on top of the standard rules.  Only a king's diagonal moves count,
+ * as its other moves stay within a row or column, where values can't repeat anyway
+ */
+#[derive(Debug, Clone)]
+pub struct ChessConstraint {
+    kind: UniquenessConstraint,
+    moves: &'static [(isize, isize)],
+}
+
+impl ChessConstraint {
+    /// No equal values a knight's move apart
+    pub fn anti_knight() -> Self {
+        Self {
+            kind: UniquenessConstraint::AntiKnight,
+            moves: &[(-2, -1), (-2, 1), (-1, -2), (-1, 2), (1, -2), (1, 2), (2, -1), (2, 1)],
+        }
+    }
+
+    /// No equal values diagonally adjacent
+    pub fn anti_king() -> Self {
+        Self {
+            kind: UniquenessConstraint::AntiKing,
+            moves: &[(-1, -1), (-1, 1), (1, -1), (1, 1)],
+        }
+    }
+
+    pub fn kind(&self) -> &UniquenessConstraint {
+        &self.kind
+    }
+
+    /// The cells a single move away from the given one
+    fn reachable_from(&self, shape: &GridShape, grid_ref: &GridReference) -> impl Iterator<Item = GridReference> {
+        let shape = *shape;
+        let (row, column) = (grid_ref.row(), grid_ref.column());
+
+        self.moves.iter().filter_map(move |(rows, columns)| {
+            let row = row.checked_add_signed(*rows).filter(|row| *row < shape.rows())?;
+            let column = column.checked_add_signed(*columns).filter(|column| *column < shape.columns())?;
+
+            Some(GridReference::for_shape(&shape, row, column).expect("Row and column are always in range"))
+        })
+    }
+}
+
+impl Constraint for ChessConstraint {
+    fn affected_cells(&self, shape: &GridShape) -> Vec<GridReference> {
+        shape.grid_refs().collect()
+    }
+
+    fn check(&self, grid: &Grid, changed: &GridReference) -> Result<(), Box<dyn Error>> {
+        let Some(value) = *grid.cell(changed) else {
+            return Ok(());
+        };
+
+        let repeated = self.reachable_from(grid.shape(), changed).any(|grid_ref| *grid.cell(&grid_ref) == Some(value));
+        if repeated {
+            return Err(UniquenessError::new(changed.row(), changed.column(), value, self.kind.clone()).into());
+        }
+
+        Ok(())
+    }
+
+    fn prune(&self, grid: &Grid, candidates: &mut [CandidateSet]) {
+        let shape = grid.shape();
+
+        for grid_ref in shape.grid_refs() {
+            let Some(value) = *grid.cell(&grid_ref) else {
+                continue;
+            };
+
+            for reachable in self.reachable_from(shape, &grid_ref) {
+                if grid.cell(&reachable).is_none() {
+                    candidates[reachable.to_index(shape)] &= !(1 << (value - 1));
+                }
+            }
+        }
+    }
+
+    fn encode(&self, shape: &GridShape, cnf: &mut Cnf) -> bool {
+        for grid_ref in shape.grid_refs() {
+            // Each pair of cells a move apart is only encoded once, from the earlier cell
+            let index = grid_ref.to_index(shape);
+            for reachable in self.reachable_from(shape, &grid_ref).filter(|reachable| reachable.to_index(shape) > index) {
+                cnf.forbid_pairs(&grid_ref, &reachable, |value, other| value != other);
+            }
+        }
+
+        true
+    }
+
+    fn formulas(&self, shape: &GridShape) -> Option<Vec<Formula>> {
+        let mut formulas = Vec::new();
+        for grid_ref in shape.grid_refs() {
+            let index = grid_ref.to_index(shape);
+            for reachable in self.reachable_from(shape, &grid_ref).filter(|reachable| reachable.to_index(shape) > index) {
+                formulas.push(Formula::NotEqual(Term::Cell(grid_ref.clone()), Term::Cell(reachable)));
+            }
+        }
+
+        Some(formulas)
+    }
+
+    /// Values don't matter here, only whether cells a move apart are still a move apart.  Turning
+    /// the grid keeps them that way, but swapping rows or columns usually doesn't
+    fn transformed(&self, shape: &GridShape, transform: &Transform) -> Option<Arc<dyn Constraint>> {
+        let keeps_moves = transform.keeps_relation(|grid_ref| self.reachable_from(shape, grid_ref).collect());
+
+        keeps_moves.then(|| Arc::new(self.clone()) as Arc<dyn Constraint>)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sudoku::grid::CellValue;
+
+    fn cell(row: usize, column: usize) -> GridReference {
+        GridReference::from_numbers(row, column).unwrap()
+    }
+
+    #[test]
+    fn rejects_equal_values_a_move_apart() {
+        let mut grid = Grid::new();
+        grid.add_constraint(ChessConstraint::anti_knight()).unwrap();
+        grid.add_constraint(ChessConstraint::anti_king()).unwrap();
+        grid.set_cell(&cell(2, 2), &CellValue::new(5).unwrap()).unwrap();
+
+        assert!(grid.clone().set_cell(&cell(4, 3), &CellValue::new(5).unwrap()).unwrap_err().to_string().contains("knight"));
+        assert!(grid.clone().set_cell(&cell(3, 3), &CellValue::new(5).unwrap()).unwrap_err().to_string().contains("king"));
+        assert!(grid.set_cell(&cell(5, 5), &CellValue::new(5).unwrap()).is_ok());
+    }
+
+    #[test]
+    fn prunes_the_cells_a_move_away() {
+        let mut grid = Grid::new();
+        grid.set_cell(&cell(0, 0), &CellValue::new(1).unwrap()).unwrap();
+
+        let mut candidates = vec![0b1_1111_1111; 81];
+        ChessConstraint::anti_knight().prune(&grid, &mut candidates);
+
+        assert_eq!(candidates[GridShape::standard().index_of(1, 2)], 0b1_1111_1110);
+        assert_eq!(candidates[GridShape::standard().index_of(2, 1)], 0b1_1111_1110);
+        assert_eq!(candidates[GridShape::standard().index_of(1, 1)], 0b1_1111_1111);
+    }
+
+    #[test]
+    fn turns_with_the_grid_but_not_with_swapped_rows() {
+        let shape = GridShape::standard();
+        let knight = ChessConstraint::anti_knight();
+
+        assert!(knight.transformed(&shape, &Transform::rotate_clockwise()).is_some());
+        assert!(knight.transformed(&shape, &Transform::swap_rows(0, 1).unwrap()).is_none());
+    }
+}
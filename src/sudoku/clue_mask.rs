@@ -0,0 +1,116 @@
+use crate::sudoku::error::{InvalidMaskCharacter, InvalidMaskLength};
+use crate::sudoku::grid::Grid;
+use crate::sudoku::reference::GridReference;
+use crate::sudoku::shape::GridShape;
+use std::error::Error;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::str::FromStr;
+
+/// Marks which cells of a puzzle should hold the givens
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ClueMask {
+    shape: GridShape,
+    cells: Vec<bool>,
+}
+
+impl ClueMask {
+    /// A mask for the standard 9 x 9 grid
+    pub fn new(cells: [[bool; 9]; 9]) -> Self {
+        Self {
+            shape: GridShape::standard(),
+            cells: cells.iter().flatten().copied().collect(),
+        }
+    }
+
+    /// A mask matching the clue pattern of an existing puzzle
+    pub fn from_grid(grid: &Grid) -> Self {
+        let shape = *grid.shape();
+
+        Self {
+            shape,
+            cells: shape.grid_refs().map(|grid_ref| grid.cell(&grid_ref).is_some()).collect(),
+        }
+    }
+
+    pub fn shape(&self) -> &GridShape {
+        &self.shape
+    }
+
+    pub fn is_clue(&self, grid_ref: &GridReference) -> bool {
+        self.cells[grid_ref.to_index(&self.shape)]
+    }
+
+    pub fn clue_count(&self) -> usize {
+        self.cells.iter().filter(|cell| **cell).count()
+    }
+}
+
+/*
+ * Masks are written out a character per cell, row by row, so 81 characters for the standard grid.
+ * Any of "X", "x", "#", "*" or "1" mark a clue, and any of ".", "-", "_" or "0" mark an empty cell.
+ * Whitespace is ignored so masks can be laid out as a block, which makes drawing shapes far easier.
+ * As with puzzles, the size of the grid is worked out from the number of cells
+ */
+impl FromStr for ClueMask {
+    type Err = Box<dyn Error>;
+
+    fn from_str(mask: &str) -> Result<Self, Self::Err> {
+        let mut cells = Vec::new();
+
+        for character in mask.chars().filter(|character| !character.is_whitespace()) {
+            match character {
+                'X' | 'x' | '#' | '*' | '1' => cells.push(true),
+                '.' | '-' | '_' | '0' => cells.push(false),
+                _ => return Err(InvalidMaskCharacter::new(cells.len(), character).into()),
+            }
+        }
+
+        let shape = GridShape::from_cell_count(cells.len())
+            .ok_or_else(|| InvalidMaskLength::new(cells.len()))?;
+
+        Ok(Self { shape, cells })
+    }
+}
+
+impl Display for ClueMask {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        for row in self.cells.chunks(self.shape.columns()) {
+            let line: String = row.iter().map(|cell| if *cell { 'X' } else { '.' }).collect();
+            writeln!(f, "{}", line)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MASK: &str = "
+        X...X...X
+        .X..X..X.
+        ..X.X.X..
+        ...XXX...
+        XXXX.XXXX
+        ...XXX...
+        ..X.X.X..
+        .X..X..X.
+        X...X...X";
+
+    #[test]
+    fn reads_and_writes_masks() {
+        let mask: ClueMask = MASK.parse().unwrap();
+
+        assert_eq!(mask.clue_count(), 32);
+        assert!(mask.is_clue(&GridReference::from_numbers(4, 0).unwrap()));
+        assert!(!mask.is_clue(&GridReference::from_numbers(4, 4).unwrap()));
+        assert_eq!(mask.to_string().parse::<ClueMask>().unwrap().to_string(), mask.to_string());
+    }
+
+    #[test]
+    fn rejects_malformed_masks() {
+        assert!(MASK.replacen('X', "?", 1).parse::<ClueMask>().unwrap_err().is::<InvalidMaskCharacter>());
+        assert!(MASK[.. MASK.len() - 1].parse::<ClueMask>().unwrap_err().is::<InvalidMaskLength>());
+    }
+}
@@ -0,0 +1,284 @@
+use crate::sudoku::error::NotUniquelySolvable;
+use crate::sudoku::grid::{CellValue, Grid};
+use crate::sudoku::rater::{Rater, Rating};
+use crate::sudoku::reference::GridReference;
+use crate::sudoku::solver::{DepthFirstSolver, SolverBuilder};
+use crate::sudoku::symmetry::Symmetry;
+use std::error::Error;
+
+/// A set of extra givens that makes a puzzle unique, along with the puzzle that results
+#[derive(Debug, Clone)]
+pub struct ClueSuggestion {
+    clues: Vec<(GridReference, u8)>,
+    puzzle: Grid,
+    kept_symmetries: Vec<Symmetry>,
+    rating: Rating,
+}
+
+impl ClueSuggestion {
+    pub fn clues(&self) -> &[(GridReference, u8)] {
+        &self.clues
+    }
+
+    pub fn puzzle(&self) -> &Grid {
+        &self.puzzle
+    }
+
+    /// The symmetries of the original clue pattern that are still present with the new clues
+    pub fn kept_symmetries(&self) -> &[Symmetry] {
+        &self.kept_symmetries
+    }
+
+    pub fn rating(&self) -> &Rating {
+        &self.rating
+    }
+}
+
+/*
+ * Suggests extra givens for a puzzle with more than one solution.
+ *
+ * We enumerate (up to a limit) the puzzle's solutions, pick one of them as the one to keep, then
+ * greedily add the clue from that solution that rules out the most of the others, until none are
+ * left.  That's the classic greedy approach to set cover, so it won't always find the smallest
+ * possible set but it gets close in practice.  As the enumeration may have been cut short, the
+ * result is checked with the solver and the process repeats if it still isn't unique.
+ *
+ * Each kept solution leads to a different suggestion.  When the original clue pattern is symmetric,
+ * we also try adding clues in symmetric groups so that the symmetry survives
+ */
+#[derive(Debug)]
+pub struct ClueSuggester<'problem> {
+    problem: &'problem Grid,
+    symmetries: Vec<Symmetry>,
+    // The score of the puzzle as it stands, which suggestions try to stay close to
+    original_score: usize,
+    solver: SolverBuilder,
+}
+
+impl<'problem> ClueSuggester<'problem> {
+    /// How many solutions to enumerate when working out which clues separate them
+    const SOLUTION_LIMIT: usize = 500;
+
+    pub fn new(problem: &'problem Grid) -> Result<Self, Box<dyn Error>> {
+        Self::with_solver(problem, DepthFirstSolver::builder())
+    }
+
+    /// Enumerates the puzzle's solutions, and those of the puzzles with clues added, with the
+    /// given solver
+    pub fn with_solver(problem: &'problem Grid, solver: SolverBuilder) -> Result<Self, Box<dyn Error>> {
+        if solver(problem)?.count_solutions(1) == 0 {
+            return Err(NotUniquelySolvable::new(0).into());
+        }
+
+        Ok(Self {
+            problem,
+            symmetries: Symmetry::detect(problem),
+            original_score: Rater::new(problem).rate().score(),
+            solver,
+        })
+    }
+
+    /*
+     * Returns up to max_suggestions suggestions, best first.  Suggestions that keep more of the
+     * original symmetry rank highest, then ones whose score stays closest to the original
+     * puzzle's, then ones that need fewer extra clues.  A puzzle that's already unique gets a
+     * single suggestion with no extra clues
+     */
+    pub fn suggest(&self, max_suggestions: usize) -> Vec<ClueSuggestion> {
+        let solutions = self.solution_values(self.problem);
+        if solutions.len() <= 1 {
+            return vec![self.build_suggestion(Vec::new())];
+        }
+
+        // Spread the solutions we keep across the enumeration so the suggestions differ
+        let mut suggestions: Vec<ClueSuggestion> = Vec::new();
+        let keep_count = max_suggestions.min(solutions.len());
+
+        for keep_index in 0 .. keep_count {
+            let target = &solutions[keep_index * solutions.len() / keep_count];
+            let mut groupings = vec![Symmetry::None];
+            groupings.extend(self.symmetries.iter());
+
+            for grouping in groupings {
+                let clues = self.separating_clues(target, &solutions, grouping);
+                let already_suggested = suggestions.iter().any(|suggestion| suggestion.clues == clues);
+                if !already_suggested {
+                    suggestions.push(self.build_suggestion(clues));
+                }
+            }
+        }
+
+        let distance = |suggestion: &ClueSuggestion| suggestion.rating.score().abs_diff(self.original_score);
+        suggestions.sort_by(|a, b| {
+            b.kept_symmetries.len().cmp(&a.kept_symmetries.len())
+                .then(distance(a).cmp(&distance(b)))
+                .then(a.clues.len().cmp(&b.clues.len()))
+        });
+        suggestions.truncate(max_suggestions);
+
+        suggestions
+    }
+
+    /// Greedily picks clues from the target solution until it's the only solution left.  Clues are
+    /// added a symmetric group at a time
+    fn separating_clues(&self, target: &[u8], solutions: &[Vec<u8>], grouping: Symmetry) -> Vec<(GridReference, u8)> {
+        let shape = self.problem.shape();
+        let mut puzzle = self.problem.clone();
+        let mut clues: Vec<(GridReference, u8)> = Vec::new();
+        let mut others: Vec<Vec<u8>> = solutions.iter().filter(|solution| solution.as_slice() != target).cloned().collect();
+
+        while !others.is_empty() {
+            let best_orbit = grouping.orbits(shape)
+                .into_iter()
+                .map(|orbit| {
+                    let cells: Vec<usize> = orbit.iter()
+                        .map(|(row, column)| shape.index_of(*row, *column))
+                        .filter(|index| Self::cell_value(&puzzle, *index).is_none())
+                        .collect();
+                    let separated = others.iter()
+                        .filter(|other| cells.iter().any(|index| other[*index] != target[*index]))
+                        .count();
+                    (cells, separated)
+                })
+                .filter(|(cells, separated)| !cells.is_empty() && *separated > 0)
+                // Prefer ruling out more solutions, and then adding fewer cells to do it
+                .max_by(|(a_cells, a_separated), (b_cells, b_separated)| {
+                    a_separated.cmp(b_separated).then(b_cells.len().cmp(&a_cells.len()))
+                });
+
+            let Some((cells, _)) = best_orbit else {
+                // Can't happen as long as every other solution differs from the target somewhere
+                break;
+            };
+
+            for index in cells.iter() {
+                let grid_ref = GridReference::for_shape(shape, index / shape.columns(), index % shape.columns())
+                    .expect("Index is always in range");
+                puzzle.set_cell(&grid_ref, &CellValue::new(target[*index]).expect("Solution values are in range"))
+                    .expect("Values from a solution never break uniqueness constraints");
+                clues.push((grid_ref, target[*index]));
+            }
+            others.retain(|other| cells.iter().all(|index| other[*index] == target[*index]));
+
+            // The enumeration may have been cut short, so go back for more solutions once we've
+            // ruled out all of the ones we know about
+            if others.is_empty() {
+                others = self.solution_values(&puzzle)
+                    .into_iter()
+                    .filter(|solution| solution.as_slice() != target)
+                    .collect();
+            }
+        }
+
+        clues
+    }
+
+    fn build_suggestion(&self, clues: Vec<(GridReference, u8)>) -> ClueSuggestion {
+        let mut puzzle = self.problem.clone();
+        for (grid_ref, value) in clues.iter() {
+            puzzle.set_cell(grid_ref, &CellValue::new(*value).expect("Solution values are in range"))
+                .expect("Values from a solution never break uniqueness constraints");
+        }
+
+        let kept_symmetries = self.symmetries.iter()
+            .filter(|symmetry| symmetry.is_present_in(&puzzle))
+            .copied()
+            .collect();
+        let rating = Rater::new(&puzzle).rate();
+
+        ClueSuggestion { clues, puzzle, kept_symmetries, rating }
+    }
+
+    fn solution_values(&self, puzzle: &Grid) -> Vec<Vec<u8>> {
+        (self.solver)(puzzle)
+            .expect("The solver took the original puzzle, so it takes it with clues added")
+            .find_solutions(Self::SOLUTION_LIMIT)
+            .iter()
+            .map(|solution| {
+                (0 .. solution.shape().cell_count())
+                    .map(|index| Self::cell_value(solution, index).expect("Solutions have every cell filled"))
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn cell_value(grid: &Grid, index: usize) -> Option<u8> {
+        let shape = grid.shape();
+        let grid_ref = GridReference::for_shape(shape, index / shape.columns(), index % shape.columns())
+            .expect("Index is always in range");
+        *grid.cell(&grid_ref)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sudoku::solver::Solver;
+    use crate::sudoku::generator::Generator;
+
+    /// A generated puzzle with the clues of its first few orbits taken out, which leaves it with
+    /// several solutions but keeps the symmetry it was generated with
+    fn loosened(symmetry: Symmetry, orbits: usize) -> Grid {
+        let mut generator = Generator::new(4);
+        generator.set_symmetry(symmetry);
+        let mut puzzle = generator.generate().unwrap().puzzle().clone();
+
+        let cells: Vec<GridReference> = symmetry.orbits(puzzle.shape())
+            .into_iter()
+            .map(|orbit| orbit.into_iter().map(|(row, column)| GridReference::from_numbers(row, column).unwrap()).collect::<Vec<_>>())
+            .filter(|orbit| puzzle.cell(&orbit[0]).is_some())
+            .take(orbits)
+            .flatten()
+            .collect();
+        for grid_ref in cells.iter() {
+            puzzle.clear_cell(grid_ref);
+        }
+
+        puzzle
+    }
+
+    #[test]
+    fn suggestions_make_the_puzzle_unique() {
+        let puzzle = loosened(Symmetry::None, 3);
+        assert!(DepthFirstSolver::for_problem(&puzzle).count_solutions(2) > 1);
+
+        let suggestions = ClueSuggester::new(&puzzle).unwrap().suggest(2);
+        assert!(!suggestions.is_empty() && suggestions.len() <= 2);
+        for suggestion in suggestions.iter() {
+            assert!(!suggestion.clues().is_empty());
+            assert!(DepthFirstSolver::for_problem(suggestion.puzzle()).has_unique_solution());
+            for (grid_ref, value) in suggestion.clues() {
+                assert_eq!(*suggestion.puzzle().cell(grid_ref), Some(*value));
+            }
+        }
+    }
+
+    #[test]
+    fn symmetric_suggestions_come_first() {
+        let puzzle = loosened(Symmetry::Rotational180, 2);
+        assert!(Symmetry::Rotational180.is_present_in(&puzzle));
+        assert!(DepthFirstSolver::for_problem(&puzzle).count_solutions(2) > 1);
+
+        let suggestions = ClueSuggester::new(&puzzle).unwrap().suggest(2);
+        assert!(suggestions[0].kept_symmetries().contains(&Symmetry::Rotational180));
+    }
+
+    #[test]
+    fn a_unique_puzzle_needs_no_clues() {
+        let suggestions = ClueSuggester::new(&loosened(Symmetry::None, 0)).unwrap().suggest(3);
+
+        assert_eq!(suggestions.len(), 1);
+        assert!(suggestions[0].clues().is_empty());
+    }
+
+    #[test]
+    fn turns_away_puzzles_with_no_solution() {
+        let mut puzzle = Grid::new();
+        for (column, value) in (1 ..= 8).enumerate() {
+            puzzle.set_cell(&GridReference::from_numbers(0, column).unwrap(), &CellValue::new(value).unwrap()).unwrap();
+        }
+        puzzle.set_cell(&GridReference::from_numbers(1, 8).unwrap(), &CellValue::new(9).unwrap()).unwrap();
+
+        assert!(ClueSuggester::new(&puzzle).unwrap_err().is::<NotUniquelySolvable>());
+    }
+}
@@ -0,0 +1,110 @@
+use crate::sudoku::constraint::{CandidateSet, Constraint};
+use crate::sudoku::error::{InvalidBorderMark, PairConstraint};
+use crate::sudoku::formula::Formula;
+use crate::sudoku::grid::Grid;
+use crate::sudoku::pair::{check_pair, encode_pair, pair_formula, prune_pair};
+use crate::sudoku::reference::GridReference;
+use crate::sudoku::sat::Cnf;
+use crate::sudoku::shape::GridShape;
+use crate::sudoku::transform::Transform;
+use std::error::Error;
+use std::sync::Arc;
+
+/*
+ * A greater-than sign on the border between two neighbouring cells, pointing at the cell with the
+ * lower value.  Comparison Sudoku usually has a sign between every pair of neighbours in the same
+ * subgrid, but any set of signs can be given
+ */
+#[derive(Debug, Clone)]
+pub struct GreaterThan {
+    cells: [GridReference; 2],
+}
+
+impl GreaterThan {
+    pub fn new(greater: GridReference, lesser: GridReference) -> Result<Self, InvalidBorderMark> {
+        if !greater.shares_side_with(&lesser) {
+            return Err(InvalidBorderMark::new(&greater, &lesser));
+        }
+
+        Ok(Self { cells: [greater, lesser] })
+    }
+
+    pub fn greater(&self) -> &GridReference {
+        &self.cells[0]
+    }
+
+    pub fn lesser(&self) -> &GridReference {
+        &self.cells[1]
+    }
+}
+
+impl Constraint for GreaterThan {
+    fn affected_cells(&self, _shape: &GridShape) -> Vec<GridReference> {
+        self.cells.to_vec()
+    }
+
+    fn check(&self, grid: &Grid, changed: &GridReference) -> Result<(), Box<dyn Error>> {
+        check_pair(&PairConstraint::GreaterThan, &self.cells, grid, changed)
+    }
+
+    fn prune(&self, grid: &Grid, candidates: &mut [CandidateSet]) {
+        prune_pair(&PairConstraint::GreaterThan, &self.cells, grid, candidates);
+    }
+
+    fn encode(&self, _shape: &GridShape, cnf: &mut Cnf) -> bool {
+        encode_pair(&PairConstraint::GreaterThan, &self.cells, cnf);
+
+        true
+    }
+
+    fn formulas(&self, _shape: &GridShape) -> Option<Vec<Formula>> {
+        Some(vec![pair_formula(&PairConstraint::GreaterThan, &self.cells)])
+    }
+
+    /// The sign points at the lesser cell, so which way it faces depends on where that cell is
+    fn border_marks(&self, _shape: &GridShape) -> Vec<(GridReference, GridReference, char)> {
+        let (greater, lesser) = (self.greater(), self.lesser());
+        let mark = match (greater.row() == lesser.row(), greater.row() < lesser.row() || greater.column() < lesser.column()) {
+            (true, true) => '>',
+            (true, false) => '<',
+            (false, true) => 'v',
+            (false, false) => '^',
+        };
+
+        vec![(greater.clone(), lesser.clone(), mark)]
+    }
+
+    fn transformed(&self, _shape: &GridShape, transform: &Transform) -> Option<Arc<dyn Constraint>> {
+        if !transform.keeps_digits() {
+            return None;
+        }
+
+        let sign = Self::new(transform.move_cell(self.greater()), transform.move_cell(self.lesser())).ok()?;
+        Some(Arc::new(sign))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sudoku::error::PairError;
+    use crate::sudoku::grid::CellValue;
+
+    #[test]
+    fn the_sign_points_at_the_lesser_value() {
+        let (greater, lesser) = (GridReference::from_numbers(4, 4).unwrap(), GridReference::from_numbers(5, 4).unwrap());
+        let sign = GreaterThan::new(greater.clone(), lesser.clone()).unwrap();
+
+        let mut candidates = vec![0b1_1111_1111; 81];
+        sign.prune(&Grid::new(), &mut candidates);
+        assert_eq!(candidates[greater.to_index(&GridShape::standard())], 0b1_1111_1110);
+        assert_eq!(candidates[lesser.to_index(&GridShape::standard())], 0b0_1111_1111);
+
+        let mut grid = Grid::new();
+        grid.add_constraint(sign).unwrap();
+        grid.set_cell(&lesser, &CellValue::new(6).unwrap()).unwrap();
+        assert!(grid.clone().set_cell(&greater, &CellValue::new(2).unwrap()).unwrap_err().is::<PairError>());
+        assert!(grid.set_cell(&greater, &CellValue::new(8).unwrap()).is_ok());
+        assert!(GreaterThan::new(greater, GridReference::from_numbers(5, 5).unwrap()).is_err());
+    }
+}
@@ -0,0 +1,582 @@
+use crate::sudoku::error::{InvalidShape, UniquenessConstraint, UniquenessError};
+use crate::sudoku::formula::{Formula, Term};
+use crate::sudoku::grid::Grid;
+use crate::sudoku::reference::GridReference;
+use crate::sudoku::region_map::RegionMap;
+use crate::sudoku::sat::{Cnf, Literal};
+use crate::sudoku::shape::GridShape;
+use crate::sudoku::transform::Transform;
+use std::error::Error;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+/// A bitmask of the values that a cell could still take, with bit 0 representing 1, bit 1
+/// representing 2, and so on
+pub type CandidateSet = u32;
+
+/*
+ * A rule that the values in a grid have to follow.  The standard rules are that no value repeats
+ * within a row, column or subgrid, and variants add rules of their own on top of (or instead of)
+ * those.
+ *
+ * Constraints are checked against partly filled grids, so an empty cell never counts as breaking
+ * one: a constraint is only broken once the values already placed can't be part of a solution
+ */
+pub trait Constraint: Debug + Send + Sync {
+    /// The cells whose values the constraint restricts.  Changing any other cell can never break it
+    fn affected_cells(&self, shape: &GridShape) -> Vec<GridReference>;
+
+    /// Checks the constraint after the given cell has been changed, describing the problem if the
+    /// values now break it
+    fn check(&self, grid: &Grid, changed: &GridReference) -> Result<(), Box<dyn Error>>;
+
+    /*
+     * Removes the values that the constraint rules out from the candidates of the empty cells it
+     * affects.  There's an entry in candidates for every cell of the grid, in row order, and the
+     * entries for filled cells should be left alone
+     */
+    fn prune(&self, grid: &Grid, candidates: &mut [CandidateSet]);
+
+    /// A house is a group of cells that must hold every value exactly once, like a row.  Solvers
+    /// use houses to spot values that only have one place left
+    fn is_house(&self, _shape: &GridShape) -> bool {
+        false
+    }
+
+    /// Cells to shade when the grid is drawn, for rules that apply to cells a solver wouldn't
+    /// otherwise be able to pick out
+    fn shaded_cells(&self, _shape: &GridShape) -> Vec<GridReference> {
+        Vec::new()
+    }
+
+    /// Cells to draw an outline around when the grid is drawn, along with a label for it, such as
+    /// the total of a Killer cage
+    fn outline(&self, _shape: &GridShape) -> Option<(Vec<GridReference>, String)> {
+        None
+    }
+
+    /// Symbols to draw on the border between two neighbouring cells, such as Kropki dots
+    fn border_marks(&self, _shape: &GridShape) -> Vec<(GridReference, GridReference, char)> {
+        Vec::new()
+    }
+
+    /// Symbols to draw inside a cell, next to its value, such as the circle of an odd cell
+    fn cell_marks(&self, _shape: &GridShape) -> Vec<(GridReference, char)> {
+        Vec::new()
+    }
+
+    /// A clue to write in the ring around the outside of the grid, such as a sandwich total, at
+    /// the given row and column.  The ring is at row and column -1 and the grid's size
+    fn outside_clue(&self, _shape: &GridShape) -> Option<(isize, isize, String)> {
+        None
+    }
+
+    /// The shape of grid the constraint was made for, if it only fits the one.  A grid won't take
+    /// a constraint made for a different shape
+    fn shape(&self) -> Option<GridShape> {
+        None
+    }
+
+    /// Adds clauses to the CNF that hold exactly when the constraint does, for checking puzzles
+    /// with an external SAT solver.  Returns false if the constraint can't be put that way
+    fn encode(&self, _shape: &GridShape, _cnf: &mut Cnf) -> bool {
+        false
+    }
+
+    /// The constraint as formulas over the cells' values, for exporting puzzles to constraint
+    /// solvers, or None if it can't be put that way
+    fn formulas(&self, _shape: &GridShape) -> Option<Vec<Formula>> {
+        None
+    }
+
+    /// The constraint moved onto the cells and digits of the grid the transform produces, or None
+    /// if it can't be, such as when the cells of a clue no longer line up
+    fn transformed(&self, _shape: &GridShape, _transform: &Transform) -> Option<Arc<dyn Constraint>> {
+        None
+    }
+}
+
+/// A group of cells that can't hold the same value twice, such as a row, column or subgrid
+#[derive(Debug, Clone)]
+pub struct UniqueGroup {
+    kind: UniquenessConstraint,
+    cells: Vec<GridReference>,
+}
+
+impl UniqueGroup {
+    pub fn new(kind: UniquenessConstraint, cells: Vec<GridReference>) -> Self {
+        Self { kind, cells }
+    }
+
+    pub fn rows(shape: &GridShape) -> Vec<Self> {
+        (0 .. shape.rows())
+            .map(|row| Self::from_positions(shape, UniquenessConstraint::Row, (0 .. shape.columns()).map(|column| (row, column))))
+            .collect()
+    }
+
+    pub fn columns(shape: &GridShape) -> Vec<Self> {
+        (0 .. shape.columns())
+            .map(|column| Self::from_positions(shape, UniquenessConstraint::Column, (0 .. shape.rows()).map(|row| (row, column))))
+            .collect()
+    }
+
+    pub fn subgrids(shape: &GridShape) -> Vec<Self> {
+        (0 .. shape.subgrid_count())
+            .map(|subgrid| {
+                let (first_row, first_column) = shape.subgrid_origin(subgrid);
+                let positions = (0 .. shape.size())
+                    .map(|offset| (first_row + offset / shape.subgrid_columns(), first_column + offset % shape.subgrid_columns()));
+
+                Self::from_positions(shape, UniquenessConstraint::SubGrid, positions)
+            })
+            .collect()
+    }
+
+    /// The regions of a jigsaw, which take the place of the subgrids
+    pub fn regions(regions: &RegionMap) -> Vec<Self> {
+        (0 .. regions.region_count())
+            .map(|region| Self::new(UniquenessConstraint::Region, regions.cells_of(region)))
+            .collect()
+    }
+
+    /// The two main diagonals, as used by Sudoku X: top left to bottom right, then top right to
+    /// bottom left
+    pub fn diagonals(shape: &GridShape) -> Vec<Self> {
+        let last = shape.size() - 1;
+
+        vec![
+            Self::from_positions(shape, UniquenessConstraint::Diagonal, (0 .. shape.size()).map(|offset| (offset, offset))),
+            Self::from_positions(shape, UniquenessConstraint::Diagonal, (0 .. shape.size()).map(|offset| (offset, last - offset))),
+        ]
+    }
+
+    /// The disjoint groups: the cells in the same position within each subgrid, such as the centre
+    /// cells of all the subgrids.  There's one group for each position, in row order
+    pub fn disjoint_groups(shape: &GridShape) -> Vec<Self> {
+        (0 .. shape.size())
+            .map(|offset| {
+                let positions = (0 .. shape.subgrid_count()).map(|subgrid| {
+                    let (first_row, first_column) = shape.subgrid_origin(subgrid);
+                    (first_row + offset / shape.subgrid_columns(), first_column + offset % shape.subgrid_columns())
+                });
+
+                Self::from_positions(shape, UniquenessConstraint::DisjointGroup, positions)
+            })
+            .collect()
+    }
+
+    /// The windows of Hyper Sudoku: extra regions the size of a subgrid, set in by one cell from
+    /// the subgrids, with one row and column between neighbouring windows.  There are four in the
+    /// standard grid, with their top left corners at 1, 1, at 1, 5, at 5, 1 and at 5, 5.  The
+    /// windows only fit grids with square subgrids
+    pub fn windows(shape: &GridShape) -> Result<Vec<Self>, InvalidShape> {
+        let bands = Self::window_bands(shape)?;
+        let (window_bands, _) = bands.split_at(bands.len() - 1);
+
+        Ok(Self::window_groups(shape, UniquenessConstraint::Window, window_bands, window_bands))
+    }
+
+    /// The regions that follow from the windows.  The columns that a band of windows covers hold
+    /// every value once each, and the windows among them hold every value once each, so what's
+    /// left of those columns between the windows must also hold every value exactly once.  The
+    /// same goes for the rows, and then for the cells where the rows and columns between the
+    /// windows cross.  There are five of them in the standard grid
+    pub fn implied_windows(shape: &GridShape) -> Result<Vec<Self>, InvalidShape> {
+        let bands = Self::window_bands(shape)?;
+        let (window_bands, between) = bands.split_at(bands.len() - 1);
+
+        let mut groups = Self::window_groups(shape, UniquenessConstraint::ImpliedWindow, between, window_bands);
+        groups.extend(Self::window_groups(shape, UniquenessConstraint::ImpliedWindow, window_bands, between));
+        groups.extend(Self::window_groups(shape, UniquenessConstraint::ImpliedWindow, between, between));
+
+        Ok(groups)
+    }
+
+    pub fn kind(&self) -> &UniquenessConstraint {
+        &self.kind
+    }
+
+    pub fn cells(&self) -> &[GridReference] {
+        &self.cells
+    }
+
+    /// The lines (rows or columns, as they're the same) that each band of windows covers, followed
+    /// by the lines that run between the windows
+    fn window_bands(shape: &GridShape) -> Result<Vec<Vec<usize>>, InvalidShape> {
+        if !shape.has_square_subgrids() {
+            return Err(InvalidShape::not_square(shape.subgrid_rows(), shape.subgrid_columns()));
+        }
+
+        let width = shape.subgrid_rows();
+
+        let mut bands: Vec<Vec<usize>> = (0 .. width - 1)
+            .map(|band| (0 .. width).map(|offset| 1 + band * (width + 1) + offset).collect())
+            .collect();
+        bands.push((0 .. width).map(|band| band * (width + 1)).collect());
+
+        Ok(bands)
+    }
+
+    /// A group for each pairing of a band of rows with a band of columns
+    fn window_groups(shape: &GridShape, kind: UniquenessConstraint, row_bands: &[Vec<usize>], column_bands: &[Vec<usize>]) -> Vec<Self> {
+        let mut groups = Vec::with_capacity(row_bands.len() * column_bands.len());
+        for rows in row_bands {
+            for columns in column_bands {
+                let positions = rows.iter().flat_map(|row| columns.iter().map(move |column| (*row, *column)));
+                groups.push(Self::from_positions(shape, kind.clone(), positions));
+            }
+        }
+
+        groups
+    }
+
+    fn from_positions(shape: &GridShape, kind: UniquenessConstraint, positions: impl Iterator<Item = (usize, usize)>) -> Self {
+        let cells = positions
+            .map(|(row, column)| {
+                GridReference::for_shape(shape, row, column).expect("Row and column are always in range")
+            })
+            .collect();
+
+        Self { kind, cells }
+    }
+}
+
+impl Constraint for UniqueGroup {
+    fn affected_cells(&self, _shape: &GridShape) -> Vec<GridReference> {
+        self.cells.clone()
+    }
+
+    fn check(&self, grid: &Grid, changed: &GridReference) -> Result<(), Box<dyn Error>> {
+        let Some(value) = *grid.cell(changed) else {
+            return Ok(());
+        };
+
+        let repeated = self.cells.iter().any(|grid_ref| grid_ref != changed && *grid.cell(grid_ref) == Some(value));
+        if repeated {
+            return Err(UniquenessError::new(changed.row(), changed.column(), value, self.kind.clone()).into());
+        }
+
+        Ok(())
+    }
+
+    fn prune(&self, grid: &Grid, candidates: &mut [CandidateSet]) {
+        let shape = grid.shape();
+        let used = self.cells.iter()
+            .filter_map(|grid_ref| *grid.cell(grid_ref))
+            .fold(0, |used, value| used | (1 << (value - 1)));
+
+        for grid_ref in self.cells.iter().filter(|grid_ref| grid.cell(grid_ref).is_none()) {
+            candidates[grid_ref.to_index(shape)] &= !used;
+        }
+    }
+
+    fn is_house(&self, shape: &GridShape) -> bool {
+        self.cells.len() == shape.size()
+    }
+
+    /// Each value at most once, and in a house at least once as well, which gives solvers more to
+    /// work with
+    fn encode(&self, shape: &GridShape, cnf: &mut Cnf) -> bool {
+        for value in 1 ..= shape.max_value() {
+            let cells: Vec<Literal> = self.cells.iter().map(|grid_ref| cnf.variable(grid_ref, value)).collect();
+            cnf.at_most_one(&cells);
+
+            if self.is_house(shape) {
+                cnf.add_clause(cells);
+            }
+        }
+
+        true
+    }
+
+    fn formulas(&self, _shape: &GridShape) -> Option<Vec<Formula>> {
+        Some(vec![Formula::AllDifferent(self.cells.iter().cloned().map(Term::Cell).collect())])
+    }
+
+    fn shaded_cells(&self, _shape: &GridShape) -> Vec<GridReference> {
+        match self.kind {
+            UniquenessConstraint::Diagonal | UniquenessConstraint::Window => self.cells.clone(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Values can't repeat however the cells are moved or the digits relabelled, but a row that's
+    /// been transposed is now a column, and the other way round
+    fn transformed(&self, _shape: &GridShape, transform: &Transform) -> Option<Arc<dyn Constraint>> {
+        let mut cells = transform.move_cells(&self.cells);
+        cells.sort_by_key(|grid_ref| (grid_ref.row(), grid_ref.column()));
+
+        let kind = match self.kind {
+            UniquenessConstraint::Row | UniquenessConstraint::Column if cells.iter().all(|grid_ref| grid_ref.row() == cells[0].row()) => {
+                UniquenessConstraint::Row
+            },
+            UniquenessConstraint::Row | UniquenessConstraint::Column => UniquenessConstraint::Column,
+            ref kind => kind.clone(),
+        };
+
+        Some(Arc::new(Self::new(kind, cells)))
+    }
+}
+
+/// The rules of standard Sudoku: no value may repeat within a row, column or subgrid
+pub fn standard_constraints(shape: &GridShape) -> Vec<Arc<dyn Constraint>> {
+    let mut constraints: Vec<Arc<dyn Constraint>> = Vec::with_capacity(shape.size() * 3);
+
+    for group in UniqueGroup::rows(shape).into_iter()
+        .chain(UniqueGroup::columns(shape))
+        .chain(UniqueGroup::subgrids(shape)) {
+        constraints.push(Arc::new(group));
+    }
+
+    constraints
+}
+
+/// The rules of Sudoku X: the standard rules, plus no value may repeat along either main diagonal
+pub fn diagonal_constraints(shape: &GridShape) -> Vec<Arc<dyn Constraint>> {
+    let mut constraints = standard_constraints(shape);
+    for diagonal in UniqueGroup::diagonals(shape) {
+        constraints.push(Arc::new(diagonal));
+    }
+
+    constraints
+}
+
+/// The rules of Hyper Sudoku: the standard rules, plus no value may repeat within a window.  The
+/// regions implied by the windows are included as well, as solvers can make use of them.  Only
+/// grids with square subgrids have windows
+pub fn hyper_constraints(shape: &GridShape) -> Result<Vec<Arc<dyn Constraint>>, InvalidShape> {
+    let mut constraints = standard_constraints(shape);
+    for group in UniqueGroup::windows(shape)?.into_iter().chain(UniqueGroup::implied_windows(shape)?) {
+        constraints.push(Arc::new(group));
+    }
+
+    Ok(constraints)
+}
+
+/// The rules of disjoint groups Sudoku: the standard rules, plus no value may repeat among the
+/// cells in the same position within each subgrid
+pub fn disjoint_constraints(shape: &GridShape) -> Vec<Arc<dyn Constraint>> {
+    let mut constraints = standard_constraints(shape);
+    for group in UniqueGroup::disjoint_groups(shape) {
+        constraints.push(Arc::new(group));
+    }
+
+    constraints
+}
+
+/// The rules of jigsaw Sudoku: no value may repeat within a row, column or region
+pub fn jigsaw_constraints(regions: &RegionMap) -> Vec<Arc<dyn Constraint>> {
+    let shape = regions.shape();
+    let mut constraints: Vec<Arc<dyn Constraint>> = Vec::with_capacity(shape.size() * 3);
+
+    for group in UniqueGroup::rows(shape).into_iter()
+        .chain(UniqueGroup::columns(shape))
+        .chain(UniqueGroup::regions(regions)) {
+        constraints.push(Arc::new(group));
+    }
+
+    constraints
+}
+
+/// The lowest value in a non-empty set
+pub(crate) fn lowest_of(values: CandidateSet) -> i32 {
+    values.trailing_zeros() as i32 + 1
+}
+
+/// The highest value in a non-empty set
+pub(crate) fn highest_of(values: CandidateSet) -> i32 {
+    (CandidateSet::BITS - values.leading_zeros()) as i32
+}
+
+/// The set of values from lowest to highest, leaving out any the grid can't hold.  Empty if
+/// lowest is above highest
+pub(crate) fn values_between(shape: &GridShape, lowest: i32, highest: i32) -> CandidateSet {
+    (lowest.max(1) ..= highest.min(shape.max_value() as i32)).fold(0, |values, value| values | (1 << (value - 1)))
+}
+
+/*
+ * Narrows the options of a group of cells down to the values that still let the group add up to
+ * something between lowest and highest, or returns None if it can't.  Each cell has to make up
+ * whatever the rest of the group can't.  Values are allowed to repeat, so this is only exact for
+ * groups where they can
+ */
+pub(crate) fn narrow_sum(shape: &GridShape, options: &[CandidateSet], lowest: i32, highest: i32) -> Option<Vec<CandidateSet>> {
+    if options.contains(&0) {
+        return None;
+    }
+
+    let lowest_total: i32 = options.iter().map(|option| lowest_of(*option)).sum();
+    let highest_total: i32 = options.iter().map(|option| highest_of(*option)).sum();
+    if highest < lowest_total || lowest > highest_total {
+        return None;
+    }
+
+    let narrowed: Vec<CandidateSet> = options.iter()
+        .map(|option| {
+            let rest_lowest = lowest_total - lowest_of(*option);
+            let rest_highest = highest_total - highest_of(*option);
+            option & values_between(shape, lowest - rest_highest, highest - rest_lowest)
+        })
+        .collect();
+
+    (!narrowed.contains(&0)).then_some(narrowed)
+}
+
+/*
+ * The values each of the given cells could hold: the value of a filled cell, and for an empty cell
+ * its candidates, or any value at all when there aren't any candidates to go on.  Constraints that
+ * work out what's possible across several cells can use the same reasoning for both checking and
+ * pruning this way
+ */
+pub(crate) fn cell_options(grid: &Grid, cells: &[GridReference], candidates: Option<&[CandidateSet]>) -> Vec<CandidateSet> {
+    let shape = grid.shape();
+
+    cells.iter()
+        .map(|grid_ref| match (*grid.cell(grid_ref), candidates) {
+            (Some(value), _) => 1 << (value - 1),
+            (None, Some(candidates)) => candidates[grid_ref.to_index(shape)],
+            (None, None) => (1 << shape.size()) - 1,
+        })
+        .collect()
+}
+
+/// Applies narrowed options to the empty cells, emptying them all if there weren't any options left
+pub(crate) fn prune_cells(grid: &Grid, cells: &[GridReference], narrowed: Option<Vec<CandidateSet>>, candidates: &mut [CandidateSet]) {
+    let shape = grid.shape();
+
+    for (position, grid_ref) in cells.iter().enumerate() {
+        if grid.cell(grid_ref).is_none() {
+            candidates[grid_ref.to_index(shape)] &= narrowed.as_ref().map_or(0, |narrowed| narrowed[position]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sudoku::grid::CellValue;
+
+    #[test]
+    fn the_standard_rules_put_every_cell_in_three_houses() {
+        for shape in [GridShape::standard(), GridShape::new(2, 3).unwrap()] {
+            let constraints = standard_constraints(&shape);
+            assert_eq!(constraints.len(), shape.size() * 3);
+            assert!(constraints.iter().all(|constraint| constraint.is_house(&shape)));
+
+            for grid_ref in shape.grid_refs() {
+                let houses = constraints.iter().filter(|constraint| constraint.affected_cells(&shape).contains(&grid_ref)).count();
+                assert_eq!(houses, 3);
+            }
+        }
+    }
+
+    #[test]
+    fn a_repeated_value_breaks_a_group_and_is_pruned_from_it() {
+        let mut grid = Grid::new();
+        let row = UniqueGroup::rows(&GridShape::standard()).remove(0);
+        grid.set_cell(&GridReference::from_numbers(0, 0).unwrap(), &CellValue::new(5).unwrap()).unwrap();
+
+        let mut candidates = vec![0b1_1111_1111; 81];
+        row.prune(&grid, &mut candidates);
+        assert_eq!(candidates[1], 0b1_1110_1111);
+        assert_eq!(candidates[0], 0b1_1111_1111);
+        assert_eq!(candidates[9], 0b1_1111_1111);
+
+        // The grid enforces the row itself, so check a copy of the group against a grid without it
+        let mut unchecked = Grid::with_constraints(GridShape::standard(), Vec::new());
+        unchecked.set_cell(&GridReference::from_numbers(0, 0).unwrap(), &CellValue::new(5).unwrap()).unwrap();
+        unchecked.set_cell(&GridReference::from_numbers(0, 8).unwrap(), &CellValue::new(5).unwrap()).unwrap();
+        let changed = GridReference::from_numbers(0, 8).unwrap();
+        assert!(row.check(&unchecked, &changed).unwrap_err().is::<UniquenessError>());
+    }
+
+    #[test]
+    fn transposing_turns_rows_into_columns() {
+        let shape = GridShape::standard();
+        let row = UniqueGroup::rows(&shape).remove(2);
+        let moved = row.transformed(&shape, &Transform::transpose()).unwrap();
+
+        let column: Vec<GridReference> = (0 .. 9).map(|row| GridReference::from_numbers(row, 2).unwrap()).collect();
+        assert_eq!(moved.affected_cells(&shape), column);
+        assert!(format!("{:?}", moved).contains("Column"));
+    }
+
+    #[test]
+    fn diagonals_are_shaded_houses_that_reject_repeats() {
+        let shape = GridShape::standard();
+        let diagonals = UniqueGroup::diagonals(&shape);
+        assert!(diagonals.iter().all(|diagonal| diagonal.is_house(&shape)));
+        assert_eq!(diagonals[1].shaded_cells(&shape)[0], GridReference::from_numbers(0, 8).unwrap());
+        assert!(UniqueGroup::rows(&shape)[0].shaded_cells(&shape).is_empty());
+
+        let mut grid = Grid::with_constraints(shape, diagonal_constraints(&shape));
+        grid.set_cell(&GridReference::from_numbers(0, 0).unwrap(), &CellValue::new(5).unwrap()).unwrap();
+        let err = grid.set_cell(&GridReference::from_numbers(4, 4).unwrap(), &CellValue::new(5).unwrap()).unwrap_err();
+        assert!(err.to_string().contains("diagonal"));
+        assert_eq!(*grid.cell(&GridReference::from_numbers(4, 4).unwrap()), None);
+    }
+
+    #[test]
+    fn windows_sit_one_cell_in_from_the_subgrids() {
+        let shape = GridShape::standard();
+        let windows = UniqueGroup::windows(&shape).unwrap();
+
+        let corners: Vec<GridReference> = windows.iter().map(|window| window.cells()[0].clone()).collect();
+        let expected: Vec<GridReference> = [(1, 1), (1, 5), (5, 1), (5, 5)].iter()
+            .map(|(row, column)| GridReference::from_numbers(*row, *column).unwrap())
+            .collect();
+        assert_eq!(corners, expected);
+        assert_eq!(windows[3].cells()[8], GridReference::from_numbers(7, 7).unwrap());
+        assert!(windows.iter().all(|window| window.is_house(&shape)));
+    }
+
+    #[test]
+    fn the_windows_and_implied_windows_split_up_the_grid() {
+        for (shape, window_count, implied_count) in [
+            (GridShape::new(2, 2).unwrap(), 1, 3),
+            (GridShape::standard(), 4, 5),
+            (GridShape::new(4, 4).unwrap(), 9, 7),
+        ] {
+            let windows = UniqueGroup::windows(&shape).unwrap();
+            let implied = UniqueGroup::implied_windows(&shape).unwrap();
+            assert_eq!((windows.len(), implied.len()), (window_count, implied_count));
+
+            let mut cells: Vec<usize> = windows.iter().chain(implied.iter())
+                .flat_map(|group| group.cells().iter().map(|grid_ref| grid_ref.to_index(&shape)))
+                .collect();
+            cells.sort();
+            assert_eq!(cells, (0 .. shape.cell_count()).collect::<Vec<usize>>());
+        }
+
+        assert_eq!(hyper_constraints(&GridShape::standard()).unwrap().len(), 27 + 9);
+    }
+
+    #[test]
+    fn only_square_subgrids_have_windows() {
+        for shape in [GridShape::new(2, 3).unwrap(), GridShape::new(3, 4).unwrap()] {
+            assert!(UniqueGroup::windows(&shape).is_err());
+            assert!(UniqueGroup::implied_windows(&shape).is_err());
+
+            let err = hyper_constraints(&shape).unwrap_err();
+            assert!(err.to_string().contains("aren't square"));
+        }
+    }
+    #[test]
+    fn disjoint_groups_take_the_same_cell_from_every_subgrid() {
+        let shape = GridShape::standard();
+        let groups = UniqueGroup::disjoint_groups(&shape);
+        assert_eq!(groups.len(), 9);
+        assert!(groups.iter().all(|group| group.is_house(&shape)));
+
+        let centres: Vec<GridReference> = [1, 4, 7].into_iter()
+            .flat_map(|row| [1, 4, 7].into_iter().map(move |column| GridReference::from_numbers(row, column).unwrap()))
+            .collect();
+        assert_eq!(groups[4].affected_cells(&shape), centres);
+
+        let mut grid = Grid::with_constraints(shape, disjoint_constraints(&shape));
+        grid.set_cell(&GridReference::from_numbers(1, 1).unwrap(), &CellValue::new(5).unwrap()).unwrap();
+        let error = grid.clone().set_cell(&GridReference::from_numbers(7, 4).unwrap(), &CellValue::new(5).unwrap()).unwrap_err();
+        assert!(error.to_string().contains("disjoint group"));
+        assert!(grid.set_cell(&GridReference::from_numbers(7, 5).unwrap(), &CellValue::new(5).unwrap()).is_ok());
+    }
+}
@@ -1,12 +1,48 @@
 use crate::sudoku::grid::CellValue;
+use crate::sudoku::reference::GridReference;
+use crate::sudoku::shape::GridShape;
 use std::error::Error;
 use std::fmt::{Debug, Display, Formatter, Result as FmtResult};
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Eq, PartialEq)]
 pub enum UniquenessConstraint {
     Row,
     Column,
     SubGrid,
+    Diagonal,
+    Region,
+    Cage,
+    Window,
+    ImpliedWindow,
+    AntiKnight,
+    AntiKing,
+    DisjointGroup,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum PairConstraint {
+    NonConsecutive,
+    WhiteDot,
+    BlackDot,
+    X,
+    V,
+    GreaterThan,
+    ConsecutivePair,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Parity {
+    Odd,
+    Even,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ClueConstraint {
+    Thermometer,
+    Arrow,
+    Sandwich,
+    LittleKiller,
+    XSum,
 }
 
 #[derive(Debug)]
@@ -24,6 +60,54 @@ pub struct InvalidSubGrid {
     pub subgrid_id: usize,
 }
 
+#[derive(Debug)]
+pub struct InvalidRegion {
+    pub region_id: usize,
+}
+
+#[derive(Debug)]
+pub struct InvalidShape {
+    pub subgrid_rows: usize,
+    pub subgrid_columns: usize,
+    // Set when the shape is valid in itself but the rule asking for it needs square subgrids
+    needs_square: bool,
+}
+
+#[derive(Debug)]
+pub struct InvalidBand {
+    pub band_id: usize,
+}
+
+#[derive(Debug)]
+pub struct InvalidStack {
+    pub stack_id: usize,
+}
+
+#[derive(Debug)]
+pub struct InvalidLineSwap {
+    pub first: usize,
+    pub second: usize,
+}
+
+#[derive(Debug)]
+pub struct InvalidDigitMapping {
+    pub mapping: Vec<u8>,
+}
+
+#[derive(Debug)]
+pub struct NonStandardShape {
+    rows: usize,
+    columns: usize,
+}
+
+#[derive(Debug)]
+pub struct UntransformableConstraint {
+    constraint: String,
+}
+
+#[derive(Debug)]
+pub struct NonStandardRules;
+
 #[derive(Debug)]
 pub struct UniquenessError {
     row: usize,
@@ -35,6 +119,190 @@ pub struct UniquenessError {
 #[derive(Debug)]
 pub struct AnswerRangeError {
     value: u8,
+    max: u8,
+}
+
+#[derive(Debug)]
+pub struct InvalidPuzzleLength {
+    length: usize,
+}
+
+#[derive(Debug)]
+pub struct InvalidPuzzleCharacter {
+    position: usize,
+    character: char,
+}
+
+#[derive(Debug)]
+pub struct InvalidMaskLength {
+    length: usize,
+}
+
+#[derive(Debug)]
+pub struct InvalidMaskCharacter {
+    position: usize,
+    character: char,
+}
+
+#[derive(Debug)]
+pub struct InvalidRegionMapLength {
+    length: usize,
+}
+
+#[derive(Debug)]
+pub struct InvalidRegionSize {
+    region: usize,
+    size: usize,
+    expected: usize,
+}
+
+#[derive(Debug)]
+pub struct DisconnectedRegion {
+    region: usize,
+}
+
+#[derive(Debug)]
+pub struct InvalidCage {
+    total: usize,
+    cell_count: usize,
+}
+
+#[derive(Debug)]
+pub struct CageSumError {
+    row: usize,
+    column: usize,
+    value: u8,
+    total: usize,
+}
+
+#[derive(Debug)]
+pub struct PairError {
+    row: usize,
+    column: usize,
+    other_row: usize,
+    other_column: usize,
+    value: u8,
+    violation: PairConstraint,
+}
+
+#[derive(Debug)]
+pub struct InvalidBorderMark {
+    first: (usize, usize),
+    second: (usize, usize),
+}
+
+#[derive(Debug)]
+pub struct ParityError {
+    row: usize,
+    column: usize,
+    value: u8,
+    parity: Parity,
+}
+
+#[derive(Debug)]
+pub struct UncoveredCell {
+    row: usize,
+    column: usize,
+}
+
+#[derive(Debug)]
+pub struct OverlapConflict {
+    row: usize,
+    column: usize,
+}
+
+#[derive(Debug)]
+pub struct UnencodableConstraint {
+    constraint: String,
+}
+
+#[derive(Debug)]
+pub struct UnsatisfiableModel;
+
+#[derive(Debug)]
+pub struct UnreadableModel {
+    token: String,
+}
+
+#[derive(Debug)]
+pub struct IncompleteModel {
+    row: usize,
+    column: usize,
+}
+
+#[derive(Debug)]
+pub struct UnexportableConstraint {
+    constraint: String,
+}
+
+#[derive(Debug)]
+pub struct UnreadableAssignment {
+    name: String,
+}
+
+#[derive(Debug)]
+pub struct PuzzleFailure {
+    index: usize,
+    message: String,
+}
+
+#[derive(Debug)]
+pub struct ClueError {
+    row: usize,
+    column: usize,
+    value: u8,
+    violation: ClueConstraint,
+}
+
+#[derive(Debug)]
+pub struct InvalidDiagonalStart {
+    row: usize,
+    column: usize,
+}
+
+#[derive(Debug)]
+pub struct ShapeMismatch {
+    expected: (usize, usize),
+    found: (usize, usize),
+}
+
+#[derive(Debug)]
+pub struct InvalidLineLength {
+    length: usize,
+    minimum: usize,
+}
+
+#[derive(Debug)]
+pub struct BrokenLine {
+    from: (usize, usize),
+    to: (usize, usize),
+}
+
+#[derive(Debug)]
+pub struct RepeatedLineCell {
+    row: usize,
+    column: usize,
+}
+
+#[derive(Debug)]
+pub struct InvalidCellName {
+    name: String,
+}
+
+#[derive(Debug)]
+pub struct InvalidClue {
+    clue: String,
+    expected: &'static str,
+}
+
+#[derive(Debug)]
+pub struct NotUniquelySolvable {
+    solution_count: usize,
+}
+
+#[derive(Debug)]
+pub struct GenerationBudgetExhausted {
+    attempts: usize,
 }
 
 impl InvalidRow {
@@ -55,6 +323,70 @@ impl InvalidSubGrid {
     }
 }
 
+impl InvalidRegion {
+    pub fn new(region_id: usize) -> Self {
+        Self {region_id}
+    }
+}
+
+impl InvalidShape {
+    pub fn new(subgrid_rows: usize, subgrid_columns: usize) -> Self {
+        Self {subgrid_rows, subgrid_columns, needs_square: false}
+    }
+
+    pub fn not_square(subgrid_rows: usize, subgrid_columns: usize) -> Self {
+        Self {subgrid_rows, subgrid_columns, needs_square: true}
+    }
+}
+
+impl InvalidBand {
+    pub fn new(band_id: usize) -> Self {
+        Self {band_id}
+    }
+}
+
+impl InvalidStack {
+    pub fn new(stack_id: usize) -> Self {
+        Self {stack_id}
+    }
+}
+
+impl InvalidLineSwap {
+    pub fn new(first: usize, second: usize) -> Self {
+        Self {first, second}
+    }
+}
+
+impl InvalidDigitMapping {
+    pub fn new(mapping: Vec<u8>) -> Self {
+        Self {mapping}
+    }
+}
+
+impl NonStandardShape {
+    pub fn new(shape: &GridShape) -> Self {
+        Self {rows: shape.rows(), columns: shape.columns()}
+    }
+}
+
+impl UntransformableConstraint {
+    pub fn new(constraint: String) -> Self {
+        Self {constraint}
+    }
+}
+
+impl NonStandardRules {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Default for NonStandardRules {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl UniquenessError {
     pub fn new(row: usize, column: usize, value: u8, violation: UniquenessConstraint) -> Self {
         Self {row, column, value, violation}
@@ -62,62 +394,683 @@ impl UniquenessError {
 }
 
 impl AnswerRangeError {
-    pub fn new(value: u8) -> Self {
-        Self {value}
+    pub fn new(value: u8, max: u8) -> Self {
+        Self {value, max}
     }
 }
 
-impl Display for InvalidRow {
-    fn fmt(&self, f: &mut Formatter) -> FmtResult {
-        write!(f, "Invalid row id: {}", self.row_id)
+impl InvalidPuzzleLength {
+    pub fn new(length: usize) -> Self {
+        Self {length}
     }
 }
 
-impl Display for InvalidColumn {
-    fn fmt(&self, f: &mut Formatter) -> FmtResult {
-        write!(f, "Invalid column id: {}", self.column_id)
+impl InvalidPuzzleCharacter {
+    pub fn new(position: usize, character: char) -> Self {
+        Self {position, character}
     }
 }
 
-impl Display for InvalidSubGrid {
-    fn fmt(&self, f: &mut Formatter) -> FmtResult {
-        write!(f, "Invalid subgrid id: {}", self.subgrid_id)
+impl InvalidMaskLength {
+    pub fn new(length: usize) -> Self {
+        Self {length}
     }
 }
 
-impl Display for UniquenessError {
-    fn fmt(&self, f: &mut Formatter) -> FmtResult {
-        let violation = match self.violation {
-            UniquenessConstraint::Row => "row",
-            UniquenessConstraint::Column => "column",
-            UniquenessConstraint::SubGrid => "subgrid",
-        };
+impl InvalidMaskCharacter {
+    pub fn new(position: usize, character: char) -> Self {
+        Self {position, character}
+    }
+}
 
-        write!(
-            f,
-            "Cannot insert value {} at {}, {} due to {} uniqueness constraints",
-            self.value,
-            self.row,
-            self.column,
+impl InvalidRegionMapLength {
+    pub fn new(length: usize) -> Self {
+        Self {length}
+    }
+}
+
+impl InvalidRegionSize {
+    pub fn new(region: usize, size: usize, expected: usize) -> Self {
+        Self {region, size, expected}
+    }
+}
+
+impl DisconnectedRegion {
+    pub fn new(region: usize) -> Self {
+        Self {region}
+    }
+}
+
+impl InvalidCage {
+    pub fn new(total: usize, cell_count: usize) -> Self {
+        Self {total, cell_count}
+    }
+}
+
+impl CageSumError {
+    pub fn new(row: usize, column: usize, value: u8, total: usize) -> Self {
+        Self {row, column, value, total}
+    }
+}
+
+impl PairError {
+    pub fn new(cell: &GridReference, other: &GridReference, value: u8, violation: PairConstraint) -> Self {
+        Self {
+            row: cell.row(),
+            column: cell.column(),
+            other_row: other.row(),
+            other_column: other.column(),
+            value,
             violation,
-        )
+        }
     }
 }
 
-impl Display for AnswerRangeError {
-    fn fmt(&self, f: &mut Formatter) -> FmtResult {
-        write!(
-            f,
-            "Cell value of {} is out of bounds: must be between {} and {}",
-            self.value,
-            CellValue::MIN_VALID_VAL,
-            CellValue::MAX_VALID_VAL,
-        )
+impl InvalidBorderMark {
+    pub fn new(first: &GridReference, second: &GridReference) -> Self {
+        Self {first: (first.row(), first.column()), second: (second.row(), second.column())}
+    }
+}
+
+impl ParityError {
+    pub fn new(row: usize, column: usize, value: u8, parity: Parity) -> Self {
+        Self {row, column, value, parity}
+    }
+}
+
+impl UncoveredCell {
+    pub fn new(row: usize, column: usize) -> Self {
+        Self {row, column}
+    }
+}
+
+impl OverlapConflict {
+    pub fn new(row: usize, column: usize) -> Self {
+        Self {row, column}
+    }
+}
+
+impl UnencodableConstraint {
+    pub fn new(constraint: String) -> Self {
+        Self {constraint}
+    }
+}
+
+impl UnsatisfiableModel {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Default for UnsatisfiableModel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl UnreadableModel {
+    pub fn new(token: &str) -> Self {
+        Self {token: token.to_string()}
+    }
+}
+
+impl IncompleteModel {
+    pub fn new(row: usize, column: usize) -> Self {
+        Self {row, column}
+    }
+}
+
+impl UnexportableConstraint {
+    pub fn new(constraint: String) -> Self {
+        Self {constraint}
+    }
+}
+
+impl UnreadableAssignment {
+    pub fn new(name: &str) -> Self {
+        Self {name: name.to_string()}
+    }
+}
+
+impl PuzzleFailure {
+    pub fn new(index: usize, message: String) -> Self {
+        Self {index, message}
+    }
+
+    /// Where the puzzle was in the batch
+    pub fn index(&self) -> usize {
+        self.index
+    }
+}
+
+impl ClueError {
+    pub fn new(row: usize, column: usize, value: u8, violation: ClueConstraint) -> Self {
+        Self {row, column, value, violation}
+    }
+}
+
+impl InvalidDiagonalStart {
+    pub fn new(row: usize, column: usize) -> Self {
+        Self {row, column}
+    }
+}
+
+impl ShapeMismatch {
+    pub fn new(expected: &GridShape, found: &GridShape) -> Self {
+        Self {expected: (expected.rows(), expected.columns()), found: (found.rows(), found.columns())}
+    }
+}
+
+impl InvalidLineLength {
+    pub fn new(length: usize, minimum: usize) -> Self {
+        Self {length, minimum}
+    }
+}
+
+impl BrokenLine {
+    pub fn new(from: &GridReference, to: &GridReference) -> Self {
+        Self {from: (from.row(), from.column()), to: (to.row(), to.column())}
+    }
+}
+
+impl RepeatedLineCell {
+    pub fn new(row: usize, column: usize) -> Self {
+        Self {row, column}
+    }
+}
+
+impl InvalidCellName {
+    pub fn new(name: &str) -> Self {
+        Self {name: name.to_string()}
+    }
+}
+
+impl InvalidClue {
+    pub fn new(clue: &str, expected: &'static str) -> Self {
+        Self {clue: clue.to_string(), expected}
+    }
+}
+
+impl NotUniquelySolvable {
+    pub fn new(solution_count: usize) -> Self {
+        Self {solution_count}
+    }
+}
+
+impl GenerationBudgetExhausted {
+    pub fn new(attempts: usize) -> Self {
+        Self {attempts}
+    }
+
+    pub fn attempts(&self) -> usize {
+        self.attempts
+    }
+}
+
+impl Display for InvalidRow {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "Invalid row id: {}", self.row_id)
+    }
+}
+
+impl Display for InvalidColumn {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "Invalid column id: {}", self.column_id)
+    }
+}
+
+impl Display for InvalidSubGrid {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "Invalid subgrid id: {}", self.subgrid_id)
+    }
+}
+
+impl Display for InvalidRegion {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "Invalid region id: {}", self.region_id)
+    }
+}
+
+impl Display for InvalidShape {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        if self.needs_square {
+            return write!(f, "Invalid grid shape: subgrids of {} x {} aren't square", self.subgrid_rows, self.subgrid_columns);
+        }
+
+        write!(
+            f,
+            "Invalid grid shape: subgrids of {} x {} must be at least {} cells each way and hold at most {} cells",
+            self.subgrid_rows,
+            self.subgrid_columns,
+            GridShape::MIN_SUBGRID_SIDE,
+            GridShape::MAX_SIZE,
+        )
+    }
+}
+
+impl Display for InvalidBand {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "Invalid band id: {}", self.band_id)
+    }
+}
+
+impl Display for InvalidStack {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "Invalid stack id: {}", self.stack_id)
+    }
+}
+
+impl Display for InvalidLineSwap {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(
+            f,
+            "Cannot swap lines {} and {}: only lines within the same band or stack can be swapped",
+            self.first,
+            self.second,
+        )
+    }
+}
+
+impl Display for InvalidDigitMapping {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(
+            f,
+            "Invalid digit mapping {:?}: must use each of {} to {} exactly once",
+            self.mapping,
+            CellValue::MIN_VALID_VAL,
+            GridShape::standard().max_value(),
+        )
+    }
+}
+
+impl Display for NonStandardShape {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "Only the standard 9 x 9 grid is supported here, not a {} x {} one", self.rows, self.columns)
+    }
+}
+
+impl Display for UntransformableConstraint {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "Cannot carry {} across the transform", self.constraint)
+    }
+}
+
+impl Display for NonStandardRules {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "Only grids following the standard rules are supported here, without jigsaw regions or variant constraints")
+    }
+}
+
+impl Display for UniquenessError {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        let violation = match self.violation {
+            UniquenessConstraint::Row => "row",
+            UniquenessConstraint::Column => "column",
+            UniquenessConstraint::SubGrid => "subgrid",
+            UniquenessConstraint::Diagonal => "diagonal",
+            UniquenessConstraint::Region => "region",
+            UniquenessConstraint::Cage => "cage",
+            UniquenessConstraint::Window => "window",
+            UniquenessConstraint::ImpliedWindow => "implied window region",
+            UniquenessConstraint::AntiKnight => "anti-knight",
+            UniquenessConstraint::AntiKing => "anti-king",
+            UniquenessConstraint::DisjointGroup => "disjoint group",
+        };
+
+        write!(
+            f,
+            "Cannot insert value {} at {}, {} due to {} uniqueness constraints",
+            self.value,
+            self.row,
+            self.column,
+            violation,
+        )
+    }
+}
+
+impl Display for AnswerRangeError {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(
+            f,
+            "Cell value of {} is out of bounds: must be between {} and {}",
+            self.value,
+            CellValue::MIN_VALID_VAL,
+            self.max,
+        )
+    }
+}
+
+impl Display for InvalidPuzzleLength {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(
+            f,
+            "Puzzle has {} cells: must be a square number of cells matching a supported grid size",
+            self.length,
+        )
+    }
+}
+
+impl Display for InvalidPuzzleCharacter {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "Unexpected character '{}' in puzzle at cell {}", self.character, self.position)
+    }
+}
+
+impl Display for InvalidMaskLength {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(
+            f,
+            "Clue mask has {} cells: must be a square number of cells matching a supported grid size",
+            self.length,
+        )
+    }
+}
+
+impl Display for InvalidMaskCharacter {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "Unexpected character '{}' in clue mask at cell {}", self.character, self.position)
+    }
+}
+
+impl Display for InvalidRegionMapLength {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(
+            f,
+            "Region map has {} cells: must be a square number of cells matching a supported grid size",
+            self.length,
+        )
+    }
+}
+
+impl Display for InvalidRegionSize {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "Region {} has {} cells: every region must have {}", self.region, self.size, self.expected)
+    }
+}
+
+impl Display for DisconnectedRegion {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "Region {} is split into separate pieces: its cells must all be connected", self.region)
+    }
+}
+
+impl Display for InvalidCage {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(
+            f,
+            "Invalid cage totalling {} with {} cells: cages need at least one cell and can't include a cell twice",
+            self.total,
+            self.cell_count,
+        )
+    }
+}
+
+impl Display for CageSumError {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(
+            f,
+            "Cannot insert value {} at {}, {}: the cage could no longer total {}",
+            self.value,
+            self.row,
+            self.column,
+            self.total,
+        )
+    }
+}
+
+impl Display for PairError {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        let violation = match self.violation {
+            PairConstraint::NonConsecutive => "non-consecutive",
+            PairConstraint::WhiteDot => "white dot",
+            PairConstraint::BlackDot => "black dot",
+            PairConstraint::X => "X",
+            PairConstraint::V => "V",
+            PairConstraint::GreaterThan => "greater-than",
+            PairConstraint::ConsecutivePair => "consecutive pair",
+        };
+
+        write!(
+            f,
+            "Cannot insert value {} at {}, {} due to the {} constraint with {}, {}",
+            self.value,
+            self.row,
+            self.column,
+            violation,
+            self.other_row,
+            self.other_column,
+        )
+    }
+}
+
+impl Display for InvalidBorderMark {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(
+            f,
+            "Cannot place a mark between {}, {} and {}, {}: marks only go between cells of the grid that share a side",
+            self.first.0,
+            self.first.1,
+            self.second.0,
+            self.second.1,
+        )
+    }
+}
+
+impl Display for ParityError {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        let parity = match self.parity {
+            Parity::Odd => "odd",
+            Parity::Even => "even",
+        };
+
+        write!(
+            f,
+            "Cannot insert value {} at {}, {}: the cell must hold an {} value",
+            self.value,
+            self.row,
+            self.column,
+            parity,
+        )
+    }
+}
+
+impl Display for UncoveredCell {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "Cell {}, {} of the canvas isn't part of any grid", self.row, self.column)
+    }
+}
+
+impl Display for OverlapConflict {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "The grids disagree about the value of cell {}, {} of the canvas, where they overlap", self.row, self.column)
+    }
+}
+
+impl Display for UnencodableConstraint {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "Cannot encode {} as CNF", self.constraint)
+    }
+}
+
+impl Display for UnsatisfiableModel {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "The solver found the puzzle unsatisfiable, so there's no solution to read")
+    }
+}
+
+impl Display for UnreadableModel {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "Cannot read '{}' in the SAT model: expected a literal", self.token)
+    }
+}
+
+impl Display for IncompleteModel {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "The solver's model doesn't give a value for cell {}, {}", self.row, self.column)
+    }
+}
+
+impl Display for UnexportableConstraint {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "Cannot export {} as a constraint model", self.constraint)
+    }
+}
+
+impl Display for UnreadableAssignment {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "Cannot read a value for {} in the solver's output", self.name)
+    }
+}
+
+impl Display for PuzzleFailure {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "Puzzle {} in the batch failed: {}", self.index, self.message)
+    }
+}
+
+impl Display for ClueError {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        let violation = match self.violation {
+            ClueConstraint::Thermometer => "thermometer",
+            ClueConstraint::Arrow => "arrow",
+            ClueConstraint::Sandwich => "sandwich",
+            ClueConstraint::LittleKiller => "Little Killer",
+            ClueConstraint::XSum => "X-sum",
+        };
+
+        write!(
+            f,
+            "Cannot insert value {} at {}, {} due to the {} clue",
+            self.value,
+            self.row,
+            self.column,
+            violation,
+        )
+    }
+}
+
+impl Display for InvalidDiagonalStart {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(
+            f,
+            "A diagonal can't start at {}, {}: it must start at the edge of the grid and run into it",
+            self.row,
+            self.column,
+        )
+    }
+}
+
+impl Display for ShapeMismatch {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(
+            f,
+            "The constraint was made for a {} x {} grid and cannot be added to a {} x {} one",
+            self.expected.0,
+            self.expected.1,
+            self.found.0,
+            self.found.1,
+        )
+    }
+}
+
+impl Display for InvalidLineLength {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "Line has {} cells: must have at least {}", self.length, self.minimum)
+    }
+}
+
+impl Display for BrokenLine {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(
+            f,
+            "Line is broken between {}, {} and {}, {}: each cell must touch the one before it",
+            self.from.0,
+            self.from.1,
+            self.to.0,
+            self.to.1,
+        )
+    }
+}
+
+impl Display for RepeatedLineCell {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "Line passes through {}, {} more than once", self.row, self.column)
+    }
+}
+
+impl Display for InvalidCellName {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(
+            f,
+            "Cannot read cell '{}': cells are written as r then the row and c then the column, counting from 1, such as r1c1",
+            self.name,
+        )
+    }
+}
+
+impl Display for InvalidClue {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "Cannot read clue '{}': expected {}", self.clue, self.expected)
+    }
+}
+
+impl Display for NotUniquelySolvable {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        match self.solution_count {
+            0 => write!(f, "Puzzle has no solution"),
+            _ => write!(f, "Puzzle has more than one solution"),
+        }
+    }
+}
+
+impl Display for GenerationBudgetExhausted {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "No puzzle with a unique solution found after {} attempts", self.attempts)
     }
 }
 
 impl Error for InvalidRow {}
 impl Error for InvalidColumn {}
 impl Error for InvalidSubGrid {}
+impl Error for InvalidRegion {}
+impl Error for InvalidShape {}
+impl Error for InvalidBand {}
+impl Error for InvalidStack {}
+impl Error for InvalidLineSwap {}
+impl Error for InvalidDigitMapping {}
+impl Error for NonStandardShape {}
+impl Error for UntransformableConstraint {}
+impl Error for NonStandardRules {}
 impl Error for UniquenessError {}
-impl Error for AnswerRangeError {}
\ No newline at end of file
+impl Error for AnswerRangeError {}
+impl Error for InvalidPuzzleLength {}
+impl Error for InvalidPuzzleCharacter {}
+impl Error for InvalidMaskLength {}
+impl Error for InvalidMaskCharacter {}
+impl Error for InvalidRegionMapLength {}
+impl Error for InvalidRegionSize {}
+impl Error for DisconnectedRegion {}
+impl Error for InvalidCage {}
+impl Error for CageSumError {}
+impl Error for PairError {}
+impl Error for InvalidBorderMark {}
+impl Error for ParityError {}
+impl Error for UncoveredCell {}
+impl Error for OverlapConflict {}
+impl Error for UnencodableConstraint {}
+impl Error for UnsatisfiableModel {}
+impl Error for UnreadableModel {}
+impl Error for IncompleteModel {}
+impl Error for UnexportableConstraint {}
+impl Error for UnreadableAssignment {}
+impl Error for PuzzleFailure {}
+impl Error for ClueError {}
+impl Error for InvalidDiagonalStart {}
+impl Error for ShapeMismatch {}
+impl Error for InvalidLineLength {}
+impl Error for BrokenLine {}
+impl Error for RepeatedLineCell {}
+impl Error for InvalidCellName {}
+impl Error for InvalidClue {}
+impl Error for NotUniquelySolvable {}
+impl Error for GenerationBudgetExhausted {}
\ No newline at end of file
@@ -0,0 +1,320 @@
+use crate::sudoku::error::{IncompleteModel, UnexportableConstraint, UnreadableAssignment, UnsatisfiableModel};
+use crate::sudoku::grid::{CellValue, Grid};
+use crate::sudoku::reference::GridReference;
+use crate::sudoku::shape::GridShape;
+use std::collections::HashMap;
+use std::error::Error;
+
+/// An integer expression over the values of the cells
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Term {
+    Cell(GridReference),
+    Number(usize),
+    Sum(Vec<Term>),
+}
+
+impl Term {
+    /// The total of the cells' values
+    pub fn sum_of(cells: &[GridReference]) -> Self {
+        Self::Sum(cells.iter().cloned().map(Self::Cell).collect())
+    }
+
+    fn to_minizinc(&self) -> String {
+        match self {
+            Self::Cell(grid_ref) => format!("grid[{},{}]", grid_ref.row() + 1, grid_ref.column() + 1),
+            Self::Number(number) => number.to_string(),
+            Self::Sum(terms) if terms.is_empty() => "0".to_string(),
+            Self::Sum(terms) => format!("({})", terms.iter().map(Self::to_minizinc).collect::<Vec<_>>().join(" + ")),
+        }
+    }
+
+    fn to_smtlib(&self) -> String {
+        match self {
+            Self::Cell(grid_ref) => cell_name(grid_ref),
+            Self::Number(number) => number.to_string(),
+            Self::Sum(terms) if terms.is_empty() => "0".to_string(),
+            Self::Sum(terms) if terms.len() == 1 => terms[0].to_smtlib(),
+            Self::Sum(terms) => format!("(+ {})", terms.iter().map(Self::to_smtlib).collect::<Vec<_>>().join(" ")),
+        }
+    }
+}
+
+/// A statement about the values of the cells, which both MiniZinc and SMT-LIB can express directly
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Formula {
+    Equal(Term, Term),
+    NotEqual(Term, Term),
+    Less(Term, Term),
+    AllDifferent(Vec<Term>),
+    And(Vec<Formula>),
+    Or(Vec<Formula>),
+    Implies(Box<Formula>, Box<Formula>),
+}
+
+impl Formula {
+    fn to_minizinc(&self) -> String {
+        let joined = |formulas: &[Formula], operator: &str| {
+            format!("({})", formulas.iter().map(Self::to_minizinc).collect::<Vec<_>>().join(operator))
+        };
+
+        match self {
+            Self::Equal(left, right) => format!("{} = {}", left.to_minizinc(), right.to_minizinc()),
+            Self::NotEqual(left, right) => format!("{} != {}", left.to_minizinc(), right.to_minizinc()),
+            Self::Less(left, right) => format!("{} < {}", left.to_minizinc(), right.to_minizinc()),
+            Self::AllDifferent(terms) => {
+                format!("alldifferent([{}])", terms.iter().map(Term::to_minizinc).collect::<Vec<_>>().join(", "))
+            },
+            Self::And(formulas) if formulas.is_empty() => "true".to_string(),
+            Self::And(formulas) => joined(formulas, " /\\ "),
+            Self::Or(formulas) if formulas.is_empty() => "false".to_string(),
+            Self::Or(formulas) => joined(formulas, " \\/ "),
+            Self::Implies(condition, consequence) => format!("({} -> {})", condition.to_minizinc(), consequence.to_minizinc()),
+        }
+    }
+
+    fn to_smtlib(&self) -> String {
+        let joined = |formulas: &[Formula], operator: &str| {
+            format!("({} {})", operator, formulas.iter().map(Self::to_smtlib).collect::<Vec<_>>().join(" "))
+        };
+
+        match self {
+            Self::Equal(left, right) => format!("(= {} {})", left.to_smtlib(), right.to_smtlib()),
+            Self::NotEqual(left, right) => format!("(not (= {} {}))", left.to_smtlib(), right.to_smtlib()),
+            Self::Less(left, right) => format!("(< {} {})", left.to_smtlib(), right.to_smtlib()),
+            // distinct needs at least two arguments, and fewer than that are always different
+            Self::AllDifferent(terms) if terms.len() < 2 => "true".to_string(),
+            Self::AllDifferent(terms) => format!("(distinct {})", terms.iter().map(Term::to_smtlib).collect::<Vec<_>>().join(" ")),
+            Self::And(formulas) if formulas.is_empty() => "true".to_string(),
+            Self::And(formulas) => joined(formulas, "and"),
+            Self::Or(formulas) if formulas.is_empty() => "false".to_string(),
+            Self::Or(formulas) => joined(formulas, "or"),
+            Self::Implies(condition, consequence) => format!("(=> {} {})", condition.to_smtlib(), consequence.to_smtlib()),
+        }
+    }
+}
+
+/*
+ * A puzzle as a model for a constraint solver, with an integer variable for each cell that can
+ * take any of the grid's values, the givens fixed, and each constraint put as formulas over those
+ * variables.  It can be written out as a MiniZinc model or an SMT-LIB2 script, and the values the
+ * solver reports back can be read into a solved grid.
+ *
+ * Both outputs name each cell after its row and column counting from 1, such as r3c5, which is
+ * what the importer looks for
+ */
+#[derive(Debug, Clone)]
+pub struct Formulation {
+    shape: GridShape,
+    givens: Vec<(GridReference, u8)>,
+    formulas: Vec<Formula>,
+}
+
+impl Formulation {
+    pub fn from_grid(grid: &Grid) -> Result<Self, Box<dyn Error>> {
+        let shape = *grid.shape();
+        let givens = shape.grid_refs()
+            .filter_map(|grid_ref| grid.cell(&grid_ref).map(|value| (grid_ref, value)))
+            .collect();
+
+        let mut formulas = Vec::new();
+        for constraint in grid.constraints() {
+            let Some(constraint_formulas) = constraint.formulas(&shape) else {
+                return Err(UnexportableConstraint::new(format!("{:?}", constraint)).into());
+            };
+            formulas.extend(constraint_formulas);
+        }
+
+        Ok(Self { shape, givens, formulas })
+    }
+
+    pub fn shape(&self) -> &GridShape {
+        &self.shape
+    }
+
+    pub fn formulas(&self) -> &[Formula] {
+        &self.formulas
+    }
+
+    /// A MiniZinc model, which prints each cell's value as r1c1 = 5 and so on once solved
+    pub fn to_minizinc(&self) -> String {
+        let (rows, columns, max_value) = (self.shape.rows(), self.shape.columns(), self.shape.max_value());
+        let mut output = format!(
+            "% {} x {} grid\ninclude \"alldifferent.mzn\";\n\narray[1..{}, 1..{}] of var 1..{}: grid;\n\n",
+            rows, columns, rows, columns, max_value,
+        );
+
+        for (grid_ref, value) in self.givens.iter() {
+            output.push_str(&format!("constraint {} = {};\n", Term::Cell(grid_ref.clone()).to_minizinc(), value));
+        }
+        for formula in self.formulas.iter() {
+            output.push_str(&format!("constraint {};\n", formula.to_minizinc()));
+        }
+
+        output.push_str(&format!(
+            "\nsolve satisfy;\n\noutput [\"r\\(r)c\\(c) = \\(grid[r,c])\\n\" | r in 1..{}, c in 1..{}];\n",
+            rows, columns,
+        ));
+
+        output
+    }
+
+    /// An SMT-LIB2 script over integers, which asks for each cell's value once it's found a model
+    pub fn to_smtlib(&self) -> String {
+        let max_value = self.shape.max_value();
+        let mut output = format!("; {} x {} grid\n(set-logic QF_LIA)\n", self.shape.rows(), self.shape.columns());
+
+        let names: Vec<String> = self.shape.grid_refs().map(|grid_ref| cell_name(&grid_ref)).collect();
+        for name in names.iter() {
+            output.push_str(&format!("(declare-const {} Int)\n(assert (and (<= 1 {}) (<= {} {})))\n", name, name, name, max_value));
+        }
+
+        for (grid_ref, value) in self.givens.iter() {
+            output.push_str(&format!("(assert (= {} {}))\n", cell_name(grid_ref), value));
+        }
+        for formula in self.formulas.iter() {
+            output.push_str(&format!("(assert {})\n", formula.to_smtlib()));
+        }
+
+        output.push_str(&format!("(check-sat)\n(get-value ({}))\n", names.join(" ")));
+
+        output
+    }
+
+    /*
+     * Reads the values a solver reported back into the grid, filling in its empty cells.  Each
+     * cell's name is followed by its value, which covers the lines the MiniZinc model prints as
+     * well as SMT-LIB's get-value pairs and the define-fun lines of get-model.  The values are set
+     * in the usual way, so an assignment that doesn't fit the grid's constraints is turned away
+     */
+    pub fn solution_from_output(&self, grid: &Grid, output: &str) -> Result<Grid, Box<dyn Error>> {
+        if output.lines().map(str::trim).any(|line| line == "unsat" || line == "=====UNSATISFIABLE=====") {
+            return Err(UnsatisfiableModel::new().into());
+        }
+
+        let names: HashMap<String, GridReference> = self.shape.grid_refs()
+            .map(|grid_ref| (cell_name(&grid_ref), grid_ref))
+            .collect();
+        let mut values: HashMap<GridReference, u8> = HashMap::new();
+
+        let mut tokens = output
+            .split(|character: char| character.is_whitespace() || "()=;,".contains(character))
+            .filter(|token| !token.is_empty() && *token != "Int");
+        while let Some(token) = tokens.next() {
+            let Some(grid_ref) = names.get(token) else {
+                continue;
+            };
+
+            let value = tokens.next()
+                .and_then(|value| value.parse::<u8>().ok())
+                .ok_or_else(|| UnreadableAssignment::new(token))?;
+            values.insert(grid_ref.clone(), value);
+        }
+
+        let mut solution = grid.clone();
+        for grid_ref in self.shape.grid_refs().filter(|grid_ref| grid.cell(grid_ref).is_none()) {
+            let value = values.get(&grid_ref).ok_or_else(|| IncompleteModel::new(grid_ref.row(), grid_ref.column()))?;
+            solution.set_cell(&grid_ref, &CellValue::new(*value)?)?;
+        }
+
+        Ok(solution)
+    }
+}
+
+/// Cells are named by row and column counting from 1, such as r1c1
+fn cell_name(grid_ref: &GridReference) -> String {
+    format!("r{}c{}", grid_ref.row() + 1, grid_ref.column() + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sudoku::constraint::{CandidateSet, Constraint};
+    use crate::sudoku::line::Thermometer;
+
+    const PUZZLE: &str = "1...........4..2";
+    const SOLVED: &str = "1234342121434312";
+
+    fn cell(row: usize, column: usize) -> GridReference {
+        GridReference::for_shape(&GridShape::new(2, 2).unwrap(), row, column).unwrap()
+    }
+
+    #[test]
+    fn writes_a_minizinc_model() {
+        let mut puzzle: Grid = PUZZLE.parse().unwrap();
+        puzzle.add_constraint(Thermometer::new(vec![cell(1, 2), cell(1, 3)]).unwrap()).unwrap();
+        let model = Formulation::from_grid(&puzzle).unwrap().to_minizinc();
+
+        assert!(model.starts_with("% 4 x 4 grid\ninclude \"alldifferent.mzn\";\n"));
+        assert!(model.contains("array[1..4, 1..4] of var 1..4: grid;"));
+        assert!(model.contains("constraint grid[1,1] = 1;\nconstraint grid[4,1] = 4;\nconstraint grid[4,4] = 2;\n"));
+        assert!(model.contains("constraint alldifferent([grid[1,1], grid[1,2], grid[1,3], grid[1,4]]);"));
+        assert!(model.contains("constraint grid[2,3] < grid[2,4];"));
+        assert!(model.ends_with("solve satisfy;\n\noutput [\"r\\(r)c\\(c) = \\(grid[r,c])\\n\" | r in 1..4, c in 1..4];\n"));
+        assert_eq!(model.matches("alldifferent(").count(), 12);
+    }
+
+    #[test]
+    fn writes_an_smtlib_script() {
+        let mut puzzle: Grid = PUZZLE.parse().unwrap();
+        puzzle.add_constraint(Thermometer::new(vec![cell(1, 2), cell(1, 3)]).unwrap()).unwrap();
+        let script = Formulation::from_grid(&puzzle).unwrap().to_smtlib();
+
+        assert!(script.starts_with("; 4 x 4 grid\n(set-logic QF_LIA)\n(declare-const r1c1 Int)\n(assert (and (<= 1 r1c1) (<= r1c1 4)))\n"));
+        assert!(script.contains("(assert (= r4c4 2))\n"));
+        assert!(script.contains("(assert (distinct r1c1 r1c2 r1c3 r1c4))\n"));
+        assert!(script.contains("(assert (< r2c3 r2c4))\n"));
+        assert!(script.ends_with("(check-sat)\n(get-value (r1c1 r1c2 r1c3 r1c4 r2c1 r2c2 r2c3 r2c4 r3c1 r3c2 r3c3 r3c4 r4c1 r4c2 r4c3 r4c4))\n"));
+    }
+
+    #[test]
+    fn reads_back_what_the_solvers_print() {
+        let puzzle: Grid = PUZZLE.parse().unwrap();
+        let formulation = Formulation::from_grid(&puzzle).unwrap();
+        let values: Vec<char> = SOLVED.chars().collect();
+        let cells: Vec<(String, char)> = puzzle.shape()
+            .grid_refs()
+            .map(|grid_ref| (cell_name(&grid_ref), values[grid_ref.to_index(puzzle.shape())]))
+            .collect();
+
+        let minizinc: String = cells.iter().map(|(name, value)| format!("{} = {}\n", name, value)).collect();
+        let minizinc = formulation.solution_from_output(&puzzle, &format!("{}----------\n", minizinc)).unwrap();
+        assert_eq!(minizinc.to_line(), SOLVED);
+
+        let pairs: Vec<String> = cells.iter().map(|(name, value)| format!("({} {})", name, value)).collect();
+        let smtlib = formulation.solution_from_output(&puzzle, &format!("sat\n({})\n", pairs.join("\n "))).unwrap();
+        assert_eq!(smtlib.to_line(), SOLVED);
+    }
+
+    #[test]
+    fn turns_away_answers_it_cant_use() {
+        let puzzle: Grid = PUZZLE.parse().unwrap();
+        let formulation = Formulation::from_grid(&puzzle).unwrap();
+
+        assert!(formulation.solution_from_output(&puzzle, "unsat\n").unwrap_err().is::<UnsatisfiableModel>());
+        assert!(formulation.solution_from_output(&puzzle, "=====UNSATISFIABLE=====\n").unwrap_err().is::<UnsatisfiableModel>());
+        assert!(formulation.solution_from_output(&puzzle, "r1c2 = 2\n").unwrap_err().is::<IncompleteModel>());
+        assert!(formulation.solution_from_output(&puzzle, "r1c2 = two\n").unwrap_err().is::<UnreadableAssignment>());
+    }
+
+    #[test]
+    fn turns_away_constraints_it_cant_export() {
+        #[derive(Debug)]
+        struct Unexportable;
+
+        impl Constraint for Unexportable {
+            fn affected_cells(&self, _shape: &GridShape) -> Vec<GridReference> {
+                Vec::new()
+            }
+
+            fn check(&self, _grid: &Grid, _changed: &GridReference) -> Result<(), Box<dyn Error>> {
+                Ok(())
+            }
+
+            fn prune(&self, _grid: &Grid, _candidates: &mut [CandidateSet]) {}
+        }
+
+        let mut grid = Grid::new();
+        grid.add_constraint(Unexportable).unwrap();
+        assert!(Formulation::from_grid(&grid).unwrap_err().is::<UnexportableConstraint>());
+    }
+}
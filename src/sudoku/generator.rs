@@ -0,0 +1,450 @@
+use crate::sudoku::clue_mask::ClueMask;
+use crate::sudoku::error::GenerationBudgetExhausted;
+use crate::sudoku::grid::{CellValue, Grid};
+use crate::sudoku::option_finder::{OptionFinder, StandardOptionFinder};
+use crate::sudoku::random::SeededRng;
+use crate::sudoku::rater::{DifficultyBand, Rater, Rating};
+use crate::sudoku::reference::GridReference;
+use crate::sudoku::shape::GridShape;
+use crate::sudoku::solver::{DepthFirstSolver, SolverBuilder};
+use crate::sudoku::symmetry::Symmetry;
+use std::error::Error;
+
+#[derive(Debug, Clone)]
+pub struct GeneratedPuzzle {
+    puzzle: Grid,
+    solution: Grid,
+}
+
+impl GeneratedPuzzle {
+    pub fn new(puzzle: Grid, solution: Grid) -> Self {
+        Self { puzzle, solution }
+    }
+
+    pub fn puzzle(&self) -> &Grid {
+        &self.puzzle
+    }
+
+    pub fn solution(&self) -> &Grid {
+        &self.solution
+    }
+
+    pub fn clue_count(&self) -> usize {
+        self.puzzle.clue_count()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RatedPuzzle {
+    puzzle: GeneratedPuzzle,
+    rating: Rating,
+}
+
+impl RatedPuzzle {
+    pub fn new(puzzle: GeneratedPuzzle, rating: Rating) -> Self {
+        Self { puzzle, rating }
+    }
+
+    pub fn puzzle(&self) -> &Grid {
+        self.puzzle.puzzle()
+    }
+
+    pub fn solution(&self) -> &Grid {
+        self.puzzle.solution()
+    }
+
+    pub fn rating(&self) -> &Rating {
+        &self.rating
+    }
+}
+
+/// Passed to the progress callback after every attempt when generating to a difficulty band
+#[derive(Debug, Clone)]
+pub struct GenerationProgress {
+    pub attempts: usize,
+    pub max_attempts: usize,
+    pub accepted: usize,
+    pub wanted: usize,
+    pub last_rating: Rating,
+}
+
+#[derive(Debug)]
+pub struct Generator {
+    rng: SeededRng,
+    shape: GridShape,
+    target_clues: Option<usize>,
+    symmetry: Symmetry,
+    solver: SolverBuilder,
+}
+
+impl Generator {
+    /// How many solutions to count up to when comparing candidate puzzles for a clue mask
+    const MASK_SOLUTION_CAP: usize = 64;
+    /// How many candidate puzzles in a row can fail to improve on the best so far before we give up
+    /// on the current starting point for a clue mask
+    const MASK_RESTART_AFTER: usize = 200;
+
+    pub fn new(seed: u64) -> Self {
+        Self {
+            rng: SeededRng::new(seed),
+            shape: GridShape::standard(),
+            target_clues: None,
+            symmetry: Symmetry::None,
+            solver: DepthFirstSolver::builder(),
+        }
+    }
+
+    /// The shape of the grids to generate, which is the standard 9 x 9 grid unless told otherwise
+    pub fn set_shape(&mut self, shape: GridShape) -> &mut Self {
+        self.shape = shape;
+        self
+    }
+
+    pub fn shape(&self) -> &GridShape {
+        &self.shape
+    }
+
+    /// Stop removing clues once the puzzle is down to this many givens.  Without a target, clues
+    /// are removed until none can be taken away without the puzzle losing its unique solution
+    pub fn set_target_clues(&mut self, target_clues: Option<usize>) -> &mut Self {
+        self.target_clues = target_clues;
+        self
+    }
+
+    pub fn target_clues(&self) -> Option<usize> {
+        self.target_clues
+    }
+
+    /// Clues are removed in the groups of cells that the symmetry maps onto each other, so the
+    /// givens of the generated puzzle always have that symmetry
+    pub fn set_symmetry(&mut self, symmetry: Symmetry) -> &mut Self {
+        self.symmetry = symmetry;
+        self
+    }
+
+    pub fn symmetry(&self) -> Symmetry {
+        self.symmetry
+    }
+
+    /// The solver that checks each candidate puzzle, which is the depth-first solver unless told
+    /// otherwise
+    pub fn set_solver(&mut self, solver: SolverBuilder) -> &mut Self {
+        self.solver = solver;
+        self
+    }
+
+    pub fn solver(&self) -> SolverBuilder {
+        self.solver
+    }
+
+    pub fn generate(&mut self) -> Result<GeneratedPuzzle, Box<dyn Error>> {
+        let solution = self.random_solution()?;
+        let puzzle = self.remove_clues(&solution)?;
+
+        Ok(GeneratedPuzzle::new(puzzle, solution))
+    }
+
+    /*
+     * Keep generating and rating puzzles, holding on to the ones that fall in the band, until we
+     * have as many as were asked for or we've run out of attempts.  Running out of attempts isn't
+     * treated as an error; the caller gets however many puzzles were found and can compare that
+     * with what they asked for.  Hard bands (especially ones requiring a specific technique) can
+     * reject the vast majority of puzzles, hence the attempt limit
+     */
+    pub fn generate_in_band(
+        &mut self,
+        band: &DifficultyBand,
+        wanted: usize,
+        max_attempts: usize,
+        on_progress: &mut dyn FnMut(&GenerationProgress),
+    ) -> Result<Vec<RatedPuzzle>, Box<dyn Error>> {
+        let mut accepted: Vec<RatedPuzzle> = Vec::with_capacity(wanted);
+        let mut attempts = 0;
+
+        while accepted.len() < wanted && attempts < max_attempts {
+            attempts += 1;
+
+            let generated = self.generate()?;
+            let rating = Rater::new(generated.puzzle()).rate();
+            if band.contains(&rating) {
+                accepted.push(RatedPuzzle::new(generated, rating.clone()));
+            }
+
+            on_progress(&GenerationProgress {
+                attempts,
+                max_attempts,
+                accepted: accepted.len(),
+                wanted,
+                last_rating: rating,
+            });
+        }
+
+        Ok(accepted)
+    }
+
+    /*
+     * Find a puzzle whose givens fill exactly the cells in the mask.
+     *
+     * Taking random solutions and keeping the values under the mask almost never gives a unique
+     * puzzle for a sparse mask, so instead we hill-climb: starting from a random solution's values,
+     * repeatedly change the value of one masked cell and keep the change if the puzzle is still
+     * solvable and has no more solutions than before.  Counting is capped, as we only need enough
+     * resolution to tell that we're heading in the right direction.  If we go too long without
+     * improving we start again from a new random solution.
+     *
+     * The budget is the number of candidate puzzles we're prepared to check.  Below some number of
+     * clues no puzzle can be unique (17 for the standard grid), so a mask that sparse will always
+     * exhaust the budget
+     */
+    pub fn generate_from_mask(&mut self, mask: &ClueMask, max_attempts: usize) -> Result<GeneratedPuzzle, Box<dyn Error>> {
+        let shape = *mask.shape();
+        let masked_cells: Vec<GridReference> = shape.grid_refs()
+            .filter(|grid_ref| mask.is_clue(grid_ref))
+            .collect();
+
+        let mut attempts = 0;
+        while attempts < max_attempts && !masked_cells.is_empty() {
+            let solution = self.random_solution_for(shape)?;
+            let mut puzzle = Grid::with_shape(shape);
+            for grid_ref in masked_cells.iter() {
+                if let Some(value) = solution.cell(grid_ref) {
+                    puzzle.set_cell(grid_ref, &CellValue::new(*value)?)?;
+                }
+            }
+
+            attempts += 1;
+            let mut solution_count = self.count_solutions(&puzzle)?;
+            let mut since_improvement = 0;
+
+            while solution_count > 1 && attempts < max_attempts && since_improvement < Self::MASK_RESTART_AFTER {
+                let grid_ref = &masked_cells[self.rng.below(masked_cells.len())];
+                let old_value = puzzle.cell(grid_ref).expect("Masked cells always hold a value");
+                let new_value = self.rng.below(shape.size()) as u8 + 1;
+                if new_value == old_value {
+                    continue;
+                }
+
+                attempts += 1;
+                since_improvement += 1;
+
+                puzzle.clear_cell(grid_ref);
+                if puzzle.set_cell(grid_ref, &CellValue::new(new_value)?).is_ok() {
+                    let new_count = self.count_solutions(&puzzle)?;
+                    if new_count > 0 && new_count <= solution_count {
+                        if new_count < solution_count {
+                            since_improvement = 0;
+                        }
+                        solution_count = new_count;
+                        continue;
+                    }
+                    puzzle.clear_cell(grid_ref);
+                }
+                puzzle.set_cell(grid_ref, &CellValue::new(old_value)?)?;
+            }
+
+            if solution_count == 1 {
+                let solution = self.solutions_of(&puzzle, 1)?.pop().expect("Puzzle has exactly one solution");
+                return Ok(GeneratedPuzzle::new(puzzle, solution));
+            }
+        }
+
+        Err(GenerationBudgetExhausted::new(attempts).into())
+    }
+
+    /// Builds a random, completely filled, valid grid
+    pub fn random_solution(&mut self) -> Result<Grid, Box<dyn Error>> {
+        self.random_solution_for(self.shape)
+    }
+
+    fn random_solution_for(&mut self, shape: GridShape) -> Result<Grid, Box<dyn Error>> {
+        let mut grid = Grid::with_shape(shape);
+
+        if !self.fill(&mut grid)? {
+            // An empty grid always has a solution, so this should be unreachable
+            panic!("Failed to fill an empty grid");
+        }
+
+        Ok(grid)
+    }
+
+    /*
+     * This is the same depth-first approach as the solver takes, filling the most constrained cell
+     * first, except the options for each cell are shuffled before we try them so that every seed
+     * leads to a different completed grid.  Filling in row order works for the standard grid but
+     * can get stuck backtracking for a very long time on the larger ones
+     */
+    fn fill(&mut self, grid: &mut Grid) -> Result<bool, Box<dyn Error>> {
+        let mut finder = StandardOptionFinder::new(grid);
+        let most_constrained = grid.shape()
+            .grid_refs()
+            .filter(|grid_ref| grid.cell(grid_ref).is_none())
+            .map(|grid_ref| {
+                let options = finder.find_for_cell(&grid_ref);
+                (grid_ref, options)
+            })
+            .min_by_key(|(_, options)| options.len());
+
+        let Some((grid_ref, mut options)) = most_constrained else {
+            return Ok(true);
+        };
+        self.rng.shuffle(&mut options);
+
+        for option in options {
+            if grid.set_cell(&grid_ref, &CellValue::new(option)?).is_ok() && self.fill(grid)? {
+                return Ok(true);
+            }
+            grid.clear_cell(&grid_ref);
+        }
+
+        Ok(false)
+    }
+
+    /*
+     * Visit every group of symmetric cells in a random order and try removing their clues.  If the
+     * puzzle no longer has a unique solution then the clues go back in.  Removing clues can only
+     * ever add solutions, so a group that had to stay in at some point can never be removed later
+     * on, meaning a single pass is enough to leave a puzzle where no further clues can be removed
+     *
+     * When there's a target clue count, groups that would take the puzzle below the target are
+     * skipped, so we'll never end up with fewer clues than asked for
+     */
+    fn remove_clues(&mut self, solution: &Grid) -> Result<Grid, Box<dyn Error>> {
+        let mut puzzle = solution.clone();
+        let mut orbits = self.symmetry.orbits(solution.shape());
+        self.rng.shuffle(&mut orbits);
+
+        let mut clue_count = puzzle.clue_count();
+        for orbit in orbits {
+            if self.target_clues.is_some_and(|target| clue_count <= target) {
+                break;
+            }
+            if self.target_clues.is_some_and(|target| clue_count < target + orbit.len()) {
+                continue;
+            }
+
+            let mut removed: Vec<(GridReference, u8)> = Vec::with_capacity(orbit.len());
+            for (row, column) in orbit {
+                let grid_ref = GridReference::for_shape(solution.shape(), row, column)?;
+                if let Some(value) = *puzzle.cell(&grid_ref) {
+                    puzzle.clear_cell(&grid_ref);
+                    removed.push((grid_ref, value));
+                }
+            }
+
+            if self.is_unique(&puzzle)? {
+                clue_count -= removed.len();
+            } else {
+                for (grid_ref, value) in removed {
+                    puzzle.set_cell(&grid_ref, &CellValue::new(value)?)?;
+                }
+            }
+        }
+
+        Ok(puzzle)
+    }
+
+    fn is_unique(&self, puzzle: &Grid) -> Result<bool, Box<dyn Error>> {
+        Ok((self.solver)(puzzle)?.has_unique_solution())
+    }
+
+    fn count_solutions(&self, puzzle: &Grid) -> Result<usize, Box<dyn Error>> {
+        Ok((self.solver)(puzzle)?.count_solutions(Self::MASK_SOLUTION_CAP))
+    }
+
+    fn solutions_of(&self, puzzle: &Grid, limit: usize) -> Result<Vec<Grid>, Box<dyn Error>> {
+        Ok((self.solver)(puzzle)?.find_solutions(limit))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sudoku::solver::Solver;
+    use crate::sudoku::rater::Difficulty;
+
+    fn values(grid: &Grid) -> Vec<Option<u8>> {
+        (0 .. 81)
+            .map(|index| *grid.cell(&GridReference::from_numbers(index / 9, index % 9).unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn a_seed_always_gives_the_same_puzzle() {
+        let first = Generator::new(42).generate().unwrap();
+        let second = Generator::new(42).generate().unwrap();
+        let other = Generator::new(43).generate().unwrap();
+
+        assert_eq!(values(first.puzzle()), values(second.puzzle()));
+        assert_eq!(values(first.solution()), values(second.solution()));
+        assert_ne!(values(first.puzzle()), values(other.puzzle()));
+    }
+
+    #[test]
+    fn puzzles_have_one_solution_that_matches_the_givens() {
+        let generated = Generator::new(7).generate().unwrap();
+        let puzzle = values(generated.puzzle());
+        let solution = values(generated.solution());
+
+        assert!(DepthFirstSolver::for_problem(generated.puzzle()).has_unique_solution());
+        assert!(solution.iter().all(Option::is_some));
+        assert!(puzzle.iter().zip(solution.iter()).all(|(given, value)| given.is_none() || given == value));
+    }
+
+    #[test]
+    fn keeps_the_symmetry_asked_for() {
+        for symmetry in [Symmetry::Rotational180, Symmetry::Rotational90, Symmetry::Diagonal] {
+            let generated = Generator::new(11).set_symmetry(symmetry).generate().unwrap();
+
+            assert!(symmetry.is_present_in(generated.puzzle()), "{}", symmetry);
+        }
+    }
+
+    #[test]
+    fn only_keeps_puzzles_in_the_band() {
+        let band = DifficultyBand::exactly(Difficulty::Easy);
+        let mut reports = 0;
+        let puzzles = Generator::new(3).generate_in_band(&band, 2, 20, &mut |_| reports += 1).unwrap();
+
+        assert_eq!(puzzles.len(), 2);
+        assert!(puzzles.iter().all(|puzzle| band.contains(puzzle.rating())));
+        assert!(reports >= 2);
+    }
+
+    #[test]
+    fn fills_exactly_the_cells_of_a_mask() {
+        let mask = ClueMask::from_grid(Generator::new(5).generate().unwrap().puzzle());
+        let generated = Generator::new(6).generate_from_mask(&mask, 5000).unwrap();
+
+        assert!(DepthFirstSolver::for_problem(generated.puzzle()).has_unique_solution());
+        for index in 0 .. 81 {
+            let grid_ref = GridReference::from_numbers(index / 9, index % 9).unwrap();
+            assert_eq!(generated.puzzle().cell(&grid_ref).is_some(), mask.is_clue(&grid_ref));
+        }
+    }
+
+    #[test]
+    fn gives_up_on_a_mask_too_sparse_to_be_unique() {
+        let mask: ClueMask = format!("{}{}", "X".repeat(16), ".".repeat(65)).parse().unwrap();
+        let error = Generator::new(5).generate_from_mask(&mask, 50).unwrap_err();
+
+        assert!(error.is::<GenerationBudgetExhausted>());
+    }
+
+    #[test]
+    fn generates_other_shapes() {
+        for shape in [GridShape::new(2, 2).unwrap(), GridShape::new(2, 3).unwrap(), GridShape::new(3, 2).unwrap()] {
+            let generated = Generator::new(8).set_shape(shape).generate().unwrap();
+
+            assert_eq!(*generated.puzzle().shape(), shape);
+            assert_eq!(generated.solution().clue_count(), shape.cell_count());
+            assert!(DepthFirstSolver::for_problem(generated.puzzle()).has_unique_solution());
+        }
+    }
+
+    #[test]
+    fn stops_at_the_target_clue_count() {
+        let generated = Generator::new(7).set_target_clues(Some(40)).generate().unwrap();
+
+        assert_eq!(generated.clue_count(), 40);
+    }
+}
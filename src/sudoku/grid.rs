@@ -1,40 +1,77 @@
+use crate::sudoku::constraint::{jigsaw_constraints, standard_constraints, Constraint};
 use crate::sudoku::error::*;
 use crate::sudoku::reference::*;
-use colored::Colorize;
+use crate::sudoku::region_map::RegionMap;
+use crate::sudoku::shape::GridShape;
+use colored::{ColoredString, Colorize};
 use std::error::Error;
-use std::fmt::{Display, Error as fmtError, Formatter, Result as FmtResult};
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::str::FromStr;
+use std::sync::Arc;
 
 #[derive(Debug)]
 #[derive(Clone)]
 pub struct Grid {
+    shape: GridShape,
+    constraints: Vec<Arc<dyn Constraint>>,
+    // For each cell, the positions in constraints of the ones that affect it
+    constraints_by_cell: Arc<Vec<Vec<usize>>>,
+    // Which region each cell is in, for looking up regions and drawing the borders between them
+    regions: Arc<RegionMap>,
     // @todo Use the CellValue struct instead of U8 once I'm a bit more familiar with moving/borrowing
     grid_data: Vec<Option<u8>>,
 }
 
 impl Grid {
-    pub(crate) const GRID_ROWS: usize = 9;
-    pub(crate) const GRID_COLUMNS: usize = 9;
-    pub(crate) const SUBGRID_ROWS: usize = 3;
-    pub(crate) const SUBGRID_COLUMNS: usize = 3;
-
+    /// An empty standard 9 x 9 grid
     pub fn new() -> Self {
-        Self {
-            grid_data: vec![None; Self::GRID_ROWS * Self::GRID_COLUMNS],
-        }
+        Self::with_shape(GridShape::standard())
+    }
+
+    /// An empty grid of the given shape, following the standard rules
+    pub fn with_shape(shape: GridShape) -> Self {
+        Self::with_constraints(shape, standard_constraints(&shape))
+    }
+
+    /// An empty grid following only the given rules, for variants that replace some of the
+    /// standard ones rather than adding to them
+    pub fn with_constraints(shape: GridShape, constraints: Vec<Arc<dyn Constraint>>) -> Self {
+        Self::with_rules(RegionMap::standard(shape), constraints)
+    }
+
+    /// An empty jigsaw grid, where the given regions take the place of the subgrids
+    pub fn with_regions(regions: RegionMap) -> Self {
+        let constraints = jigsaw_constraints(&regions);
+
+        Self::with_rules(regions, constraints)
+    }
+
+    /// An empty grid with the given regions drawn on it, following only the given rules.  It's up
+    /// to the caller to make sure the rules include any the regions stand for
+    pub(crate) fn with_rules(regions: RegionMap, constraints: Vec<Arc<dyn Constraint>>) -> Self {
+        let shape = *regions.shape();
+        let mut grid = Self {
+            shape,
+            constraints,
+            constraints_by_cell: Arc::new(Vec::new()),
+            regions: Arc::new(regions),
+            grid_data: vec![None; shape.cell_count()],
+        };
+        grid.index_constraints();
+
+        grid
     }
 
-    pub fn from_array(array_grid: [[Option<u8>;Self::GRID_ROWS];Self::GRID_COLUMNS]) -> Result<Self, Box<dyn Error>> {
+    pub fn from_array(array_grid: [[Option<u8>; 9]; 9]) -> Result<Self, Box<dyn Error>> {
         let mut this_grid = Self::new();
-        let mut val: Option<u8>;
 
-        for row in 0 .. Self::GRID_COLUMNS {
-            for col in 0 .. Self::GRID_ROWS {
-                val = array_grid[row][col];
-                if val.is_some() {
-                    // Is it safe to use unwrap() here?
+        for (row, values) in array_grid.iter().enumerate() {
+            for (col, val) in values.iter().enumerate() {
+                if let Some(val) = val {
                     this_grid.set_cell(
                         &GridReference::from_numbers(row, col)?,
-                        &CellValue::new(val.unwrap())?,
+                        &CellValue::new(*val)?,
                     )?;
                 }
             }
@@ -43,13 +80,67 @@ impl Grid {
         Ok(this_grid)
     }
 
+    /// Reads a jigsaw puzzle in the same format as parse(), with the regions given separately
+    pub fn parse_jigsaw(puzzle: &str, regions: RegionMap) -> Result<Self, Box<dyn Error>> {
+        let cells = Self::puzzle_cells(puzzle);
+        if cells.len() != regions.shape().cell_count() {
+            return Err(InvalidPuzzleLength::new(cells.len()).into());
+        }
+
+        Self::with_regions(regions).fill_from(cells)
+    }
+
+    pub fn shape(&self) -> &GridShape {
+        &self.shape
+    }
+
+    pub fn regions(&self) -> &RegionMap {
+        &self.regions
+    }
+
+    pub fn constraints(&self) -> &[Arc<dyn Constraint>] {
+        &self.constraints
+    }
+
+    /// The constraints that restrict the given cell
+    pub fn constraints_at(&self, grid_ref: &GridReference) -> impl Iterator<Item = &Arc<dyn Constraint>> {
+        self.constraints_by_cell[self.index_of(grid_ref)]
+            .iter()
+            .map(|position| &self.constraints[*position])
+    }
+
+    /// Adds a rule to the grid.  The rule's cells have to be within the grid, and any values
+    /// already in the grid have to follow it
+    pub fn add_constraint(&mut self, constraint: impl Constraint + 'static) -> Result<&mut Self, Box<dyn Error>> {
+        let constraint: Arc<dyn Constraint> = Arc::new(constraint);
+        if let Some(shape) = constraint.shape() && shape != self.shape {
+            return Err(ShapeMismatch::new(&shape, &self.shape).into());
+        }
+
+        let affected_cells = constraint.affected_cells(&self.shape);
+        for grid_ref in affected_cells.iter() {
+            RowReference::for_shape(&self.shape, grid_ref.row())?;
+            ColumnReference::for_shape(&self.shape, grid_ref.column())?;
+        }
+
+        for grid_ref in affected_cells.iter() {
+            constraint.check(self, grid_ref)?;
+        }
+
+        self.constraints.push(constraint);
+        self.index_constraints();
+
+        Ok(self)
+    }
+
     pub fn cell(&self, grid_ref: &GridReference) -> &Option<u8> {
-        &self.grid_data[grid_ref.to_index()]
+        &self.grid_data[self.index_of(grid_ref)]
     }
 
     pub fn row(&self, row_ref: &RowReference) -> &[Option<u8>] {
         let row = row_ref.row();
-        &self.grid_data[row * Self::GRID_COLUMNS..(row + 1) * Self::GRID_COLUMNS]
+        let columns = self.shape.columns();
+        &self.grid_data[row * columns .. (row + 1) * columns]
     }
 
     // @todo This is probably not the preferred way to extrapolate the columns and it returns a Vec
@@ -66,198 +157,611 @@ impl Grid {
             .iter()
             .skip(column_ref.column())
             // Stepping is to the same column on the next row
-            .step_by(Self::GRID_ROWS)
+            .step_by(self.shape.columns())
             .collect::<Vec<&Option<u8>>>()
     }
 
-    // @todo This is a pretty hacky POC and could use a refactor into something that handles
-    // selecting the subslices more elegantly
-    pub fn subgrid(&self, subgrid_ref: &SubgridReference) -> Vec<Option<u8>> {
-        /*
-         * As we're simulating the grid with a 1-dimensional array, a "subgrid" can be considered to
-         * be 3 sub-slices of 3 elements each, comprising a total of the 9 elements that make up the
-         * subgrid.
-         *
-         * The start of the first slice of each sub grid lies in column 0, 3, or 6, and row 0, 3, or
-         * 6.  If we're using IDs of 0 .. 8 to refer to each subgrid, then that means that the first
-         * slice of each subgrid starts at index 0, 3, 6, then skips to 27, 30, 33, and so on.  Each
-         * slice is 3 elements long, and the entire subgrid is made up of the sub-slices starting in
-         * the same column from the next two rows (so subgrid 0 consists of the array elements
-         * [0, 1, 2, 9, 10, 11, 18, 19, 20], subgrid 4 would consist of the elements
-         * [30, 31, 32, 39, 40, 41, 48, 49, 50], and so on)
-         */
-        let subgrid_id = subgrid_ref.subgrid();
-        let subgrid_col = subgrid_id * Self::SUBGRID_ROWS % Self::GRID_ROWS;
-        let subgrid_row = (
-            (subgrid_id * Self::GRID_COLUMNS) / (Self::GRID_COLUMNS * Self::SUBGRID_COLUMNS)
-        ) * (Self::GRID_COLUMNS * Self::SUBGRID_COLUMNS);
-        let subgrid_index = subgrid_col + subgrid_row;
-
-        let mut subgrid:Vec<Option<u8>> = Vec::new();
-        for row_start in 0 .. 3 {
-            subgrid.extend_from_slice(&self.grid_data[subgrid_index + (9 * row_start) .. subgrid_index + 3 + (9 * row_start)]);
-        }
-
-        subgrid
+    /// The values of a region, in row order.  In standard Sudoku the regions are the subgrids, and
+    /// are numbered left to right, then top to bottom
+    pub fn region(&self, region_ref: &RegionReference) -> Vec<Option<u8>> {
+        self.regions.cells_of(region_ref.region())
+            .iter()
+            .map(|grid_ref| *self.cell(grid_ref))
+            .collect()
     }
 
-    pub fn subgrid_at(&self, grid_ref: &GridReference) -> Vec<Option<u8>> {
-        self.subgrid(&SubgridReference::from_grid_ref(grid_ref))
+    pub fn region_at(&self, grid_ref: &GridReference) -> Vec<Option<u8>> {
+        self.region(&RegionReference::from_grid_ref(&self.regions, grid_ref))
     }
 
     pub fn row_values(&self, row_ref: &RowReference) -> Vec<u8> {
-        let row: &[Option<u8>] = self.row(&row_ref);
-
-        // Is it safe to use unwrap() here?
-        row.iter()
-            .filter(|row| row.is_some())
-            .map(|row| row.unwrap())
-            .collect()
+        self.row(row_ref).iter().flatten().copied().collect()
     }
 
     pub fn col_values(&self, column_ref: &ColumnReference) -> Vec<u8> {
-        let col: Vec<&Option<u8>> = self.column(column_ref);
-
-        // Is it safe to use unwrap here?
-        col.iter()
-            .filter(|row| row.is_some())
-            .map(|row| row.unwrap())
-            .collect()
+        self.column(column_ref).into_iter().flatten().copied().collect()
     }
 
-    pub fn subgrid_values(&self, subgrid_ref: &SubgridReference) -> Vec<u8> {
-        let subgrid: Vec<Option<u8>> = self.subgrid(&subgrid_ref);
-
-        // Is it safe to use unwrap here?
-        subgrid.iter()
-            .filter(|row| row.is_some())
-            .map(|row| row.unwrap())
-            .collect()
+    pub fn region_values(&self, region_ref: &RegionReference) -> Vec<u8> {
+        self.region(region_ref).into_iter().flatten().collect()
     }
 
-    pub fn subgrid_values_at(&self, grid_ref: &GridReference) -> Vec<u8> {
-        self.subgrid_values(&SubgridReference::from_grid_ref(&grid_ref))
+    pub fn region_values_at(&self, grid_ref: &GridReference) -> Vec<u8> {
+        self.region_values(&RegionReference::from_grid_ref(&self.regions, grid_ref))
     }
 
     pub fn set_cell(&mut self, grid_ref: &GridReference, value: &CellValue) -> Result<&mut Self, Box<dyn Error>> {
         let value = value.value();
-        let index = grid_ref.to_index();
-        let old_value = self.grid_data[index].clone();
+        if value > self.shape.max_value() {
+            return Err(AnswerRangeError::new(value, self.shape.max_value()).into());
+        }
+
+        let index = self.index_of(grid_ref);
+        let old_value = self.grid_data[index];
 
         self.grid_data[index] = Some(value);
 
-        let validated = self.validate_uniqueness(&grid_ref);
-        if validated.is_err() {
+        if let Err(err) = self.validate_constraints(grid_ref) {
             self.grid_data[index] = old_value;
-            return Err(validated.unwrap_err().into());
+            return Err(err);
         }
 
         Ok(self)
     }
 
     pub fn clear_cell(&mut self, grid_ref: &GridReference) -> &mut Self {
-        self.grid_data[grid_ref.to_index()] = None;
+        let index = self.index_of(grid_ref);
+        self.grid_data[index] = None;
         self
     }
 
-    fn validate_uniqueness(&self, grid_ref: &GridReference) -> Result<(), Box<dyn Error>> {
-        let row_ref = grid_ref.row_ref();
-        let column_ref = grid_ref.column_ref();
+    pub fn clue_count(&self) -> usize {
+        self.grid_data.iter().filter(|cell| cell.is_some()).count()
+    }
+
+    /// The grid as a single line of text, row by row, with "." for empty cells and letters for
+    /// values above 9.  This is the format most puzzle collections use, and it can be read back
+    /// in with parse()
+    pub fn to_line(&self) -> String {
+        self.grid_data
+            .iter()
+            .map(|cell| match cell {
+                Some(value) => GridShape::symbol_for(*value),
+                None => '.',
+            })
+            .collect()
+    }
 
-        if !self.row_is_unique(&row_ref) {
-            // Is it safe to use unwrap() here?
-            return Err(UniquenessError::new(
-                row_ref.row(),
-                column_ref.column(),
-                self.cell(&grid_ref).unwrap(),
-                UniquenessConstraint::Row,
-            ).into());
+    /*
+     * Draws the grid with box-drawing characters along the borders between regions.  The caller
+     * decides how each value is shown, which lets GridDiff highlight the cells that have changed.
+     * Cells that any of the constraints ask to be shaded (such as the diagonals of Sudoku X) get a
+     * grey background.  When a constraint asks for an outline (such as a Killer cage), a mark on
+     * the border between two cells (such as a Kropki dot) or a mark inside a cell (such as the
+     * circle of an odd cell) the grid is drawn with more room, so that there's space for them.
+     * Clues outside the grid (such as sandwich totals) are written in a ring around it, which also
+     * needs the extra room when there are any above or below the grid
+     */
+    pub(crate) fn render(&self, show_value: impl Fn(&GridReference, u8) -> ColoredString) -> String {
+        let mut decorations = Decorations::new(self.shape.cell_count());
+        decorations.add(self, |grid_ref| grid_ref.to_index(&self.shape));
+
+        let ring = Ring::new(self.constraints.iter().filter_map(|constraint| constraint.outside_clue(&self.shape)));
+
+        let cell = |grid_ref: &GridReference| match self.cell(grid_ref) {
+            Some(val) => show_value(grid_ref, *val),
+            None => "-".blue(),
+        };
+
+        if !decorations.needs_room() && !ring.has_edges(self.shape.rows()) {
+            return self.render_compact(cell, &decorations.shaded, &ring);
         }
 
-        if !self.col_is_unique(&column_ref) {
-            // Is it safe to use unwrap() here?
-            return Err(UniquenessError::new(
-                row_ref.row(),
-                column_ref.column(),
-                self.cell(&grid_ref).unwrap(),
-                UniquenessConstraint::Column,
-            ).into());
+        let (rows, columns) = (self.shape.rows(), self.shape.columns());
+        let frame = Frame {
+            rows,
+            columns,
+            right: &|row, col| col < 0 || self.border_right(row, col as usize),
+            above: &|row, col| row == 0 || self.border_below(row as usize - 1, col),
+        };
+        let cell = |row: usize, col: usize| {
+            cell(&GridReference::for_shape(&self.shape, row, col).expect("Row and column are always in range"))
+        };
+
+        render_expanded(&frame, cell, &decorations, &ring)
+    }
+
+    /*
+     * Every cell takes up two characters: a space, then its value.  Space for a border is only left
+     * between the columns (and rows) that some region border runs between, so the columns still
+     * line up from one row to the next.  For the standard regions that gives the familiar lattice,
+     * while a jigsaw gets space for a border almost everywhere and only draws the ones it needs
+     */
+    fn render_compact(&self, cell: impl Fn(&GridReference) -> ColoredString, shaded: &[bool], ring: &Ring) -> String {
+        let (rows, columns) = (self.shape.rows(), self.shape.columns());
+        let column_gaps: Vec<bool> = (0 .. columns).map(|col| (0 .. rows).any(|row| self.border_right(row, col))).collect();
+        let row_gaps: Vec<bool> = (0 .. rows).map(|row| (0 .. columns).any(|col| self.border_below(row, col))).collect();
+
+        // Where each column's values sit along a line, after the left border
+        let mut value_positions = Vec::with_capacity(columns);
+        let mut width = 1;
+        for gap in column_gaps.iter() {
+            value_positions.push(width + 1);
+            width += if *gap { 4 } else { 2 };
         }
 
-        if !self.subgrid_is_unique_at(grid_ref) {
-            // Is it safe to use unwrap() here?
-            return Err(UniquenessError::new(
-                row_ref.row(),
-                column_ref.column(),
-                self.cell(&grid_ref).unwrap(),
-                UniquenessConstraint::SubGrid,
-            ).into());
+        // The line along the top of the given row, with rows giving the line along the bottom of
+        // the grid
+        let line = |row: usize| {
+            let across = |col: usize| row == 0 || self.border_below(row - 1, col);
+
+            let mut line = format!("\t{}", ring.padding());
+            line.push(Self::junction(row > 0, row < rows, false, across(0)));
+            for (col, gap) in column_gaps.iter().enumerate() {
+                line.push_str(if across(col) { "──" } else { "  " });
+
+                if *gap {
+                    line.push(if across(col) { '─' } else { ' ' });
+                    line.push(Self::junction(
+                        row > 0 && self.border_right(row - 1, col),
+                        row < rows && self.border_right(row, col),
+                        across(col),
+                        col < columns - 1 && across(col + 1),
+                    ));
+                }
+            }
+
+            format!("{}\n", line).yellow().to_string()
+        };
+
+        let mut output = String::with_capacity(2048);
+        output.push_str(&ring.edge(-1, &value_positions, width));
+        output.push_str(&line(0));
+
+        for (row, row_gap) in row_gaps.iter().enumerate() {
+            output.push_str(&format!("\t{}{}", ring.left(row), "│".yellow()));
+            for (col, column_gap) in column_gaps.iter().enumerate() {
+                let grid_ref = GridReference::for_shape(&self.shape, row, col)
+                    .expect("Row and column are always in range");
+                let spacer = " ".normal();
+                let cooked_val = cell(&grid_ref);
+
+                if shaded[grid_ref.to_index(&self.shape)] {
+                    output.push_str(&format!("{}{}", spacer.on_bright_black(), cooked_val.on_bright_black()));
+                } else {
+                    output.push_str(&format!("{}{}", spacer, cooked_val));
+                }
+
+                if *column_gap {
+                    match self.border_right(row, col) {
+                        true => output.push_str(&format!("{}", " │".yellow())),
+                        false => output.push_str("  "),
+                    }
+                }
+            }
+
+            output.push_str(&format!("{}\n", ring.right(row)));
+            if *row_gap && row < rows - 1 {
+                output.push_str(&line(row + 1));
+            }
         }
+        output.push_str(&line(rows));
+        output.push_str(&ring.edge(rows as isize, &value_positions, width));
 
-        Ok(())
+        output
     }
 
-    fn row_is_unique(&self, row_ref: &RowReference) -> bool {
-        let mut row_values = self.row_values(row_ref);
-        Self::values_are_unique(&mut row_values)
+    /// Whether there's a region border to the right of the given cell.  The edge of the grid always
+    /// counts as one
+    fn border_right(&self, row: usize, col: usize) -> bool {
+        col == self.shape.columns() - 1 || !self.regions.same_region((row, col), (row, col + 1))
     }
 
-    fn col_is_unique(&self, column_ref: &ColumnReference) -> bool {
-        let mut col_values = self.col_values(&column_ref);
-        Self::values_are_unique(&mut col_values)
+    /// Whether there's a region border below the given cell.  The edge of the grid always counts as
+    /// one
+    fn border_below(&self, row: usize, col: usize) -> bool {
+        row == self.shape.rows() - 1 || !self.regions.same_region((row, col), (row + 1, col))
     }
 
-    fn subgrid_is_unique(&self, subgrid_ref: &SubgridReference) -> bool {
-        let mut subgrid_values = self.subgrid_values(&subgrid_ref);
-        Self::values_are_unique(&mut subgrid_values)
+    /// The box-drawing character where borders meet, given which directions they run off in
+    pub(crate) fn junction(up: bool, down: bool, left: bool, right: bool) -> char {
+        match (up, down, left, right) {
+            (true, true, true, true) => '┼',
+            (true, true, true, false) => '┤',
+            (true, true, false, true) => '├',
+            (false, true, true, true) => '┬',
+            (true, false, true, true) => '┴',
+            (false, true, false, true) => '┌',
+            (false, true, true, false) => '┐',
+            (true, false, false, true) => '└',
+            (true, false, true, false) => '┘',
+            (true, _, false, false) | (_, true, false, false) => '│',
+            (false, false, true, _) | (false, false, _, true) => '─',
+            (false, false, false, false) => ' ',
+        }
     }
 
-    fn subgrid_is_unique_at(&self, grid_ref: &GridReference) -> bool {
-        let mut subgrid_values = self.subgrid_values_at(&grid_ref);
-        Self::values_are_unique(&mut subgrid_values)
+    /// References are only validated against the shape they were made for, so make sure that
+    /// one made for a larger grid can't quietly point at the wrong cell of this one
+    fn index_of(&self, grid_ref: &GridReference) -> usize {
+        assert!(
+            grid_ref.row() < self.shape.rows() && grid_ref.column() < self.shape.columns(),
+            "Cell {}, {} is outside the {} x {} grid",
+            grid_ref.row(),
+            grid_ref.column(),
+            self.shape.rows(),
+            self.shape.columns(),
+        );
+
+        grid_ref.to_index(&self.shape)
     }
 
-    fn values_are_unique(values: &mut [u8]) -> bool {
-        values.sort();
+    fn index_constraints(&mut self) {
+        let mut constraints_by_cell = vec![Vec::new(); self.shape.cell_count()];
+        for (position, constraint) in self.constraints.iter().enumerate() {
+            for grid_ref in constraint.affected_cells(&self.shape) {
+                constraints_by_cell[grid_ref.to_index(&self.shape)].push(position);
+            }
+        }
 
-        for index in 0 .. values.len() - 1 {
-            if values[index] == values[index + 1] {
-                return false;
+        self.constraints_by_cell = Arc::new(constraints_by_cell);
+    }
+
+    fn validate_constraints(&self, grid_ref: &GridReference) -> Result<(), Box<dyn Error>> {
+        for constraint in self.constraints_at(grid_ref) {
+            constraint.check(self, grid_ref)?;
+        }
+
+        Ok(())
+    }
+
+    fn puzzle_cells(puzzle: &str) -> Vec<char> {
+        puzzle.chars().filter(|character| !character.is_whitespace()).collect()
+    }
+
+    fn fill_from(mut self, cells: Vec<char>) -> Result<Self, Box<dyn Error>> {
+        let shape = self.shape;
+
+        for (index, character) in cells.into_iter().enumerate() {
+            if matches!(character, '.' | '0' | '-' | '_') {
+                continue;
+            }
+
+            match GridShape::value_for(character) {
+                Some(value) if value <= shape.max_value() => {
+                    self.set_cell(
+                        &GridReference::for_shape(&shape, index / shape.columns(), index % shape.columns())?,
+                        &CellValue::new(value)?,
+                    )?;
+                },
+                _ => return Err(InvalidPuzzleCharacter::new(index, character).into()),
             }
         }
 
-        true
+        Ok(self)
+    }
+}
+
+impl Default for Grid {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
 impl Display for Grid {
     fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
-        let mut output = String::with_capacity(2048);
-        output.push_str(format!("{}", String::from("\t┌───────┬───────┬───────┐\n").yellow()).as_str());
-
-        for row in 0..Self::GRID_COLUMNS {
-            output.push_str(format!("\t{}", String::from("│").yellow()).as_str());
-            for col in 0..Self::GRID_ROWS {
-                let raw_val = self.cell(&GridReference::from_numbers(row, col).map_err(|_| fmtError)?);
-                let cooked_val = match raw_val {
-                    Some(val) => format!(" {}", val.to_string().white()),
-                    None => format!("{}", String::from(" -").blue()),
-                };
-                output.push_str(cooked_val.as_str());
-
-                if Self::SUBGRID_COLUMNS - 1 == col % Self::SUBGRID_COLUMNS {
-                    output.push_str(format!("{}", String::from(" │").yellow()).as_str());
-                }
+        let output = self.render(|_, val| GridShape::symbol_for(val).to_string().white());
+
+        write!(f, "{}", output)
+    }
+}
+
+/*
+ * Reads a puzzle from the one-line format written by to_line().  Empty cells can be given as ".",
+ * "0", "-" or "_", and whitespace is ignored so puzzles can also be laid out as a block.  The size
+ * of the grid is worked out from the number of cells, so 81 cells gives the standard grid, 36 a
+ * 6 x 6 grid with 2 x 3 subgrids, 256 a 16 x 16 grid and so on
+ */
+impl FromStr for Grid {
+    type Err = Box<dyn Error>;
+
+    fn from_str(puzzle: &str) -> Result<Self, Self::Err> {
+        let cells = Self::puzzle_cells(puzzle);
+        let shape = GridShape::from_cell_count(cells.len())
+            .ok_or_else(|| InvalidPuzzleLength::new(cells.len()))?;
+
+        Self::with_shape(shape).fill_from(cells)
+    }
+}
+
+/*
+ * What a grid's constraints add to the picture besides its values and region borders, by the
+ * position of each cell in row order.  A picture can take the decorations of several grids, which
+ * is how a MultiGrid draws the rules of all of its grids together
+ */
+pub(crate) struct Decorations {
+    shaded: Vec<bool>,
+    outlines: Vec<(Vec<usize>, String)>,
+    // Marks are looked up by the positions of the two cells, in row order
+    marks: HashMap<(usize, usize), char>,
+    cell_marks: Vec<Option<char>>,
+}
+
+impl Decorations {
+    /// No decorations, for a picture with the given number of cells
+    pub(crate) fn new(cell_count: usize) -> Self {
+        Self {
+            shaded: vec![false; cell_count],
+            outlines: Vec::new(),
+            marks: HashMap::new(),
+            cell_marks: vec![None; cell_count],
+        }
+    }
+
+    /// Adds the decorations of the grid's constraints, with position_of saying where each of the
+    /// grid's cells sits in the picture
+    pub(crate) fn add(&mut self, grid: &Grid, position_of: impl Fn(&GridReference) -> usize) {
+        let shape = grid.shape();
+
+        for constraint in grid.constraints() {
+            for grid_ref in constraint.shaded_cells(shape) {
+                self.shaded[position_of(&grid_ref)] = true;
+            }
+
+            if let Some((cells, label)) = constraint.outline(shape) {
+                self.outlines.push((cells.iter().map(&position_of).collect(), label));
+            }
+
+            for (first, second, mark) in constraint.border_marks(shape) {
+                let (first, second) = (position_of(&first), position_of(&second));
+                self.marks.insert((first.min(second), first.max(second)), mark);
             }
 
-            output.push('\n');
-            if Grid::SUBGRID_ROWS - 1 == row % Grid::SUBGRID_ROWS && row < Grid::GRID_ROWS - 1 {
-                output.push_str(format!("{}", String::from("\t├───────┼───────┼───────┤\n").yellow()).as_str());
+            for (grid_ref, mark) in constraint.cell_marks(shape) {
+                self.cell_marks[position_of(&grid_ref)] = Some(mark);
             }
         }
-        output.push_str(format!("{}", String::from("\t└───────┴───────┴───────┘\n").yellow()).as_str());
+    }
 
-        write!(f, "{}", output)
+    pub(crate) fn shaded(&self) -> &[bool] {
+        &self.shaded
+    }
+
+    /// Outlines and marks need more room than the compact picture leaves, while shading doesn't
+    pub(crate) fn needs_room(&self) -> bool {
+        !self.outlines.is_empty() || !self.marks.is_empty() || self.cell_marks.iter().any(Option::is_some)
+    }
+}
+
+/// The size of a picture and where its solid borders run.  The border to the right of a cell is
+/// asked for with column -1 for the left edge, and the border along the top of a row with the
+/// number of rows for the bottom edge
+pub(crate) struct Frame<'a> {
+    pub(crate) rows: usize,
+    pub(crate) columns: usize,
+    pub(crate) right: &'a dyn Fn(usize, isize) -> bool,
+    pub(crate) above: &'a dyn Fn(isize, usize) -> bool,
+}
+
+/// Draws a picture with room for its decorations and no clues around the outside, for when there
+/// isn't a single grid to write them around
+pub(crate) fn render_decorated(frame: &Frame, cell: impl Fn(usize, usize) -> ColoredString, decorations: &Decorations) -> String {
+    render_expanded(frame, cell, decorations, &Ring::new(std::iter::empty()))
+}
+
+/*
+ * Every cell is three characters wide, with room for a border between every pair of cells.  Region
+ * borders are drawn solid, outlines dotted, and any marks go in the middle of the border.  A cell's
+ * own mark goes just after its value.  When there are outlines each cell takes up two lines, with
+ * the outline's label in the first cell of the outline, above its value
+ */
+fn render_expanded(frame: &Frame, cell: impl Fn(usize, usize) -> ColoredString, decorations: &Decorations, ring: &Ring) -> String {
+    let (rows, columns, right, above) = (frame.rows, frame.columns, frame.right, frame.above);
+    let Decorations { shaded, outlines, marks, cell_marks } = decorations;
+    let index_of = |row: usize, col: usize| row * columns + col;
+    let value_positions: Vec<usize> = (0 .. columns).map(|col| 4 * col + 2).collect();
+    let width = 4 * columns + 1;
+
+    let mut outline_of: Vec<Option<usize>> = vec![None; rows * columns];
+    let mut labels: Vec<Option<&str>> = vec![None; rows * columns];
+    for (position, (cells, label)) in outlines.iter().enumerate() {
+        for index in cells.iter() {
+            outline_of[*index] = Some(position);
+        }
+        if let Some(first) = cells.iter().min() {
+            labels[*first] = Some(label);
+        }
+    }
+
+    let outline_right = |row: usize, col: usize| {
+        let index = index_of(row, col);
+        col < columns - 1 && outline_of[index] != outline_of[index + 1]
+    };
+    let outline_below = |row: usize, col: usize| {
+        let index = index_of(row, col);
+        row < rows - 1 && outline_of[index] != outline_of[index + columns]
+    };
+    // The left edge of the row, which is also where the line above it starts
+    let left_edge = |row: usize| row < rows && right(row, -1);
+
+    let line = |row: usize| {
+        let solid = |col: usize| above(row as isize, col);
+        let dotted = |col: usize| row > 0 && outline_below(row - 1, col);
+
+        let mut line = format!("\t{}", ring.padding());
+        line.push(Grid::junction(row > 0 && left_edge(row - 1), left_edge(row), false, solid(0)));
+        for col in 0 .. columns {
+            let (edge, middle) = match (solid(col), dotted(col)) {
+                (true, _) => ('─', '─'),
+                (false, true) => ('┄', '┄'),
+                (false, false) => (' ', ' '),
+            };
+            let middle = match row {
+                0 => middle,
+                _ => *marks.get(&(index_of(row - 1, col), index_of(row, col))).unwrap_or(&middle),
+            };
+            line.extend([edge, middle, edge]);
+
+            let solid_arms = [
+                row > 0 && right(row - 1, col as isize),
+                row < rows && right(row, col as isize),
+                solid(col),
+                col < columns - 1 && solid(col + 1),
+            ];
+            let dotted_arms = [
+                row > 0 && outline_right(row - 1, col),
+                row < rows && outline_right(row, col),
+                dotted(col),
+                col < columns - 1 && dotted(col + 1),
+            ];
+
+            line.push(match (solid_arms.contains(&true), dotted_arms.contains(&true)) {
+                (true, _) => Grid::junction(solid_arms[0], solid_arms[1], solid_arms[2], solid_arms[3]),
+                (false, true) => '·',
+                (false, false) => ' ',
+            });
+        }
+
+        format!("{}\n", line).yellow().to_string()
+    };
+
+    let mut output = String::with_capacity(8192);
+    output.push_str(&ring.edge(-1, &value_positions, width));
+    for row in 0 .. rows {
+        output.push_str(&line(row));
+
+        let edge = if left_edge(row) { "│".yellow() } else { " ".normal() };
+        let mut label_line = format!("\t{}{}", ring.padding(), edge);
+        let mut value_line = format!("\t{}{}", ring.left(row), edge);
+        for col in 0 .. columns {
+            let index = index_of(row, col);
+            let mut label = format!("{:<3}", labels[index].unwrap_or("")).cyan();
+            let padding = match cell_marks[index] {
+                Some(mark) => mark.to_string().cyan(),
+                None => " ".normal(),
+            };
+            let (mut spacer, mut value, mut padding) = (" ".normal(), cell(row, col), padding);
+
+            if shaded[index] {
+                label = label.on_bright_black();
+                (spacer, value, padding) = (spacer.on_bright_black(), value.on_bright_black(), padding.on_bright_black());
+            }
+
+            let border = match (right(row, col as isize), outline_right(row, col)) {
+                (true, _) => "│".yellow(),
+                (false, true) => "┆".yellow(),
+                (false, false) => " ".normal(),
+            };
+            let mark = match marks.get(&(index, index + 1)) {
+                Some(mark) if col < columns - 1 => mark.to_string().yellow(),
+                _ => border.clone(),
+            };
+
+            label_line.push_str(&format!("{}{}", label, border));
+            value_line.push_str(&format!("{}{}{}{}", spacer, value, padding, mark));
+        }
+
+        if !outlines.is_empty() {
+            output.push_str(&format!("{}\n", label_line));
+        }
+        output.push_str(&format!("{}{}\n", value_line, ring.right(row)));
+    }
+    output.push_str(&line(rows));
+    output.push_str(&ring.edge(rows as isize, &value_positions, width));
+
+    output
+}
+
+/*
+ * Clues written in the ring around the outside of a grid, by the row and column they're written
+ * at, where -1 and the grid's size are the positions just outside it.  Clues for the same place,
+ * such as a sandwich total and an X-sum at the start of the same row, are written together with a
+ * slash between them, in the order they were given.  Without any clues the ring takes up no room
+ * at all
+ */
+struct Ring {
+    clues: HashMap<(isize, isize), String>,
+    // The width of the column of clues to the left of the grid, including a space before the grid
+    margin: usize,
+}
+
+impl Ring {
+    fn new(clues: impl Iterator<Item = (isize, isize, String)>) -> Self {
+        let mut combined_clues: HashMap<(isize, isize), String> = HashMap::new();
+        for (row, column, clue) in clues {
+            combined_clues.entry((row, column))
+                .and_modify(|existing| *existing = format!("{}/{}", existing, clue))
+                .or_insert(clue);
+        }
+
+        let clues = combined_clues;
+        let margin = clues.iter()
+            .filter(|((_, column), _)| *column == -1)
+            .map(|(_, clue)| clue.chars().count() + 1)
+            .max()
+            .unwrap_or(0);
+
+        Self { clues, margin }
+    }
+
+    /// Whether there are clues above or below the grid, rather than only to the sides
+    fn has_edges(&self, rows: usize) -> bool {
+        self.clues.keys().any(|(row, _)| *row < 0 || *row >= rows as isize)
+    }
+
+    /// Blank space the width of the left column of clues, for lines without one
+    fn padding(&self) -> String {
+        " ".repeat(self.margin)
+    }
+
+    /// The clue to the left of the given row, padded to the width of the left column
+    fn left(&self, row: usize) -> ColoredString {
+        match self.clues.get(&(row as isize, -1)) {
+            Some(clue) => format!("{:>width$} ", clue, width = self.margin - 1).cyan(),
+            None => self.padding().normal(),
+        }
+    }
+
+    /// The clue to the right of the given row, if there is one
+    fn right(&self, row: usize) -> ColoredString {
+        self.clues.iter()
+            .find(|((clue_row, column), _)| *clue_row == row as isize && *column >= 0)
+            .map_or("".normal(), |(_, clue)| format!(" {}", clue).cyan())
+    }
+
+    /*
+     * The line of clues above the grid (row -1) or below it, with each column's clue ending where
+     * that column's values sit, and any corner clues either side.  The positions are counted from
+     * the left border of the grid, which is width characters wide.  Empty if there aren't any
+     */
+    fn edge(&self, row: isize, value_positions: &[usize], width: usize) -> String {
+        let clues: Vec<(isize, &String)> = self.clues.iter()
+            .filter(|((clue_row, _), _)| *clue_row == row)
+            .map(|((_, column), clue)| (*column, clue))
+            .collect();
+        if clues.is_empty() {
+            return String::new();
+        }
+
+        let longest = clues.iter().map(|(_, clue)| clue.chars().count()).max().unwrap_or(0);
+        let mut line = vec![' '; self.margin + width + 1 + longest];
+        for (column, clue) in clues {
+            let clue: Vec<char> = clue.chars().collect();
+            let start = match usize::try_from(column).ok().and_then(|column| value_positions.get(column)) {
+                Some(position) => (self.margin + position + 1).saturating_sub(clue.len()),
+                None if column < 0 => self.margin.saturating_sub(clue.len() + 1),
+                None => self.margin + width + 1,
+            };
+
+            for (offset, character) in clue.into_iter().enumerate() {
+                if let Some(place) = line.get_mut(start + offset) {
+                    *place = character;
+                }
+            }
+        }
+
+        let line: String = line.into_iter().collect();
+        format!("\t{}\n", line.trim_end().cyan())
     }
 }
 
@@ -268,7 +772,9 @@ pub struct CellValue {
 
 impl CellValue {
     pub(crate) const MIN_VALID_VAL: u8 = 1;
-    pub(crate) const MAX_VALID_VAL: u8 = 9;
+    /// The largest value any grid can hold.  Grids check values against their own size when
+    /// they're set
+    pub(crate) const MAX_VALID_VAL: u8 = GridShape::MAX_SIZE as u8;
 
     pub fn new(value: u8) -> Result<Self, AnswerRangeError> {
         let value = Self::validate_cell_value(value)?;
@@ -282,7 +788,75 @@ impl CellValue {
     fn validate_cell_value(value: u8) -> Result<u8, AnswerRangeError> {
         match value {
             Self::MIN_VALID_VAL..=Self::MAX_VALID_VAL => Ok(value),
-            _ => Err(AnswerRangeError::new(value))
+            _ => Err(AnswerRangeError::new(value, Self::MAX_VALID_VAL))
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sudoku::comparison::GreaterThan;
+    use crate::sudoku::parity::ParityCell;
+    use crate::sudoku::xv::XvMarker;
+
+    const PUZZLE: &str = "53..7....6..195....98....6.8...6...34..8.3..17...2...6.6....28....419..5....8..79";
+
+    #[test]
+    fn reads_and_writes_the_one_line_format() {
+        let grid: Grid = PUZZLE.parse().unwrap();
+        assert_eq!(grid.clue_count(), 30);
+        assert_eq!(grid.to_line(), PUZZLE);
+
+        let block: String = PUZZLE.replace('.', "0").as_bytes().chunks(9).map(|row| format!("{}\n", String::from_utf8_lossy(row))).collect();
+        assert_eq!(block.parse::<Grid>().unwrap().to_line(), PUZZLE);
+    }
+
+    #[test]
+    fn rejects_malformed_lines() {
+        assert!(PUZZLE[.. 80].parse::<Grid>().unwrap_err().is::<InvalidPuzzleLength>());
+        assert!(format!("{}1", PUZZLE).parse::<Grid>().unwrap_err().is::<InvalidPuzzleLength>());
+        assert!(PUZZLE.replacen('.', "x", 1).parse::<Grid>().unwrap_err().is::<InvalidPuzzleCharacter>());
+        assert!(PUZZLE.replacen("53", "55", 1).parse::<Grid>().unwrap_err().is::<UniquenessError>());
+    }
+
+    #[test]
+    fn works_out_the_shape_from_the_length() {
+        let grid: Grid = "1..4 .4.. ..3. 3..1".parse().unwrap();
+
+        assert_eq!(*grid.shape(), GridShape::new(2, 2).unwrap());
+        assert_eq!(grid.to_line(), "1..4.4....3.3..1");
+        assert!("1".repeat(49).parse::<Grid>().unwrap_err().is::<InvalidPuzzleLength>());
+    }
+
+    #[test]
+    fn draws_marks_in_and_between_cells() {
+        let cell = |row, column| GridReference::from_numbers(row, column).unwrap();
+        let mut grid = Grid::new();
+        grid.add_constraint(ParityCell::odd(cell(0, 0))).unwrap();
+        grid.add_constraint(ParityCell::even(cell(0, 2))).unwrap();
+        grid.add_constraint(XvMarker::x(cell(0, 0), cell(0, 1)).unwrap()).unwrap();
+        grid.add_constraint(XvMarker::v(cell(0, 0), cell(1, 0)).unwrap()).unwrap();
+        grid.add_constraint(GreaterThan::new(cell(1, 2), cell(1, 1)).unwrap()).unwrap();
+        grid.add_constraint(GreaterThan::new(cell(1, 2), cell(2, 2)).unwrap()).unwrap();
+        grid.set_cell(&cell(0, 0), &CellValue::new(3).unwrap()).unwrap();
+
+        // Strip the colours, which are written as escape sequences ending in m
+        let mut drawn = String::new();
+        let mut in_escape = false;
+        for character in grid.to_string().chars() {
+            match character {
+                '\x1b' => in_escape = true,
+                'm' if in_escape => in_escape = false,
+                _ if !in_escape => drawn.push(character),
+                _ => {},
+            }
+        }
+
+        let lines: Vec<&str> = drawn.lines().collect();
+        assert!(lines[1].starts_with("\t│ 3○X -   -□│"));
+        assert!(lines[2].starts_with("\t│ V         │"));
+        assert!(lines[3].starts_with("\t│ -   - < - │"));
+        assert!(lines[4].starts_with("\t│         v │"));
+    }
+}
@@ -1,7 +1,7 @@
 use crate::sudoku::grid::Grid;
-use crate::sudoku::reference::GridReference;
+use crate::sudoku::shape::GridShape;
 use colored::Colorize;
-use std::fmt::{Display, Error as fmtError, Formatter, Result as FmtResult};
+use std::fmt::{Display, Formatter, Result as FmtResult};
 
 pub struct GridDiff<'a> {
     base: &'a Grid,
@@ -16,38 +16,14 @@ impl<'a> GridDiff<'a> {
 
 impl<'a> Display for GridDiff<'a> {
     fn fmt(&self, f: &mut Formatter) -> FmtResult {
-        let mut output = String::with_capacity(2048);
-        output.push_str(format!("{}", String::from("\t┌───────┬───────┬───────┐\n").yellow()).as_str());
-
-        for row in 0..Grid::GRID_COLUMNS {
-            output.push_str(format!("\t{}", String::from("│").yellow()).as_str());
-            for col in 0..Grid::GRID_ROWS {
-                let grid_ref = GridReference::from_numbers(row, col).map_err(|_| fmtError)?;
-                let raw_val = self.current.cell(&grid_ref);
-                let cooked_val = match raw_val {
-                    Some(val) => {
-                        let prev_val = self.base.cell(&grid_ref);
-                        if prev_val != raw_val {
-                            format!(" {}", val.to_string().bright_green())
-                        } else {
-                            format!(" {}", val.to_string().white())
-                        }
-                    },
-                    None => format!("{}", String::from(" -").blue()),
-                };
-                output.push_str(cooked_val.as_str());
-
-                if Grid::SUBGRID_COLUMNS - 1 == col % Grid::SUBGRID_COLUMNS {
-                    output.push_str(format!("{}", String::from(" │").yellow()).as_str());
-                }
-            }
-
-            output.push('\n');
-            if Grid::SUBGRID_ROWS - 1 == row % Grid::SUBGRID_ROWS && row < Grid::GRID_ROWS - 1 {
-                output.push_str(format!("{}", String::from("\t├───────┼───────┼───────┤\n").yellow()).as_str());
+        let output = self.current.render(|grid_ref, val| {
+            let symbol = GridShape::symbol_for(val).to_string();
+            if *self.base.cell(grid_ref) != Some(val) {
+                symbol.bright_green()
+            } else {
+                symbol.white()
             }
-        }
-        output.push_str(format!("{}", String::from("\t└───────┴───────┴───────┘\n").yellow()).as_str());
+        });
 
         write!(f, "{}", output)
     }
@@ -0,0 +1,430 @@
+use crate::sudoku::constraint::{CandidateSet, Constraint};
+use crate::sudoku::error::{InvalidBorderMark, PairConstraint, PairError};
+use crate::sudoku::formula::Formula;
+use crate::sudoku::grid::Grid;
+use crate::sudoku::pair::{allows, check_pair, encode_pair, pair_formula, partners_of, prune_pair};
+use crate::sudoku::reference::GridReference;
+use crate::sudoku::sat::Cnf;
+use crate::sudoku::shape::GridShape;
+use crate::sudoku::transform::Transform;
+use std::error::Error;
+use std::sync::Arc;
+
+/// No two orthogonally neighbouring cells anywhere in the grid may hold consecutive values
+#[derive(Debug, Clone)]
+pub struct NonConsecutive;
+
+impl NonConsecutive {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Default for NonConsecutive {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Constraint for NonConsecutive {
+    fn affected_cells(&self, shape: &GridShape) -> Vec<GridReference> {
+        shape.grid_refs().collect()
+    }
+
+    fn check(&self, grid: &Grid, changed: &GridReference) -> Result<(), Box<dyn Error>> {
+        let Some(value) = *grid.cell(changed) else {
+            return Ok(());
+        };
+
+        for neighbour in neighbours(grid.shape(), changed) {
+            if let Some(other) = *grid.cell(&neighbour)
+                && !allows(&PairConstraint::NonConsecutive, value, other) {
+                return Err(PairError::new(changed, &neighbour, value, PairConstraint::NonConsecutive).into());
+            }
+        }
+
+        Ok(())
+    }
+
+    fn prune(&self, grid: &Grid, candidates: &mut [CandidateSet]) {
+        let shape = grid.shape();
+
+        for grid_ref in shape.grid_refs() {
+            let Some(value) = *grid.cell(&grid_ref) else {
+                continue;
+            };
+
+            for neighbour in neighbours(shape, &grid_ref).filter(|neighbour| grid.cell(neighbour).is_none()) {
+                candidates[neighbour.to_index(shape)] &= partners_of(&PairConstraint::NonConsecutive, shape, value);
+            }
+        }
+    }
+
+    fn encode(&self, shape: &GridShape, cnf: &mut Cnf) -> bool {
+        for grid_ref in shape.grid_refs() {
+            for neighbour in later_neighbours(shape, &grid_ref) {
+                encode_pair(&PairConstraint::NonConsecutive, &[grid_ref.clone(), neighbour], cnf);
+            }
+        }
+
+        true
+    }
+
+    fn formulas(&self, shape: &GridShape) -> Option<Vec<Formula>> {
+        let formulas = shape.grid_refs()
+            .flat_map(|grid_ref| {
+                later_neighbours(shape, &grid_ref)
+                    .map(|neighbour| pair_formula(&PairConstraint::NonConsecutive, &[grid_ref.clone(), neighbour]))
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+
+        Some(formulas)
+    }
+
+    fn transformed(&self, shape: &GridShape, transform: &Transform) -> Option<Arc<dyn Constraint>> {
+        let keeps_neighbours = transform.keeps_relation(|grid_ref| neighbours(shape, grid_ref).collect());
+
+        (transform.keeps_digits() && keeps_neighbours).then(|| Arc::new(self.clone()) as Arc<dyn Constraint>)
+    }
+}
+
+/*
+ * Consecutive pairs Sudoku: every pair of neighbouring cells whose values differ by 1 is marked,
+ * so the marked pairs must hold consecutive values and no other neighbours may.  The marks are
+ * drawn like white Kropki dots, which follow the same rule when every dot is given.  Since the
+ * unmarked pairs count too, the marks only fit the shape of grid they were made for
+ */
+#[derive(Debug, Clone)]
+pub struct ConsecutivePairs {
+    shape: GridShape,
+    pairs: Vec<[GridReference; 2]>,
+}
+
+impl ConsecutivePairs {
+    /// The rule covers the whole of a grid of the given shape, so every marked pair has to be a
+    /// pair of neighbours within it
+    pub fn new(shape: &GridShape, pairs: Vec<(GridReference, GridReference)>) -> Result<Self, InvalidBorderMark> {
+        let on_grid = |grid_ref: &GridReference| grid_ref.row() < shape.rows() && grid_ref.column() < shape.columns();
+        let pairs = pairs.into_iter()
+            .map(|(first, second)| match on_grid(&first) && on_grid(&second) && first.shares_side_with(&second) {
+                true => Ok([first, second]),
+                false => Err(InvalidBorderMark::new(&first, &second)),
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self { shape: *shape, pairs })
+    }
+
+    pub fn pairs(&self) -> &[[GridReference; 2]] {
+        &self.pairs
+    }
+
+    fn is_marked(&self, first: &GridReference, second: &GridReference) -> bool {
+        self.pairs.iter().any(|pair| (pair[0] == *first && pair[1] == *second) || (pair[0] == *second && pair[1] == *first))
+    }
+}
+
+impl Constraint for ConsecutivePairs {
+    fn affected_cells(&self, _shape: &GridShape) -> Vec<GridReference> {
+        self.shape.grid_refs().collect()
+    }
+
+    fn check(&self, grid: &Grid, changed: &GridReference) -> Result<(), Box<dyn Error>> {
+        let Some(value) = *grid.cell(changed) else {
+            return Ok(());
+        };
+
+        for neighbour in neighbours(grid.shape(), changed) {
+            if self.is_marked(changed, &neighbour) {
+                check_pair(&PairConstraint::ConsecutivePair, &[changed.clone(), neighbour], grid, changed)?;
+            } else if let Some(other) = *grid.cell(&neighbour)
+                && !allows(&PairConstraint::NonConsecutive, value, other) {
+                return Err(PairError::new(changed, &neighbour, value, PairConstraint::NonConsecutive).into());
+            }
+        }
+
+        Ok(())
+    }
+
+    fn prune(&self, grid: &Grid, candidates: &mut [CandidateSet]) {
+        let shape = grid.shape();
+
+        for grid_ref in shape.grid_refs() {
+            let Some(value) = *grid.cell(&grid_ref) else {
+                continue;
+            };
+
+            for neighbour in neighbours(shape, &grid_ref).filter(|neighbour| grid.cell(neighbour).is_none()) {
+                if !self.is_marked(&grid_ref, &neighbour) {
+                    candidates[neighbour.to_index(shape)] &= partners_of(&PairConstraint::NonConsecutive, shape, value);
+                }
+            }
+        }
+
+        for pair in self.pairs.iter() {
+            prune_pair(&PairConstraint::ConsecutivePair, pair, grid, candidates);
+        }
+    }
+
+    fn encode(&self, shape: &GridShape, cnf: &mut Cnf) -> bool {
+        for grid_ref in shape.grid_refs() {
+            for neighbour in later_neighbours(shape, &grid_ref) {
+                let kind = match self.is_marked(&grid_ref, &neighbour) {
+                    true => PairConstraint::ConsecutivePair,
+                    false => PairConstraint::NonConsecutive,
+                };
+                encode_pair(&kind, &[grid_ref.clone(), neighbour], cnf);
+            }
+        }
+
+        true
+    }
+
+    fn formulas(&self, shape: &GridShape) -> Option<Vec<Formula>> {
+        let mut formulas = Vec::new();
+        for grid_ref in shape.grid_refs() {
+            for neighbour in later_neighbours(shape, &grid_ref) {
+                let kind = match self.is_marked(&grid_ref, &neighbour) {
+                    true => PairConstraint::ConsecutivePair,
+                    false => PairConstraint::NonConsecutive,
+                };
+                formulas.push(pair_formula(&kind, &[grid_ref.clone(), neighbour]));
+            }
+        }
+
+        Some(formulas)
+    }
+
+    fn border_marks(&self, _shape: &GridShape) -> Vec<(GridReference, GridReference, char)> {
+        self.pairs.iter()
+            .map(|[first, second]| (first.clone(), second.clone(), '○'))
+            .collect()
+    }
+
+    fn shape(&self) -> Option<GridShape> {
+        Some(self.shape)
+    }
+
+    /// The unmarked pairs matter as much as the marked ones, so every pair of neighbours has to
+    /// stay together
+    fn transformed(&self, shape: &GridShape, transform: &Transform) -> Option<Arc<dyn Constraint>> {
+        let keeps_neighbours = transform.keeps_relation(|grid_ref| neighbours(shape, grid_ref).collect());
+        if !transform.keeps_digits() || !keeps_neighbours {
+            return None;
+        }
+
+        let pairs = self.pairs.iter()
+            .map(|[first, second]| (transform.move_cell(first), transform.move_cell(second)))
+            .collect();
+        let consecutive_pairs = Self::new(shape, pairs).ok()?;
+        Some(Arc::new(consecutive_pairs))
+    }
+}
+
+/*
+ * A Kropki dot on the border between two neighbouring cells.  A white dot means the two values are
+ * consecutive, and a black dot means one of them is double the other.  Where a pair could be either
+ * (1 and 2) the puzzle can use either colour
+ */
+#[derive(Debug, Clone)]
+pub struct KropkiDot {
+    kind: PairConstraint,
+    cells: [GridReference; 2],
+}
+
+impl KropkiDot {
+    pub fn white(first: GridReference, second: GridReference) -> Result<Self, InvalidBorderMark> {
+        Self::new(PairConstraint::WhiteDot, first, second)
+    }
+
+    pub fn black(first: GridReference, second: GridReference) -> Result<Self, InvalidBorderMark> {
+        Self::new(PairConstraint::BlackDot, first, second)
+    }
+
+    pub fn kind(&self) -> &PairConstraint {
+        &self.kind
+    }
+
+    pub fn cells(&self) -> &[GridReference; 2] {
+        &self.cells
+    }
+
+    fn new(kind: PairConstraint, first: GridReference, second: GridReference) -> Result<Self, InvalidBorderMark> {
+        if !first.shares_side_with(&second) {
+            return Err(InvalidBorderMark::new(&first, &second));
+        }
+
+        Ok(Self { kind, cells: [first, second] })
+    }
+}
+
+impl Constraint for KropkiDot {
+    fn affected_cells(&self, _shape: &GridShape) -> Vec<GridReference> {
+        self.cells.to_vec()
+    }
+
+    fn check(&self, grid: &Grid, changed: &GridReference) -> Result<(), Box<dyn Error>> {
+        check_pair(&self.kind, &self.cells, grid, changed)
+    }
+
+    fn prune(&self, grid: &Grid, candidates: &mut [CandidateSet]) {
+        prune_pair(&self.kind, &self.cells, grid, candidates);
+    }
+
+    fn encode(&self, _shape: &GridShape, cnf: &mut Cnf) -> bool {
+        encode_pair(&self.kind, &self.cells, cnf);
+
+        true
+    }
+
+    fn formulas(&self, _shape: &GridShape) -> Option<Vec<Formula>> {
+        Some(vec![pair_formula(&self.kind, &self.cells)])
+    }
+
+    fn border_marks(&self, _shape: &GridShape) -> Vec<(GridReference, GridReference, char)> {
+        let mark = match self.kind {
+            PairConstraint::BlackDot => '●',
+            _ => '○',
+        };
+
+        vec![(self.cells[0].clone(), self.cells[1].clone(), mark)]
+    }
+
+    /// Only kept if the cells are still neighbours and the digits stay as they are
+    fn transformed(&self, _shape: &GridShape, transform: &Transform) -> Option<Arc<dyn Constraint>> {
+        if !transform.keeps_digits() {
+            return None;
+        }
+
+        let [first, second] = &self.cells;
+        let dot = Self::new(self.kind.clone(), transform.move_cell(first), transform.move_cell(second)).ok()?;
+        Some(Arc::new(dot))
+    }
+}
+
+/// The cells directly below and to the right of the given one, so that each pair of neighbours
+/// only comes up once
+fn later_neighbours(shape: &GridShape, grid_ref: &GridReference) -> impl Iterator<Item = GridReference> {
+    let index = grid_ref.to_index(shape);
+    let owned = *shape;
+
+    neighbours(shape, grid_ref).filter(move |neighbour| neighbour.to_index(&owned) > index)
+}
+
+/// The cells directly above, below, left and right of the given one
+fn neighbours(shape: &GridShape, grid_ref: &GridReference) -> impl Iterator<Item = GridReference> {
+    let shape = *shape;
+    let (row, column) = (grid_ref.row(), grid_ref.column());
+
+    [(-1, 0), (1, 0), (0, -1), (0, 1)].into_iter().filter_map(move |(rows, columns)| {
+        let row = row.checked_add_signed(rows).filter(|row| *row < shape.rows())?;
+        let column = column.checked_add_signed(columns).filter(|column| *column < shape.columns())?;
+
+        Some(GridReference::for_shape(&shape, row, column).expect("Row and column are always in range"))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sudoku::error::ShapeMismatch;
+    use crate::sudoku::grid::CellValue;
+
+    fn cell(row: usize, column: usize) -> GridReference {
+        GridReference::from_numbers(row, column).unwrap()
+    }
+
+    fn plain(text: &str) -> String {
+        let mut plain = String::new();
+        let mut in_escape = false;
+        for character in text.chars() {
+            match character {
+                '\x1b' => in_escape = true,
+                'm' if in_escape => in_escape = false,
+                _ if !in_escape => plain.push(character),
+                _ => {},
+            }
+        }
+
+        plain
+    }
+
+    #[test]
+    fn draws_dots_between_their_cells() {
+        let mut grid = Grid::new();
+        grid.add_constraint(KropkiDot::white(cell(0, 0), cell(0, 1)).unwrap()).unwrap();
+        grid.add_constraint(KropkiDot::black(cell(0, 0), cell(1, 0)).unwrap()).unwrap();
+        grid.set_cell(&cell(0, 0), &CellValue::new(4).unwrap()).unwrap();
+        grid.set_cell(&cell(0, 1), &CellValue::new(5).unwrap()).unwrap();
+        grid.set_cell(&cell(1, 0), &CellValue::new(2).unwrap()).unwrap();
+
+        let drawn = plain(&grid.to_string());
+        let lines: Vec<&str> = drawn.lines().collect();
+        assert!(lines[1].starts_with("\t│ 4 ○ 5   - │"));
+        assert!(lines[2].starts_with("\t│ ●         │"));
+        assert!(lines[3].starts_with("\t│ 2   -   - │"));
+    }
+
+    #[test]
+    fn dots_hold_their_pair_of_values() {
+        let mut grid = Grid::new();
+        grid.add_constraint(KropkiDot::white(cell(0, 0), cell(0, 1)).unwrap()).unwrap();
+        grid.add_constraint(KropkiDot::black(cell(0, 0), cell(1, 0)).unwrap()).unwrap();
+        grid.set_cell(&cell(0, 0), &CellValue::new(4).unwrap()).unwrap();
+
+        assert!(grid.clone().set_cell(&cell(0, 1), &CellValue::new(6).unwrap()).unwrap_err().is::<PairError>());
+        assert!(grid.clone().set_cell(&cell(1, 0), &CellValue::new(3).unwrap()).unwrap_err().is::<PairError>());
+
+        let mut candidates = vec![0b1_1111_1111; 81];
+        grid.constraints().iter().for_each(|constraint| constraint.prune(&grid, &mut candidates));
+        assert_eq!(candidates[1], 0b1_0100);
+        assert_eq!(candidates[9], 0b1000_0010);
+    }
+
+    #[test]
+    fn dots_only_go_between_neighbours() {
+        assert!(KropkiDot::white(cell(0, 0), cell(1, 1)).is_err());
+        assert!(KropkiDot::black(cell(0, 0), cell(0, 2)).is_err());
+        assert!(KropkiDot::black(cell(4, 4), cell(3, 4)).is_ok());
+    }
+
+    #[test]
+    fn neighbours_cant_be_consecutive() {
+        let mut grid = Grid::new();
+        grid.add_constraint(NonConsecutive::new()).unwrap();
+        grid.set_cell(&cell(4, 4), &CellValue::new(5).unwrap()).unwrap();
+
+        assert!(grid.clone().set_cell(&cell(4, 5), &CellValue::new(6).unwrap()).unwrap_err().is::<PairError>());
+        assert!(grid.clone().set_cell(&cell(3, 4), &CellValue::new(4).unwrap()).unwrap_err().is::<PairError>());
+        assert!(grid.set_cell(&cell(3, 3), &CellValue::new(6).unwrap()).is_ok());
+    }
+    #[test]
+    fn consecutive_pairs_are_marked_between_neighbours_on_the_grid() {
+        let shape = GridShape::standard();
+
+        assert!(ConsecutivePairs::new(&shape, vec![(cell(0, 0), cell(1, 1))]).unwrap_err().to_string().contains("share a side"));
+        assert!(ConsecutivePairs::new(&shape, vec![(cell(0, 8), GridReference::for_shape(&GridShape::largest(), 0, 9).unwrap())]).is_err());
+        assert!(ConsecutivePairs::new(&GridShape::new(2, 2).unwrap(), vec![(cell(3, 3), cell(3, 4))]).is_err());
+        assert_eq!(ConsecutivePairs::new(&shape, vec![(cell(0, 8), cell(1, 8))]).unwrap().pairs().len(), 1);
+
+        let small = ConsecutivePairs::new(&GridShape::new(2, 2).unwrap(), Vec::new()).unwrap();
+        assert!(Grid::new().add_constraint(small).unwrap_err().is::<ShapeMismatch>());
+    }
+
+    #[test]
+    fn only_marked_pairs_can_be_consecutive() {
+        let mut grid = Grid::new();
+        grid.add_constraint(ConsecutivePairs::new(grid.shape(), vec![(cell(4, 4), cell(4, 5))]).unwrap()).unwrap();
+        grid.set_cell(&cell(4, 4), &CellValue::new(5).unwrap()).unwrap();
+
+        assert!(grid.clone().set_cell(&cell(4, 5), &CellValue::new(7).unwrap()).unwrap_err().is::<PairError>());
+        assert!(grid.clone().set_cell(&cell(3, 4), &CellValue::new(6).unwrap()).unwrap_err().is::<PairError>());
+        assert!(grid.clone().set_cell(&cell(4, 5), &CellValue::new(6).unwrap()).is_ok());
+
+        let mut candidates = vec![0b1_1111_1111; 81];
+        grid.constraints().iter().for_each(|constraint| constraint.prune(&grid, &mut candidates));
+        assert_eq!(candidates[GridShape::standard().index_of(4, 5)], 0b10_1000);
+        assert_eq!(candidates[GridShape::standard().index_of(3, 4)], 0b1_1100_0111);
+    }
+}
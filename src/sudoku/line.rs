@@ -0,0 +1,329 @@
+use crate::sudoku::constraint::{cell_options, highest_of, lowest_of, narrow_sum, prune_cells, values_between, CandidateSet, Constraint};
+use crate::sudoku::error::{BrokenLine, ClueConstraint, ClueError, InvalidClue, InvalidLineLength, RepeatedLineCell};
+use crate::sudoku::formula::{Formula, Term};
+use crate::sudoku::grid::Grid;
+use crate::sudoku::reference::GridReference;
+use crate::sudoku::sat::Cnf;
+use crate::sudoku::shape::GridShape;
+use crate::sudoku::transform::Transform;
+use std::error::Error;
+use std::str::FromStr;
+use std::sync::Arc;
+
+/*
+ * A thermometer: a line of cells running from the bulb to the tip, whose values must strictly
+ * increase along the way.  Values can only be so low near the tip and so high near the bulb, and
+ * each placed value pushes the bounds of the cells either side of it
+ */
+#[derive(Debug, Clone)]
+pub struct Thermometer {
+    cells: Vec<GridReference>,
+}
+
+impl Thermometer {
+    /// The cells are given in order from the bulb to the tip
+    pub fn new(cells: Vec<GridReference>) -> Result<Self, Box<dyn Error>> {
+        validate_line(&cells, 2)?;
+
+        Ok(Self { cells })
+    }
+
+    pub fn cells(&self) -> &[GridReference] {
+        &self.cells
+    }
+
+    /// Narrows the options of each cell down to the values that leave room for the cells either
+    /// side of it, or returns None if some cell is left without any
+    fn narrow(&self, shape: &GridShape, options: &[CandidateSet]) -> Option<Vec<CandidateSet>> {
+        if options.contains(&0) {
+            return None;
+        }
+
+        let mut lowest = vec![0; options.len()];
+        for (position, option) in options.iter().enumerate() {
+            let previous = if position > 0 { lowest[position - 1] } else { 0 };
+            lowest[position] = lowest_of(*option).max(previous + 1);
+        }
+
+        let mut highest = vec![0; options.len()];
+        for (position, option) in options.iter().enumerate().rev() {
+            let next = highest.get(position + 1).copied().unwrap_or(shape.max_value() as i32 + 1);
+            highest[position] = highest_of(*option).min(next - 1);
+        }
+
+        let narrowed: Vec<CandidateSet> = options.iter()
+            .enumerate()
+            .map(|(position, option)| option & values_between(shape, lowest[position], highest[position]))
+            .collect();
+
+        (!narrowed.contains(&0)).then_some(narrowed)
+    }
+}
+
+impl Constraint for Thermometer {
+    fn affected_cells(&self, _shape: &GridShape) -> Vec<GridReference> {
+        self.cells.clone()
+    }
+
+    fn check(&self, grid: &Grid, changed: &GridReference) -> Result<(), Box<dyn Error>> {
+        let Some(value) = *grid.cell(changed) else {
+            return Ok(());
+        };
+
+        match self.narrow(grid.shape(), &cell_options(grid, &self.cells, None)) {
+            Some(_) => Ok(()),
+            None => Err(ClueError::new(changed.row(), changed.column(), value, ClueConstraint::Thermometer).into()),
+        }
+    }
+
+    fn prune(&self, grid: &Grid, candidates: &mut [CandidateSet]) {
+        let narrowed = self.narrow(grid.shape(), &cell_options(grid, &self.cells, Some(candidates)));
+        prune_cells(grid, &self.cells, narrowed, candidates);
+    }
+
+    fn encode(&self, _shape: &GridShape, cnf: &mut Cnf) -> bool {
+        for pair in self.cells.windows(2) {
+            cnf.forbid_pairs(&pair[0], &pair[1], |lower, higher| lower < higher);
+        }
+
+        true
+    }
+
+    fn formulas(&self, _shape: &GridShape) -> Option<Vec<Formula>> {
+        let formulas = self.cells.windows(2)
+            .map(|pair| Formula::Less(Term::Cell(pair[0].clone()), Term::Cell(pair[1].clone())))
+            .collect();
+
+        Some(formulas)
+    }
+
+    /// Moving the cells can pull the line apart, and relabelling the digits breaks their order
+    fn transformed(&self, _shape: &GridShape, transform: &Transform) -> Option<Arc<dyn Constraint>> {
+        if !transform.keeps_digits() {
+            return None;
+        }
+
+        let thermometer = Self::new(transform.move_cells(&self.cells)).ok()?;
+        Some(Arc::new(thermometer))
+    }
+}
+
+/// Thermometers are written as their cells from the bulb to the tip, such as "r1c1 r1c2 r2c3".
+/// Commas or dashes can be used between the cells as well as spaces
+impl FromStr for Thermometer {
+    type Err = Box<dyn Error>;
+
+    fn from_str(thermometer: &str) -> Result<Self, Self::Err> {
+        Self::new(parse_cells(thermometer)?)
+    }
+}
+
+/*
+ * An arrow: the values along the arrow's shaft must add up to the value in the circle at its end.
+ * Unlike a cage, values can repeat along an arrow wherever the rest of the rules allow it
+ */
+#[derive(Debug, Clone)]
+pub struct Arrow {
+    circle: GridReference,
+    shaft: Vec<GridReference>,
+}
+
+impl Arrow {
+    /// The shaft's cells are given in order, starting from the one touching the circle
+    pub fn new(circle: GridReference, shaft: Vec<GridReference>) -> Result<Self, Box<dyn Error>> {
+        let mut line = vec![circle.clone()];
+        line.extend(shaft.iter().cloned());
+        validate_line(&line, 2)?;
+
+        Ok(Self { circle, shaft })
+    }
+
+    pub fn circle(&self) -> &GridReference {
+        &self.circle
+    }
+
+    pub fn shaft(&self) -> &[GridReference] {
+        &self.shaft
+    }
+
+    /*
+     * Narrows the options of the circle (first) and the shaft (the rest) down to the values that
+     * still allow the shaft to add up to the circle, or returns None if some cell is left without
+     * any.  The circle has to lie between the lowest and highest totals the shaft could make, and
+     * each cell of the shaft has to make up what the rest of the shaft can't
+     */
+    fn narrow(&self, shape: &GridShape, options: &[CandidateSet]) -> Option<Vec<CandidateSet>> {
+        if options.contains(&0) {
+            return None;
+        }
+
+        let shaft = &options[1 ..];
+        let lowest_total: i32 = shaft.iter().map(|option| lowest_of(*option)).sum();
+        let highest_total: i32 = shaft.iter().map(|option| highest_of(*option)).sum();
+
+        let circle = options[0] & values_between(shape, lowest_total, highest_total);
+        if circle == 0 {
+            return None;
+        }
+
+        let mut narrowed = vec![circle];
+        narrowed.extend(narrow_sum(shape, shaft, lowest_of(circle), highest_of(circle))?);
+
+        Some(narrowed)
+    }
+
+    fn cells(&self) -> Vec<GridReference> {
+        let mut cells = vec![self.circle.clone()];
+        cells.extend(self.shaft.iter().cloned());
+
+        cells
+    }
+}
+
+impl Constraint for Arrow {
+    fn affected_cells(&self, _shape: &GridShape) -> Vec<GridReference> {
+        self.cells()
+    }
+
+    fn check(&self, grid: &Grid, changed: &GridReference) -> Result<(), Box<dyn Error>> {
+        let Some(value) = *grid.cell(changed) else {
+            return Ok(());
+        };
+
+        match self.narrow(grid.shape(), &cell_options(grid, &self.cells(), None)) {
+            Some(_) => Ok(()),
+            None => Err(ClueError::new(changed.row(), changed.column(), value, ClueConstraint::Arrow).into()),
+        }
+    }
+
+    fn prune(&self, grid: &Grid, candidates: &mut [CandidateSet]) {
+        let cells = self.cells();
+        let narrowed = self.narrow(grid.shape(), &cell_options(grid, &cells, Some(candidates)));
+        prune_cells(grid, &cells, narrowed, candidates);
+    }
+
+    /// Whatever the shaft adds up to, the circle has to hold that value
+    fn encode(&self, shape: &GridShape, cnf: &mut Cnf) -> bool {
+        let totals = cnf.running_totals(&self.shaft);
+        let reached = totals.last().expect("There's always a list for no cells");
+
+        for (total, variable) in reached.iter().enumerate() {
+            let Some(variable) = variable else {
+                continue;
+            };
+
+            match u8::try_from(total).ok().filter(|total| (1 ..= shape.max_value()).contains(total)) {
+                Some(total) => cnf.add_clause(vec![-variable, cnf.variable(&self.circle, total)]),
+                None => cnf.add_clause(vec![-variable]),
+            }
+        }
+
+        true
+    }
+
+    fn formulas(&self, _shape: &GridShape) -> Option<Vec<Formula>> {
+        Some(vec![Formula::Equal(Term::sum_of(&self.shaft), Term::Cell(self.circle.clone()))])
+    }
+
+    fn transformed(&self, _shape: &GridShape, transform: &Transform) -> Option<Arc<dyn Constraint>> {
+        if !transform.keeps_digits() {
+            return None;
+        }
+
+        let arrow = Self::new(transform.move_cell(&self.circle), transform.move_cells(&self.shaft)).ok()?;
+        Some(Arc::new(arrow))
+    }
+}
+
+/// Arrows are written as the circle's cell, a colon, then the cells of the shaft starting from the
+/// circle, such as "r1c1: r1c2 r1c3"
+impl FromStr for Arrow {
+    type Err = Box<dyn Error>;
+
+    fn from_str(arrow: &str) -> Result<Self, Self::Err> {
+        let (circle, shaft) = arrow.split_once(':')
+            .ok_or_else(|| InvalidClue::new(arrow, "the circle's cell, a colon, then the cells of the arrow, such as r1c1: r1c2 r1c3"))?;
+
+        Self::new(circle.parse()?, parse_cells(shaft)?)
+    }
+}
+
+/// Each cell of a line has to touch the one before it, sideways or diagonally, and a line can't
+/// pass through the same cell twice
+fn validate_line(cells: &[GridReference], minimum: usize) -> Result<(), Box<dyn Error>> {
+    if cells.len() < minimum {
+        return Err(InvalidLineLength::new(cells.len(), minimum).into());
+    }
+
+    for (position, cell) in cells.iter().enumerate() {
+        if cells[.. position].contains(cell) {
+            return Err(RepeatedLineCell::new(cell.row(), cell.column()).into());
+        }
+    }
+
+    for pair in cells.windows(2) {
+        if pair[0].row().abs_diff(pair[1].row()) > 1 || pair[0].column().abs_diff(pair[1].column()) > 1 {
+            return Err(BrokenLine::new(&pair[0], &pair[1]).into());
+        }
+    }
+
+    Ok(())
+}
+
+fn parse_cells(cells: &str) -> Result<Vec<GridReference>, Box<dyn Error>> {
+    cells.split(|character: char| character.is_whitespace() || character == ',' || character == '-')
+        .filter(|cell| !cell.is_empty())
+        .map(|cell| cell.parse())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sudoku::grid::CellValue;
+
+    fn cell(row: usize, column: usize) -> GridReference {
+        GridReference::from_numbers(row, column).unwrap()
+    }
+
+    fn set(grid: &mut Grid, row: usize, column: usize, value: u8) -> Result<(), Box<dyn Error>> {
+        grid.set_cell(&cell(row, column), &CellValue::new(value).unwrap()).map(|_| ())
+    }
+
+    #[test]
+    fn thermometers_rise_from_the_bulb() {
+        let mut grid = Grid::new();
+        grid.add_constraint("r1c1 r1c2 r2c3".parse::<Thermometer>().unwrap()).unwrap();
+
+        assert!(set(&mut grid.clone(), 0, 1, 1).unwrap_err().is::<ClueError>());
+        assert!(set(&mut grid.clone(), 1, 2, 2).unwrap_err().is::<ClueError>());
+        set(&mut grid, 0, 0, 4).unwrap();
+        assert!(set(&mut grid.clone(), 0, 1, 3).unwrap_err().is::<ClueError>());
+        set(&mut grid, 0, 1, 5).unwrap();
+
+        let mut candidates = vec![0b1_1111_1111; 81];
+        grid.constraints().iter().for_each(|constraint| constraint.prune(&grid, &mut candidates));
+        assert_eq!(candidates[GridShape::standard().index_of(1, 2)], 0b1_1110_0000);
+    }
+
+    #[test]
+    fn arrows_add_up_to_their_circle() {
+        let mut grid = Grid::new();
+        grid.add_constraint("r1c1: r1c2 r1c3".parse::<Arrow>().unwrap()).unwrap();
+
+        assert!(set(&mut grid.clone(), 0, 0, 1).unwrap_err().is::<ClueError>());
+        set(&mut grid, 0, 0, 9).unwrap();
+        set(&mut grid, 0, 1, 5).unwrap();
+        assert!(set(&mut grid.clone(), 0, 2, 3).unwrap_err().is::<ClueError>());
+        assert!(set(&mut grid, 0, 2, 4).is_ok());
+    }
+
+    #[test]
+    fn lines_have_to_join_up() {
+        assert!("r1c1 r1c3".parse::<Thermometer>().unwrap_err().is::<BrokenLine>());
+        assert!("r1c1 r1c2 r1c1".parse::<Thermometer>().unwrap_err().is::<RepeatedLineCell>());
+        assert!("r1c1".parse::<Thermometer>().unwrap_err().is::<InvalidLineLength>());
+        assert!("r1c1 r1c2".parse::<Arrow>().unwrap_err().is::<InvalidClue>());
+        assert_eq!("r2c2: r3c3, r4c4".parse::<Arrow>().unwrap().shaft(), &[cell(2, 2), cell(3, 3)]);
+    }
+}
@@ -0,0 +1,158 @@
+use crate::sudoku::error::NotUniquelySolvable;
+use crate::sudoku::grid::{CellValue, Grid};
+use crate::sudoku::random::SeededRng;
+use crate::sudoku::reference::GridReference;
+use crate::sudoku::solver::{DepthFirstSolver, SolverBuilder};
+use std::error::Error;
+
+/// The order in which minimise() tries to remove clues
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum RemovalOrder {
+    /// Top left to bottom right, a row at a time
+    RowByRow,
+    /// A shuffled order, which is always the same for the same seed
+    Seeded(u64),
+}
+
+/*
+ * A puzzle is minimal if it has a unique solution and removing any single clue would leave it
+ * with more than one.  Only puzzles with a unique solution can be checked, as the question of which
+ * clues are needed doesn't mean much otherwise
+ */
+#[derive(Debug)]
+pub struct Minimiser<'problem> {
+    problem: &'problem Grid,
+    solver: SolverBuilder,
+}
+
+impl<'problem> Minimiser<'problem> {
+    pub fn new(problem: &'problem Grid) -> Result<Self, Box<dyn Error>> {
+        Self::with_solver(problem, DepthFirstSolver::builder())
+    }
+
+    /// Checks the puzzle, and the puzzles made by taking clues out of it, with the given solver
+    pub fn with_solver(problem: &'problem Grid, solver: SolverBuilder) -> Result<Self, Box<dyn Error>> {
+        let solution_count = solver(problem)?.count_solutions(2);
+        if solution_count != 1 {
+            return Err(NotUniquelySolvable::new(solution_count).into());
+        }
+
+        Ok(Self { problem, solver })
+    }
+
+    /*
+     * The clues that could each be removed on their own without losing the unique solution.  Note
+     * that this doesn't mean they can all be removed together: taking one of them out can make
+     * another one necessary
+     */
+    pub fn redundant_clues(&self) -> Vec<GridReference> {
+        self.clue_cells()
+            .into_iter()
+            .filter(|grid_ref| self.is_redundant(grid_ref))
+            .collect()
+    }
+
+    pub fn is_minimal(&self) -> bool {
+        !self.clue_cells().iter().any(|grid_ref| self.is_redundant(grid_ref))
+    }
+
+    /*
+     * Remove clues one at a time in the given order, putting each back if the puzzle stops being
+     * unique.  Removing clues can only ever add solutions, so a clue that had to stay in at some
+     * point can't become removable later on and a single pass always leaves a minimal puzzle
+     */
+    pub fn minimise(&self, order: RemovalOrder) -> Grid {
+        let mut clues = self.clue_cells();
+        if let RemovalOrder::Seeded(seed) = order {
+            SeededRng::new(seed).shuffle(&mut clues);
+        }
+
+        let mut puzzle = self.problem.clone();
+        for grid_ref in clues {
+            let value = puzzle.cell(&grid_ref).expect("Only cells holding clues are visited");
+
+            puzzle.clear_cell(&grid_ref);
+            if !self.has_unique_solution(&puzzle) {
+                puzzle.set_cell(&grid_ref, &CellValue::new(value).expect("Clue values are always in range"))
+                    .expect("Restoring a clue from a valid puzzle can't break uniqueness constraints");
+            }
+        }
+
+        puzzle
+    }
+
+    fn is_redundant(&self, grid_ref: &GridReference) -> bool {
+        let mut puzzle = self.problem.clone();
+        puzzle.clear_cell(grid_ref);
+
+        self.has_unique_solution(&puzzle)
+    }
+
+    fn has_unique_solution(&self, puzzle: &Grid) -> bool {
+        (self.solver)(puzzle)
+            .expect("The solver took the original puzzle, so it takes it with clues removed")
+            .has_unique_solution()
+    }
+
+    fn clue_cells(&self) -> Vec<GridReference> {
+        self.problem.shape()
+            .grid_refs()
+            .filter(|grid_ref| self.problem.cell(grid_ref).is_some())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sudoku::solver::Solver;
+    use crate::sudoku::generator::Generator;
+
+    fn values(grid: &Grid) -> Vec<Option<u8>> {
+        (0 .. 81)
+            .map(|index| *grid.cell(&GridReference::from_numbers(index / 9, index % 9).unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn generated_puzzles_are_minimal() {
+        let generated = Generator::new(1).generate().unwrap();
+        let minimiser = Minimiser::new(generated.puzzle()).unwrap();
+
+        assert!(minimiser.is_minimal());
+        assert!(minimiser.redundant_clues().is_empty());
+    }
+
+    #[test]
+    fn reports_an_extra_clue_as_redundant() {
+        let generated = Generator::new(1).generate().unwrap();
+        let grid_ref = (0 .. 81)
+            .map(|index| GridReference::from_numbers(index / 9, index % 9).unwrap())
+            .find(|grid_ref| generated.puzzle().cell(grid_ref).is_none())
+            .unwrap();
+        let mut puzzle = generated.puzzle().clone();
+        puzzle.set_cell(&grid_ref, &CellValue::new(generated.solution().cell(&grid_ref).unwrap()).unwrap()).unwrap();
+        let minimiser = Minimiser::new(&puzzle).unwrap();
+
+        assert!(!minimiser.is_minimal());
+        assert!(minimiser.redundant_clues().contains(&grid_ref));
+    }
+
+    #[test]
+    fn minimising_leaves_a_minimal_puzzle_with_the_same_solution() {
+        let solution = Generator::new(2).random_solution().unwrap();
+        let minimiser = Minimiser::new(&solution).unwrap();
+
+        let minimised = minimiser.minimise(RemovalOrder::Seeded(9));
+        assert!(Minimiser::new(&minimised).unwrap().is_minimal());
+        assert_eq!(values(DepthFirstSolver::for_problem(&minimised).solve().get_solution().as_ref().unwrap()), values(&solution));
+        assert_eq!(values(&minimiser.minimise(RemovalOrder::Seeded(9))), values(&minimised));
+    }
+
+    #[test]
+    fn only_takes_uniquely_solvable_puzzles() {
+        let error = Minimiser::new(&Grid::new()).unwrap_err();
+
+        assert!(error.is::<NotUniquelySolvable>());
+    }
+}
@@ -0,0 +1,558 @@
+use crate::sudoku::constraint::CandidateSet;
+use crate::sudoku::error::{NonStandardShape, OverlapConflict, UncoveredCell};
+use crate::sudoku::grid::{render_decorated, CellValue, Decorations, Frame, Grid};
+use crate::sudoku::reference::GridReference;
+use crate::sudoku::shape::GridShape;
+use colored::{ColoredString, Colorize};
+use std::error::Error;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+/// A grid placed on the canvas, with its top left cell at the given row and column of the canvas
+#[derive(Debug, Clone)]
+struct Placement {
+    row: usize,
+    column: usize,
+    grid: Grid,
+}
+
+/*
+ * Several grids laid out on a shared canvas, where grids can overlap.  A canvas cell covered by
+ * more than one grid is a single cell shared between them, so it always holds the same value in
+ * each of them, and setting it has to keep every one of those grids' rules.  Samurai Sudoku is the
+ * best known example, with four grids each sharing a corner subgrid with a fifth in the middle
+ */
+#[derive(Debug, Clone)]
+pub struct MultiGrid {
+    rows: usize,
+    columns: usize,
+    placements: Vec<Placement>,
+    // For each canvas cell, in row order, the grids covering it and where it lies within each
+    covering: Vec<Vec<(usize, GridReference)>>,
+}
+
+impl MultiGrid {
+    /// An empty canvas, which grows to fit the grids added to it
+    pub fn new() -> Self {
+        Self { rows: 0, columns: 0, placements: Vec::new(), covering: Vec::new() }
+    }
+
+    /// Samurai Sudoku from its five grids, given from the top left, top right, middle, bottom left
+    /// to bottom right.  The corner grids each share a subgrid with the middle one, so where they
+    /// overlap any values they give have to agree.  Every grid has to be the standard 9 x 9 grid
+    pub fn samurai(grids: [Grid; 5]) -> Result<Self, Box<dyn Error>> {
+        let origins = [(0, 0), (0, 12), (6, 6), (12, 0), (12, 12)];
+        let mut samurai = Self::new();
+
+        for ((row, column), grid) in origins.into_iter().zip(grids) {
+            if *grid.shape() != GridShape::standard() {
+                return Err(NonStandardShape::new(grid.shape()).into());
+            }
+            samurai.add_grid(row, column, grid)?;
+        }
+
+        Ok(samurai)
+    }
+
+    /*
+     * Places a grid with its top left cell at the given row and column of the canvas.  Where it
+     * overlaps the grids already there, a value given by either side is copied to the other,
+     * which fails if the values disagree or the copied value breaks the rules of the grid it's
+     * copied to.  The canvas is left as it was if the grid can't be added
+     */
+    pub fn add_grid(&mut self, row: usize, column: usize, grid: Grid) -> Result<&mut Self, Box<dyn Error>> {
+        let mut updated = self.clone();
+        let shape = *grid.shape();
+
+        updated.rows = updated.rows.max(row + shape.rows());
+        updated.columns = updated.columns.max(column + shape.columns());
+        updated.placements.push(Placement { row, column, grid });
+        updated.index_cells();
+
+        for grid_ref in shape.grid_refs() {
+            let (canvas_row, canvas_column) = (row + grid_ref.row(), column + grid_ref.column());
+            let values: Vec<Option<u8>> = updated.covering[updated.index_of(canvas_row, canvas_column)].iter()
+                .map(|(placement, grid_ref)| *updated.placements[*placement].grid.cell(grid_ref))
+                .collect();
+
+            let mut given = values.iter().flatten();
+            let Some(value) = given.next().copied() else {
+                continue;
+            };
+            if given.any(|other| *other != value) {
+                return Err(OverlapConflict::new(canvas_row, canvas_column).into());
+            }
+            if values.contains(&None) {
+                updated.set_cell(canvas_row, canvas_column, &CellValue::new(value)?)?;
+            }
+        }
+
+        *self = updated;
+
+        Ok(self)
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn columns(&self) -> usize {
+        self.columns
+    }
+
+    pub fn grid_count(&self) -> usize {
+        self.placements.len()
+    }
+
+    /// The grids in the order they were added
+    pub fn grid(&self, index: usize) -> &Grid {
+        &self.placements[index].grid
+    }
+
+    /// Where the top left cell of the grid sits on the canvas, as a row and column
+    pub fn origin(&self, index: usize) -> (usize, usize) {
+        (self.placements[index].row, self.placements[index].column)
+    }
+
+    /// Whether any grid covers the given cell of the canvas
+    pub fn covers(&self, row: usize, column: usize) -> bool {
+        row < self.rows && column < self.columns && !self.covering[self.index_of(row, column)].is_empty()
+    }
+
+    /// The value of the given cell of the canvas, or None if it's empty or not part of any grid
+    pub fn cell(&self, row: usize, column: usize) -> Option<u8> {
+        if !self.covers(row, column) {
+            return None;
+        }
+
+        let (placement, grid_ref) = &self.covering[self.index_of(row, column)][0];
+        *self.placements[*placement].grid.cell(grid_ref)
+    }
+
+    /// Sets the value of a canvas cell in every grid that covers it.  If any of those grids won't
+    /// take the value then none of them change
+    pub fn set_cell(&mut self, row: usize, column: usize, value: &CellValue) -> Result<&mut Self, Box<dyn Error>> {
+        if !self.covers(row, column) {
+            return Err(UncoveredCell::new(row, column).into());
+        }
+
+        let old_value = self.cell(row, column);
+        let covering = &self.covering[self.index_of(row, column)];
+
+        for (position, (placement, grid_ref)) in covering.iter().enumerate() {
+            let Err(err) = self.placements[*placement].grid.set_cell(grid_ref, value) else {
+                continue;
+            };
+
+            // The grids that did take the value held the old one before, which they can take back
+            for (placement, grid_ref) in covering[.. position].iter() {
+                let grid = &mut self.placements[*placement].grid;
+                match old_value {
+                    Some(old_value) => grid.set_cell(grid_ref, &CellValue::new(old_value)?)?,
+                    None => grid.clear_cell(grid_ref),
+                };
+            }
+
+            return Err(err);
+        }
+
+        Ok(self)
+    }
+
+    pub fn clear_cell(&mut self, row: usize, column: usize) -> &mut Self {
+        if self.covers(row, column) {
+            for (placement, grid_ref) in self.covering[self.index_of(row, column)].iter() {
+                self.placements[*placement].grid.clear_cell(grid_ref);
+            }
+        }
+
+        self
+    }
+
+    /// The number of filled cells on the canvas, counting shared cells once
+    pub fn clue_count(&self) -> usize {
+        (0 .. self.rows)
+            .flat_map(|row| (0 .. self.columns).map(move |column| (row, column)))
+            .filter(|(row, column)| self.cell(*row, *column).is_some())
+            .count()
+    }
+
+    fn index_of(&self, row: usize, column: usize) -> usize {
+        row * self.columns + column
+    }
+
+    fn index_cells(&mut self) {
+        let mut covering = vec![Vec::new(); self.rows * self.columns];
+
+        for (position, placement) in self.placements.iter().enumerate() {
+            for grid_ref in placement.grid.shape().grid_refs() {
+                covering[self.index_of(placement.row + grid_ref.row(), placement.column + grid_ref.column())]
+                    .push((position, grid_ref));
+            }
+        }
+
+        self.covering = covering;
+    }
+
+    /*
+     * Whether a border runs between two cells of the canvas, with cells off the edge of the canvas
+     * given as None.  There's a border around the edge of the area covered by the grids, and
+     * between two covered cells unless some grid has them both in the same region
+     */
+    fn border_between(&self, first: Option<(usize, usize)>, second: Option<(usize, usize)>) -> bool {
+        let covered = |cell: Option<(usize, usize)>| cell.is_some_and(|(row, column)| self.covers(row, column));
+
+        match (covered(first), covered(second)) {
+            (true, true) => {
+                let ((row, column), (other_row, other_column)) = (first.unwrap(), second.unwrap());
+
+                !self.placements.iter().any(|placement| {
+                    let within = |row: usize, column: usize| {
+                        let (rows, columns) = (placement.grid.shape().rows(), placement.grid.shape().columns());
+                        let (row, column) = (row.checked_sub(placement.row)?, column.checked_sub(placement.column)?);
+                        (row < rows && column < columns).then_some((row, column))
+                    };
+
+                    match (within(row, column), within(other_row, other_column)) {
+                        (Some(cell), Some(other)) => placement.grid.regions().same_region(cell, other),
+                        _ => false,
+                    }
+                })
+            },
+            (first, second) => first != second,
+        }
+    }
+
+    /*
+     * Draws the whole canvas as one picture, in the same way as a single grid: a border runs
+     * around each region, cells outside every grid are left blank, and the shading, outlines and
+     * marks of every grid's rules are drawn where that grid sits.  Clues outside the grids aren't
+     * drawn, as the space around one grid is often taken up by another
+     */
+    pub(crate) fn render(&self, show_value: impl Fn(usize, usize, u8) -> ColoredString) -> String {
+        let (rows, columns) = (self.rows, self.columns);
+        let mut decorations = Decorations::new(rows * columns);
+        for placement in self.placements.iter() {
+            decorations.add(&placement.grid, |grid_ref| {
+                self.index_of(placement.row + grid_ref.row(), placement.column + grid_ref.column())
+            });
+        }
+        let cell = |row: usize, column: usize| (row < rows && column < columns).then_some((row, column));
+
+        // The border to the right of the cell, and the one above it, counting from -1 for the
+        // borders along the left and top edges
+        let right = |row: usize, column: isize| {
+            let left = usize::try_from(column).ok().and_then(|column| cell(row, column));
+            self.border_between(left, cell(row, (column + 1) as usize))
+        };
+        let above = |row: isize, column: usize| {
+            let up = usize::try_from(row - 1).ok().and_then(|row| cell(row, column));
+            self.border_between(up, cell(row as usize, column))
+        };
+
+        let value = |row: usize, column: usize| match (self.covers(row, column), self.cell(row, column)) {
+            (true, Some(value)) => show_value(row, column, value),
+            (true, None) => "-".blue(),
+            (false, _) => " ".normal(),
+        };
+
+        if decorations.needs_room() {
+            return render_decorated(&Frame { rows, columns, right: &right, above: &above }, value, &decorations);
+        }
+
+        let column_gaps: Vec<bool> = (0 .. columns).map(|column| (0 .. rows).any(|row| right(row, column as isize))).collect();
+        let row_gaps: Vec<bool> = (0 .. rows).map(|row| (0 .. columns).any(|column| above(row as isize + 1, column))).collect();
+
+        // The line along the top of the given row, with rows giving the line along the bottom of
+        // the canvas
+        let line = |row: usize| {
+            let signed_row = row as isize;
+            let vertical = |row: isize, column: isize| usize::try_from(row).ok().is_some_and(|row| row < rows && right(row, column));
+            let across = |column: isize| usize::try_from(column).ok().is_some_and(|column| above(signed_row, column));
+
+            let mut line = String::from("\t");
+            line.push(Grid::junction(vertical(signed_row - 1, -1), vertical(signed_row, -1), false, across(0)));
+            for (column, gap) in column_gaps.iter().enumerate() {
+                let column = column as isize;
+                line.push_str(if across(column) { "──" } else { "  " });
+
+                if *gap {
+                    line.push(if across(column) { '─' } else { ' ' });
+                    line.push(Grid::junction(
+                        vertical(signed_row - 1, column),
+                        vertical(signed_row, column),
+                        across(column),
+                        across(column + 1),
+                    ));
+                }
+            }
+
+            format!("{}\n", line).yellow().to_string()
+        };
+
+        let mut output = String::with_capacity(8192);
+        output.push_str(&line(0));
+
+        for (row, row_gap) in row_gaps.iter().enumerate() {
+            let edge = if right(row, -1) { "│" } else { " " };
+            output.push_str(&format!("\t{}", edge.yellow()));
+
+            for (column, column_gap) in column_gaps.iter().enumerate() {
+                match decorations.shaded()[self.index_of(row, column)] {
+                    true => output.push_str(&format!("{}{}", " ".on_bright_black(), value(row, column).on_bright_black())),
+                    false => output.push_str(&format!(" {}", value(row, column))),
+                }
+
+                if *column_gap {
+                    match right(row, column as isize) {
+                        true => output.push_str(&format!("{}", " │".yellow())),
+                        false => output.push_str("  "),
+                    }
+                }
+            }
+
+            output.push('\n');
+            if *row_gap && row < rows - 1 {
+                output.push_str(&line(row + 1));
+            }
+        }
+        output.push_str(&line(rows));
+
+        output
+    }
+}
+
+impl Default for MultiGrid {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Display for MultiGrid {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        let output = self.render(|_, _, value| GridShape::symbol_for(value).to_string().white());
+
+        write!(f, "{}", output)
+    }
+}
+
+/*
+ * Solves all the grids of a canvas together, as one problem.  It searches the same way as the
+ * DepthFirstSolver, always filling the canvas cell with the fewest options left, but a shared cell
+ * only has the options every grid covering it allows
+ */
+#[derive(Debug)]
+pub struct MultiGridSolver<'problem> {
+    problem: &'problem MultiGrid,
+    // The canvas cells of every house of every grid, along with every value that grid can hold
+    houses: Vec<(CandidateSet, Vec<usize>)>,
+    solution: Option<MultiGrid>,
+}
+
+impl<'problem> MultiGridSolver<'problem> {
+    pub fn for_problem(problem: &'problem MultiGrid) -> Self {
+        Self { problem, houses: Self::houses_of(problem), solution: None }
+    }
+
+    pub fn solve(&mut self) -> &mut Self {
+        self.solution = self.find_solutions(1).pop();
+        self
+    }
+
+    /// Searches for up to limit solutions, like DepthFirstSolver::find_solutions
+    pub fn find_solutions(&mut self, limit: usize) -> Vec<MultiGrid> {
+        let mut solution = self.problem.clone();
+        let mut found: Vec<MultiGrid> = Vec::new();
+
+        if limit > 0 {
+            self.find_solution(&mut solution, &mut found, limit);
+        }
+
+        found
+    }
+
+    pub fn count_solutions(&mut self, limit: usize) -> usize {
+        self.find_solutions(limit).len()
+    }
+
+    pub fn has_unique_solution(&mut self) -> bool {
+        self.count_solutions(2) == 1
+    }
+
+    pub fn get_solution(&self) -> &Option<MultiGrid> {
+        &self.solution
+    }
+
+    fn houses_of(problem: &MultiGrid) -> Vec<(CandidateSet, Vec<usize>)> {
+        problem.placements.iter()
+            .flat_map(|placement| {
+                let shape = placement.grid.shape();
+                let all_values: CandidateSet = (1 << shape.size()) - 1;
+
+                placement.grid.constraints()
+                    .iter()
+                    .filter(|constraint| constraint.is_house(shape))
+                    .map(move |constraint| {
+                        let cells = constraint.affected_cells(shape)
+                            .iter()
+                            .map(|grid_ref| problem.index_of(placement.row + grid_ref.row(), placement.column + grid_ref.column()))
+                            .collect();
+
+                        (all_values, cells)
+                    })
+            })
+            .collect()
+    }
+
+    /*
+     * Each grid's constraints prune the candidates of its own cells, and a canvas cell keeps only
+     * the candidates left by every grid covering it.  Like the DepthFirstSolver we pick the empty
+     * cell with the fewest, unless some value has only one place left in a house
+     */
+    fn most_constrained_cell(&self, solution: &MultiGrid) -> Option<(usize, CandidateSet)> {
+        let cell_count = solution.rows * solution.columns;
+        let mut filled: Vec<CandidateSet> = vec![0; cell_count];
+        let mut open: Vec<CandidateSet> = vec![CandidateSet::MAX; cell_count];
+
+        for placement in solution.placements.iter() {
+            let (grid, shape) = (&placement.grid, placement.grid.shape());
+            let all_values: CandidateSet = (1 << shape.size()) - 1;
+
+            let mut candidates: Vec<CandidateSet> = shape.grid_refs()
+                .map(|grid_ref| if grid.cell(&grid_ref).is_some() { 0 } else { all_values })
+                .collect();
+            for constraint in grid.constraints() {
+                constraint.prune(grid, &mut candidates);
+            }
+
+            for grid_ref in shape.grid_refs() {
+                let index = solution.index_of(placement.row + grid_ref.row(), placement.column + grid_ref.column());
+                match grid.cell(&grid_ref) {
+                    Some(value) => filled[index] = 1 << (value - 1),
+                    None => open[index] &= candidates[grid_ref.to_index(shape)],
+                }
+            }
+        }
+
+        let mut best: Option<(usize, CandidateSet)> = None;
+        for index in (0 .. cell_count).filter(|index| filled[*index] == 0 && !solution.covering[*index].is_empty()) {
+            if best.is_none_or(|(_, best_open)| open[index].count_ones() < best_open.count_ones()) {
+                best = Some((index, open[index]));
+            }
+        }
+
+        let (mut index, mut candidates) = best?;
+        if candidates.count_ones() > 1 {
+            for (all_values, house) in self.houses.iter() {
+                let mut placed = 0;
+                let mut seen_once = 0;
+                let mut seen_twice = 0;
+                for cell in house.iter() {
+                    let cell_open = if filled[*cell] == 0 { open[*cell] } else { 0 };
+                    placed |= filled[*cell];
+                    seen_twice |= seen_once & cell_open;
+                    seen_once |= cell_open;
+                }
+
+                // A value the house still needs but has nowhere to put is a dead end
+                if all_values & !placed & !seen_once != 0 {
+                    candidates = 0;
+                    break;
+                }
+
+                let hidden_singles = seen_once & !seen_twice;
+                if hidden_singles != 0 {
+                    let value_bit = 1 << hidden_singles.trailing_zeros();
+                    index = *house.iter()
+                        .find(|cell| filled[**cell] == 0 && open[**cell] & value_bit != 0)
+                        .expect("The value has exactly one place in the house");
+                    candidates = value_bit;
+                    break;
+                }
+            }
+        }
+
+        Some((index, candidates))
+    }
+
+    /// Returns true once enough solutions have been found to stop searching
+    fn find_solution(&self, solution: &mut MultiGrid, found: &mut Vec<MultiGrid>, limit: usize) -> bool {
+        let Some((index, candidates)) = self.most_constrained_cell(solution) else {
+            found.push(solution.clone());
+            return found.len() >= limit;
+        };
+
+        let (row, column) = (index / solution.columns, index % solution.columns);
+        for value in (1 ..= GridShape::MAX_SIZE as u8).filter(|value| candidates & (1 << (value - 1)) != 0) {
+            let value = CellValue::new(value).expect("Candidates are always valid values");
+
+            if solution.set_cell(row, column, &value).is_ok() && self.find_solution(solution, found, limit) {
+                return true;
+            }
+            solution.clear_cell(row, column);
+        }
+
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn value(value: u8) -> CellValue {
+        CellValue::new(value).unwrap()
+    }
+
+    fn empty_samurai() -> MultiGrid {
+        MultiGrid::samurai([Grid::new(), Grid::new(), Grid::new(), Grid::new(), Grid::new()]).unwrap()
+    }
+
+    #[test]
+    fn a_shared_cell_is_left_alone_if_any_grid_rejects_it() {
+        let mut middle = Grid::new();
+        middle.set_cell(&GridReference::from_numbers(0, 3).unwrap(), &value(5)).unwrap();
+        let mut samurai = MultiGrid::samurai([Grid::new(), Grid::new(), middle, Grid::new(), Grid::new()]).unwrap();
+
+        // The top left grid takes the 5 first, but it breaks the middle grid's first row
+        assert!(samurai.set_cell(6, 6, &value(5)).is_err());
+        assert_eq!(samurai.cell(6, 6), None);
+        assert_eq!(*samurai.grid(0).cell(&GridReference::from_numbers(6, 6).unwrap()), None);
+
+        samurai.set_cell(6, 6, &value(4)).unwrap();
+        assert_eq!(*samurai.grid(0).cell(&GridReference::from_numbers(6, 6).unwrap()), Some(4));
+        assert_eq!(*samurai.grid(2).cell(&GridReference::from_numbers(0, 0).unwrap()), Some(4));
+    }
+
+    #[test]
+    fn overlapping_grids_have_to_agree() {
+        let mut corner = Grid::new();
+        corner.set_cell(&GridReference::from_numbers(8, 8).unwrap(), &value(1)).unwrap();
+        let mut middle = Grid::new();
+        middle.set_cell(&GridReference::from_numbers(2, 2).unwrap(), &value(2)).unwrap();
+
+        let error = MultiGrid::samurai([corner, Grid::new(), middle, Grid::new(), Grid::new()]).unwrap_err();
+        assert!(error.is::<OverlapConflict>());
+        assert!(empty_samurai().set_cell(0, 10, &value(1)).unwrap_err().is::<UncoveredCell>());
+        assert!(MultiGrid::samurai([Grid::with_shape(GridShape::new(2, 2).unwrap()), Grid::new(), Grid::new(), Grid::new(), Grid::new()])
+            .unwrap_err()
+            .is::<NonStandardShape>());
+    }
+
+    #[test]
+    fn solves_all_five_grids_of_a_samurai_together() {
+        let solved = MultiGridSolver::for_problem(&empty_samurai()).solve().get_solution().clone().unwrap();
+        assert_eq!(solved.clue_count(), 369);
+
+        // Clearing a row across the middle grid leaves one way to fill it back in
+        let mut puzzle = solved.clone();
+        for column in 6 .. 15 {
+            puzzle.clear_cell(10, column);
+        }
+        assert_eq!(puzzle.clue_count(), 360);
+
+        let mut solver = MultiGridSolver::for_problem(&puzzle);
+        assert!(solver.has_unique_solution());
+        let solution = solver.solve().get_solution().clone().unwrap();
+        assert!((6 .. 15).all(|column| solution.cell(10, column) == solved.cell(10, column)));
+    }
+}
@@ -1,3 +1,4 @@
+use crate::sudoku::constraint::CandidateSet;
 use crate::sudoku::grid::Grid;
 use crate::sudoku::reference::GridReference;
 use std::fmt::{Debug, Formatter};
@@ -17,38 +18,33 @@ impl<'problem> StandardOptionFinder<'problem> {
     pub fn new(problem: &'problem Grid) -> StandardOptionFinder<'problem> {
         Self { problem }
     }
-
-    fn build_used_list(&self, grid_ref: &GridReference) -> OptionList {
-        let mut used_values: OptionList = self.problem.row_values(&grid_ref.row_ref());
-
-        used_values.extend(self.problem.col_values(&grid_ref.column_ref()));
-        used_values.extend(self.problem.subgrid_values_at(&grid_ref));
-
-        used_values.sort();
-        used_values.dedup();
-
-        used_values
-    }
 }
 
 impl<'problem> OptionFinder for StandardOptionFinder<'problem> {
     fn find_for_cell(&mut self, grid_ref: &GridReference) -> OptionList {
         // Early out: If this cell already has a value then it can't have any options
-        if self.problem.cell(&grid_ref).is_some() {
+        if self.problem.cell(grid_ref).is_some() {
             return Vec::new();
         }
 
-        let mut options = vec![1, 2, 3, 4, 5, 6, 7, 8, 9];
-        let used_vals = self.build_used_list(&grid_ref);
-
-        for value in used_vals.iter() {
-            let found: Option<usize> = options.iter().position(|pos| pos == value);
-            if let Some(index) = found {
-                options.remove(index);
-            }
+        // Each constraint on the cell gets to rule out the values it doesn't allow.  Constraints can
+        // look at what the other empty cells could hold, so those start out with every value too
+        let shape = self.problem.shape();
+        let index = grid_ref.to_index(shape);
+        let mut candidates: Vec<CandidateSet> = shape.grid_refs()
+            .map(|cell| match self.problem.cell(&cell) {
+                Some(_) => 0,
+                None => (1 << shape.size()) - 1,
+            })
+            .collect();
+
+        for constraint in self.problem.constraints_at(grid_ref) {
+            constraint.prune(self.problem, &mut candidates);
         }
 
-        options
+        (1 ..= shape.max_value())
+            .filter(|value| candidates[index] & (1 << (value - 1)) != 0)
+            .collect()
     }
 }
 
@@ -0,0 +1,467 @@
+use crate::sudoku::constraint::{cell_options, narrow_sum, prune_cells, CandidateSet, Constraint};
+use crate::sudoku::error::{ClueConstraint, ClueError, InvalidDiagonalStart};
+use crate::sudoku::formula::{Formula, Term};
+use crate::sudoku::grid::Grid;
+use crate::sudoku::reference::{ColumnReference, GridReference, RowReference};
+use crate::sudoku::sat::Cnf;
+use crate::sudoku::shape::GridShape;
+use crate::sudoku::transform::Transform;
+use std::error::Error;
+use std::sync::Arc;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Diagonal {
+    DownRight,
+    DownLeft,
+    UpRight,
+    UpLeft,
+}
+
+impl Diagonal {
+    /// How far a single step along the diagonal moves, in rows and columns
+    fn step(&self) -> (isize, isize) {
+        match self {
+            Self::DownRight => (1, 1),
+            Self::DownLeft => (1, -1),
+            Self::UpRight => (-1, 1),
+            Self::UpLeft => (-1, -1),
+        }
+    }
+
+    fn arrow(&self) -> char {
+        match self {
+            Self::DownRight => '↘',
+            Self::DownLeft => '↙',
+            Self::UpRight => '↗',
+            Self::UpLeft => '↖',
+        }
+    }
+}
+
+/*
+ * A Little Killer clue: the total of a diagonal running from a cell on the edge of the grid right
+ * across to the far edge.  Unlike a Killer cage, values can repeat along the diagonal wherever the
+ * rest of the rules allow it.  The total is written just outside the grid, before the first cell,
+ * with an arrow showing which way the diagonal runs.  The diagonal depends on the size of the grid,
+ * so a clue only fits the shape of grid it was made for
+ */
+#[derive(Debug, Clone)]
+pub struct LittleKiller {
+    shape: GridShape,
+    diagonal: Diagonal,
+    cells: Vec<GridReference>,
+    total: usize,
+}
+
+impl LittleKiller {
+    /// The diagonal runs from the start cell in the given direction, so the cell a step before the
+    /// start has to be outside the grid
+    pub fn new(shape: &GridShape, start: GridReference, diagonal: Diagonal, total: usize) -> Result<Self, Box<dyn Error>> {
+        let start = GridReference::for_shape(shape, start.row(), start.column())?;
+        let (row_step, column_step) = diagonal.step();
+        let inside = |row: isize, column: isize| {
+            let row = usize::try_from(row).ok().filter(|row| *row < shape.rows())?;
+            let column = usize::try_from(column).ok().filter(|column| *column < shape.columns())?;
+
+            Some(GridReference::for_shape(shape, row, column).expect("Row and column are always in range"))
+        };
+
+        let (mut row, mut column) = (start.row() as isize, start.column() as isize);
+        if inside(row - row_step, column - column_step).is_some() {
+            return Err(InvalidDiagonalStart::new(start.row(), start.column()).into());
+        }
+
+        let mut cells = Vec::new();
+        while let Some(grid_ref) = inside(row, column) {
+            cells.push(grid_ref);
+            (row, column) = (row + row_step, column + column_step);
+        }
+
+        Ok(Self { shape: *shape, diagonal, cells, total })
+    }
+
+    pub fn diagonal(&self) -> Diagonal {
+        self.diagonal
+    }
+
+    pub fn cells(&self) -> &[GridReference] {
+        &self.cells
+    }
+
+    pub fn total(&self) -> usize {
+        self.total
+    }
+}
+
+impl Constraint for LittleKiller {
+    fn affected_cells(&self, _shape: &GridShape) -> Vec<GridReference> {
+        self.cells.clone()
+    }
+
+    fn check(&self, grid: &Grid, changed: &GridReference) -> Result<(), Box<dyn Error>> {
+        let Some(value) = *grid.cell(changed) else {
+            return Ok(());
+        };
+
+        let total = self.total as i32;
+        match narrow_sum(grid.shape(), &cell_options(grid, &self.cells, None), total, total) {
+            Some(_) => Ok(()),
+            None => Err(ClueError::new(changed.row(), changed.column(), value, ClueConstraint::LittleKiller).into()),
+        }
+    }
+
+    fn prune(&self, grid: &Grid, candidates: &mut [CandidateSet]) {
+        let total = self.total as i32;
+        let narrowed = narrow_sum(grid.shape(), &cell_options(grid, &self.cells, Some(candidates)), total, total);
+        prune_cells(grid, &self.cells, narrowed, candidates);
+    }
+
+    fn encode(&self, _shape: &GridShape, cnf: &mut Cnf) -> bool {
+        cnf.require_total(&self.cells, self.total);
+
+        true
+    }
+
+    fn formulas(&self, _shape: &GridShape) -> Option<Vec<Formula>> {
+        Some(vec![Formula::Equal(Term::sum_of(&self.cells), Term::Number(self.total))])
+    }
+
+    fn outside_clue(&self, _shape: &GridShape) -> Option<(isize, isize, String)> {
+        let (row_step, column_step) = self.diagonal.step();
+        let first = &self.cells[0];
+
+        Some((
+            first.row() as isize - row_step,
+            first.column() as isize - column_step,
+            format!("{}{}", self.total, self.diagonal.arrow()),
+        ))
+    }
+
+    fn shape(&self) -> Option<GridShape> {
+        Some(self.shape)
+    }
+
+    /// The diagonal is kept if the cells it moves to make up another diagonal, starting from the
+    /// cell the start moved to
+    fn transformed(&self, shape: &GridShape, transform: &Transform) -> Option<Arc<dyn Constraint>> {
+        if !transform.keeps_digits() {
+            return None;
+        }
+
+        let cells = transform.move_cells(&self.cells);
+        [Diagonal::DownRight, Diagonal::DownLeft, Diagonal::UpRight, Diagonal::UpLeft]
+            .into_iter()
+            .filter_map(|diagonal| Self::new(shape, cells[0].clone(), diagonal, self.total).ok())
+            .find(|clue| clue.cells == cells)
+            .map(|clue| Arc::new(clue) as Arc<dyn Constraint>)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Side {
+    Left,
+    Right,
+    Top,
+    Bottom,
+}
+
+/*
+ * An X-sum clue, given outside one end of a row or column.  Reading in from that end, the first
+ * value says how many cells to add up, counting itself, and those cells must add up to the total
+ */
+#[derive(Debug, Clone)]
+pub struct XSum {
+    side: Side,
+    line: usize,
+    total: usize,
+}
+
+impl XSum {
+    /// The line is a row for a clue on the left or right, and a column for one at the top or bottom
+    pub fn new(side: Side, line: usize, total: usize) -> Result<Self, Box<dyn Error>> {
+        match side {
+            Side::Left | Side::Right => RowReference::for_shape(&GridShape::largest(), line).map(|_| ())?,
+            Side::Top | Side::Bottom => ColumnReference::for_shape(&GridShape::largest(), line).map(|_| ())?,
+        }
+
+        Ok(Self { side, line, total })
+    }
+
+    pub fn side(&self) -> Side {
+        self.side
+    }
+
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    pub fn total(&self) -> usize {
+        self.total
+    }
+
+    /// The cells of the line, reading in from the clue.  Like a sandwich's these are only made out
+    /// for the largest grid, so it's up to the grid to check they fit
+    fn cells(&self, shape: &GridShape) -> Vec<GridReference> {
+        let (largest, size) = (GridShape::largest(), shape.size());
+
+        (0 .. size)
+            .map(|position| match self.side {
+                Side::Left => GridReference::for_shape(&largest, self.line, position),
+                Side::Right => GridReference::for_shape(&largest, self.line, size - 1 - position),
+                Side::Top => GridReference::for_shape(&largest, position, self.line),
+                Side::Bottom => GridReference::for_shape(&largest, size - 1 - position, self.line),
+            })
+            .map(|grid_ref| grid_ref.expect("Lines are checked against the largest grid when they're made"))
+            .collect()
+    }
+
+    /*
+     * Narrows the options for each cell of the line down to the ones that fit some count in the
+     * first cell, or returns None if no count fits.  With a count of n the first n cells (the first
+     * one included) have to make up the total, and the cells beyond them are free
+     */
+    fn narrow(&self, shape: &GridShape, options: &[CandidateSet]) -> Option<Vec<CandidateSet>> {
+        let mut narrowed = vec![0; options.len()];
+
+        for count in (1 ..= options.len()).filter(|count| options[0] & (1 << (count - 1)) != 0) {
+            let rest = self.total as i32 - count as i32;
+            let Some(counted) = narrow_sum(shape, &options[1 .. count], rest, rest) else {
+                continue;
+            };
+
+            narrowed[0] |= 1 << (count - 1);
+            for (offset, option) in counted.iter().enumerate() {
+                narrowed[1 + offset] |= option;
+            }
+            for position in count .. options.len() {
+                narrowed[position] |= options[position];
+            }
+        }
+
+        (!narrowed.contains(&0)).then_some(narrowed)
+    }
+}
+
+impl Constraint for XSum {
+    fn affected_cells(&self, shape: &GridShape) -> Vec<GridReference> {
+        self.cells(shape)
+    }
+
+    fn check(&self, grid: &Grid, changed: &GridReference) -> Result<(), Box<dyn Error>> {
+        let Some(value) = *grid.cell(changed) else {
+            return Ok(());
+        };
+
+        let shape = grid.shape();
+        match self.narrow(shape, &cell_options(grid, &self.cells(shape), None)) {
+            Some(_) => Ok(()),
+            None => Err(ClueError::new(changed.row(), changed.column(), value, ClueConstraint::XSum).into()),
+        }
+    }
+
+    fn prune(&self, grid: &Grid, candidates: &mut [CandidateSet]) {
+        let cells = self.cells(grid.shape());
+        let narrowed = self.narrow(grid.shape(), &cell_options(grid, &cells, Some(candidates)));
+        prune_cells(grid, &cells, narrowed, candidates);
+    }
+
+    /// With a count of n in the first cell, the running totals of the next n - 1 cells have to
+    /// make up the rest of the total
+    fn encode(&self, shape: &GridShape, cnf: &mut Cnf) -> bool {
+        let cells = self.cells(shape);
+        let totals = cnf.running_totals(&cells[1 ..]);
+
+        for count in 1 ..= cells.len().min(shape.max_value() as usize) {
+            for (total, variable) in totals[count - 1].iter().enumerate() {
+                if let Some(variable) = variable && total + count != self.total {
+                    cnf.add_clause(vec![-cnf.variable(&cells[0], count as u8), -variable]);
+                }
+            }
+        }
+
+        true
+    }
+
+    fn formulas(&self, shape: &GridShape) -> Option<Vec<Formula>> {
+        let cells = self.cells(shape);
+        let formulas = (1 ..= cells.len())
+            .map(|count| Formula::Implies(
+                Box::new(Formula::Equal(Term::Cell(cells[0].clone()), Term::Number(count))),
+                Box::new(Formula::Equal(Term::sum_of(&cells[.. count]), Term::Number(self.total))),
+            ))
+            .collect();
+
+        Some(formulas)
+    }
+
+    fn outside_clue(&self, shape: &GridShape) -> Option<(isize, isize, String)> {
+        let (line, size) = (self.line as isize, shape.size() as isize);
+        let (row, column) = match self.side {
+            Side::Left => (line, -1),
+            Side::Right => (line, size),
+            Side::Top => (-1, line),
+            Side::Bottom => (size, line),
+        };
+
+        Some((row, column, self.total.to_string()))
+    }
+    /// The clue moves to whichever end of the line the first cell went to, and the line has to
+    /// stay in order reading in from it
+    fn transformed(&self, shape: &GridShape, transform: &Transform) -> Option<Arc<dyn Constraint>> {
+        if !transform.keeps_digits() {
+            return None;
+        }
+
+        let cells = transform.move_cells(&self.cells(shape));
+        let (first, second) = (&cells[0], &cells[1]);
+        let (side, line) = match (first.row() == second.row(), first.column() < second.column(), first.row() < second.row()) {
+            (true, true, _) => (Side::Left, first.row()),
+            (true, false, _) => (Side::Right, first.row()),
+            (false, _, true) => (Side::Top, first.column()),
+            (false, _, false) => (Side::Bottom, first.column()),
+        };
+
+        let clue = Self::new(side, line, self.total).ok()?;
+        (clue.cells(shape) == cells).then(|| Arc::new(clue) as Arc<dyn Constraint>)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sudoku::error::ShapeMismatch;
+    use crate::sudoku::grid::CellValue;
+    use crate::sudoku::sandwich::Sandwich;
+    use crate::sudoku::sat::{CardinalityEncoding, Cnf};
+
+    fn cell(row: usize, column: usize) -> GridReference {
+        GridReference::from_numbers(row, column).unwrap()
+    }
+
+    fn set(grid: &mut Grid, row: usize, column: usize, value: u8) -> Result<(), Box<dyn Error>> {
+        grid.set_cell(&cell(row, column), &CellValue::new(value).unwrap()).map(|_| ())
+    }
+
+    fn plain(text: &str) -> String {
+        let mut plain = String::new();
+        let mut in_escape = false;
+        for character in text.chars() {
+            match character {
+                '\x1b' => in_escape = true,
+                'm' if in_escape => in_escape = false,
+                _ if !in_escape => plain.push(character),
+                _ => {},
+            }
+        }
+
+        plain
+    }
+
+    #[test]
+    fn diagonals_run_from_the_edge_to_the_far_edge() {
+        let shape = GridShape::standard();
+
+        let corner = LittleKiller::new(&shape, cell(0, 0), Diagonal::DownRight, 45).unwrap();
+        assert_eq!(corner.cells().len(), 9);
+        assert_eq!(corner.cells()[8], cell(8, 8));
+
+        let short = LittleKiller::new(&shape, cell(0, 3), Diagonal::DownLeft, 10).unwrap();
+        assert_eq!(short.cells(), &[cell(0, 3), cell(1, 2), cell(2, 1), cell(3, 0)]);
+
+        let error = LittleKiller::new(&shape, cell(1, 1), Diagonal::DownRight, 10).unwrap_err();
+        assert!(error.is::<InvalidDiagonalStart>());
+        assert!(LittleKiller::new(&shape, cell(8, 4), Diagonal::DownRight, 10).unwrap_err().is::<InvalidDiagonalStart>());
+    }
+
+    #[test]
+    fn little_killers_add_up_along_the_diagonal() {
+        let mut grid = Grid::new();
+        grid.add_constraint(LittleKiller::new(grid.shape(), cell(0, 3), Diagonal::DownLeft, 10).unwrap()).unwrap();
+        set(&mut grid, 0, 3, 1).unwrap();
+        set(&mut grid, 1, 2, 2).unwrap();
+
+        assert!(set(&mut grid.clone(), 2, 1, 7).unwrap_err().is::<ClueError>());
+        set(&mut grid, 2, 1, 3).unwrap();
+        assert!(set(&mut grid.clone(), 3, 0, 3).unwrap_err().is::<ClueError>());
+        assert!(set(&mut grid, 3, 0, 4).is_ok());
+    }
+
+    #[test]
+    fn little_killers_only_fit_the_grid_they_were_made_for() {
+        let small = GridShape::new(2, 2).unwrap();
+        let clue = LittleKiller::new(&small, cell(0, 0), Diagonal::DownRight, 10).unwrap();
+
+        assert!(Grid::new().add_constraint(clue.clone()).unwrap_err().is::<ShapeMismatch>());
+        assert!(Grid::with_shape(small).add_constraint(clue).is_ok());
+    }
+
+    #[test]
+    fn x_sums_count_in_from_the_clue() {
+        let mut grid = Grid::new();
+        grid.add_constraint(XSum::new(Side::Right, 0, 9).unwrap()).unwrap();
+        set(&mut grid, 0, 8, 3).unwrap();
+        set(&mut grid, 0, 7, 4).unwrap();
+
+        assert!(set(&mut grid.clone(), 0, 6, 1).unwrap_err().is::<ClueError>());
+        assert!(set(&mut grid, 0, 6, 2).is_ok());
+    }
+
+    #[test]
+    fn an_x_sum_of_one_puts_a_one_first() {
+        let mut grid = Grid::new();
+        grid.add_constraint(XSum::new(Side::Top, 4, 1).unwrap()).unwrap();
+
+        assert!(set(&mut grid.clone(), 0, 4, 2).unwrap_err().is::<ClueError>());
+
+        let mut candidates = vec![0b1_1111_1111; 81];
+        grid.constraints().iter().for_each(|constraint| constraint.prune(&grid, &mut candidates));
+        assert_eq!(candidates[4], 0b1);
+        assert_eq!(candidates[13], 0b1_1111_1111);
+    }
+
+    #[test]
+    fn clues_in_the_same_place_are_written_together() {
+        let mut grid = Grid::new();
+        grid.add_constraint("r1=15".parse::<Sandwich>().unwrap()).unwrap();
+        grid.add_constraint(XSum::new(Side::Left, 0, 10).unwrap()).unwrap();
+        grid.add_constraint(XSum::new(Side::Right, 0, 20).unwrap()).unwrap();
+
+        let drawn = plain(&grid.to_string());
+        let lines: Vec<&str> = drawn.lines().collect();
+        assert!(lines[1].starts_with("\t15/10 │"));
+        assert!(lines[1].ends_with("│ 20"));
+        assert!(lines[2].starts_with("\t      │"));
+    }
+
+    #[test]
+    fn little_killer_totals_are_written_before_the_diagonal() {
+        let mut grid = Grid::new();
+        grid.add_constraint(LittleKiller::new(grid.shape(), cell(0, 3), Diagonal::DownLeft, 10).unwrap()).unwrap();
+
+        let clue = grid.constraints().last().unwrap().outside_clue(grid.shape());
+        assert_eq!(clue, Some((-1, 4, "10↙".to_string())));
+        assert!(plain(&grid.to_string()).lines().next().unwrap().contains("10↙"));
+    }
+    #[test]
+    fn the_encoding_agrees_with_the_clues_on_a_solved_grid() {
+        let solved: Grid = "534678912672195348198342567859761423426853791713924856961537284287419635345286179".parse().unwrap();
+        let clues = |killer_total: usize| {
+            let mut grid = Grid::new();
+            grid.add_constraint(LittleKiller::new(grid.shape(), cell(0, 0), Diagonal::DownRight, killer_total).unwrap()).unwrap();
+            grid.add_constraint(XSum::new(Side::Left, 0, 25).unwrap()).unwrap();
+            grid.add_constraint(XSum::new(Side::Top, 8, 10).unwrap()).unwrap();
+            grid
+        };
+        let fill = |mut grid: Grid| {
+            solved.shape()
+                .grid_refs()
+                .try_for_each(|grid_ref| set(&mut grid, grid_ref.row(), grid_ref.column(), solved.cell(&grid_ref).unwrap()))
+        };
+
+        for (killer_total, holds) in [(50, true), (49, false)] {
+            let cnf = Cnf::from_grid(&clues(killer_total), CardinalityEncoding::Pairwise).unwrap();
+            assert_eq!(cnf.holds_for(&solved), holds);
+            assert_eq!(fill(clues(killer_total)).is_ok(), holds);
+        }
+    }
+}
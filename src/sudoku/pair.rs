@@ -0,0 +1,123 @@
+use crate::sudoku::constraint::CandidateSet;
+use crate::sudoku::error::{PairConstraint, PairError};
+use crate::sudoku::formula::{Formula, Term};
+use crate::sudoku::grid::Grid;
+use crate::sudoku::reference::GridReference;
+use crate::sudoku::sat::Cnf;
+use crate::sudoku::shape::GridShape;
+use std::error::Error;
+
+/*
+ * Checks a rule between two particular cells after one of them has changed.  The value has to pair
+ * up with the other cell's value, or with some value the other cell could still take if it's
+ * empty.  The values are always given to the rule in the order of the cells, which matters for
+ * rules like greater-than that aren't the same both ways round
+ */
+pub(crate) fn check_pair(
+    kind: &PairConstraint,
+    cells: &[GridReference; 2],
+    grid: &Grid,
+    changed: &GridReference,
+) -> Result<(), Box<dyn Error>> {
+    let Some(value) = *grid.cell(changed) else {
+        return Ok(());
+    };
+
+    let changed_first = *changed == cells[0];
+    let other = if changed_first { &cells[1] } else { &cells[0] };
+    let pairs_with = |other_value: u8| match changed_first {
+        true => allows(kind, value, other_value),
+        false => allows(kind, other_value, value),
+    };
+
+    let broken = match *grid.cell(other) {
+        Some(other_value) => !pairs_with(other_value),
+        None => !(1 ..= grid.shape().max_value()).any(pairs_with),
+    };
+
+    if broken {
+        return Err(PairError::new(changed, other, value, kind.clone()).into());
+    }
+
+    Ok(())
+}
+
+/// Narrows each empty cell of the pair down to the values that pair up with some value the other
+/// cell could hold, in the order of the cells like check_pair
+pub(crate) fn prune_pair(kind: &PairConstraint, cells: &[GridReference; 2], grid: &Grid, candidates: &mut [CandidateSet]) {
+    let shape = grid.shape();
+
+    for (position, cell) in cells.iter().enumerate() {
+        if grid.cell(cell).is_some() {
+            continue;
+        }
+
+        let other = &cells[1 - position];
+        let other_values = match *grid.cell(other) {
+            Some(value) => 1 << (value - 1),
+            None => candidates[other.to_index(shape)],
+        };
+
+        let supported = (1 ..= shape.max_value())
+            .filter(|value| {
+                (1 ..= shape.max_value())
+                    .filter(|other_value| other_values & (1 << (other_value - 1)) != 0)
+                    .any(|other_value| match position {
+                        0 => allows(kind, *value, other_value),
+                        _ => allows(kind, other_value, *value),
+                    })
+            })
+            .fold(0, |supported, value| supported | (1 << (value - 1)));
+
+        candidates[cell.to_index(shape)] &= supported;
+    }
+}
+
+/// Rules out every pair of values the rule doesn't allow, in the order of the cells like check_pair
+pub(crate) fn encode_pair(kind: &PairConstraint, cells: &[GridReference; 2], cnf: &mut Cnf) {
+    cnf.forbid_pairs(&cells[0], &cells[1], |first, second| allows(kind, first, second));
+}
+
+/// The rule as a formula over the two cells' values, in the order of the cells like check_pair
+pub(crate) fn pair_formula(kind: &PairConstraint, cells: &[GridReference; 2]) -> Formula {
+    let (first, second) = (Term::Cell(cells[0].clone()), Term::Cell(cells[1].clone()));
+    let plus = |term: &Term, other: Term| Term::Sum(vec![term.clone(), other]);
+
+    match kind {
+        PairConstraint::NonConsecutive => Formula::And(vec![
+            Formula::NotEqual(first.clone(), plus(&second, Term::Number(1))),
+            Formula::NotEqual(second.clone(), plus(&first, Term::Number(1))),
+        ]),
+        PairConstraint::WhiteDot | PairConstraint::ConsecutivePair => Formula::Or(vec![
+            Formula::Equal(first.clone(), plus(&second, Term::Number(1))),
+            Formula::Equal(second.clone(), plus(&first, Term::Number(1))),
+        ]),
+        PairConstraint::BlackDot => Formula::Or(vec![
+            Formula::Equal(first.clone(), plus(&second, second.clone())),
+            Formula::Equal(second.clone(), plus(&first, first.clone())),
+        ]),
+        PairConstraint::X => Formula::Equal(plus(&first, second), Term::Number(10)),
+        PairConstraint::V => Formula::Equal(plus(&first, second), Term::Number(5)),
+        PairConstraint::GreaterThan => Formula::Less(second, first),
+    }
+}
+
+/// Whether the two values can sit either side of the rule.  For a greater-than sign the first value
+/// is the one that has to be greater
+pub(crate) fn allows(kind: &PairConstraint, first: u8, second: u8) -> bool {
+    match kind {
+        PairConstraint::NonConsecutive => first.abs_diff(second) != 1,
+        PairConstraint::WhiteDot | PairConstraint::ConsecutivePair => first.abs_diff(second) == 1,
+        PairConstraint::BlackDot => first == second * 2 || second == first * 2,
+        PairConstraint::X => first + second == 10,
+        PairConstraint::V => first + second == 5,
+        PairConstraint::GreaterThan => first > second,
+    }
+}
+
+/// Every value that can sit alongside the given one
+pub(crate) fn partners_of(kind: &PairConstraint, shape: &GridShape, value: u8) -> CandidateSet {
+    (1 ..= shape.max_value())
+        .filter(|other| allows(kind, value, *other))
+        .fold(0, |partners, other| partners | (1 << (other - 1)))
+}
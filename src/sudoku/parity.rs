@@ -0,0 +1,137 @@
+use crate::sudoku::constraint::{CandidateSet, Constraint};
+use crate::sudoku::error::{Parity, ParityError};
+use crate::sudoku::formula::{Formula, Term};
+use crate::sudoku::grid::Grid;
+use crate::sudoku::reference::GridReference;
+use crate::sudoku::sat::Cnf;
+use crate::sudoku::shape::GridShape;
+use crate::sudoku::transform::Transform;
+use std::error::Error;
+use std::sync::Arc;
+
+/// A cell that can only hold odd values, or only even ones.  Odd cells are usually drawn with a
+/// circle and even cells with a square
+#[derive(Debug, Clone)]
+pub struct ParityCell {
+    parity: Parity,
+    cell: GridReference,
+}
+
+impl ParityCell {
+    pub fn odd(cell: GridReference) -> Self {
+        Self { parity: Parity::Odd, cell }
+    }
+
+    pub fn even(cell: GridReference) -> Self {
+        Self { parity: Parity::Even, cell }
+    }
+
+    pub fn parity(&self) -> Parity {
+        self.parity
+    }
+
+    pub fn cell(&self) -> &GridReference {
+        &self.cell
+    }
+
+    /// Every value of the right parity, as a set of candidates
+    fn values(&self, shape: &GridShape) -> CandidateSet {
+        let remainder = match self.parity {
+            Parity::Odd => 1,
+            Parity::Even => 0,
+        };
+
+        (1 ..= shape.max_value())
+            .filter(|value| value % 2 == remainder)
+            .fold(0, |values, value| values | (1 << (value - 1)))
+    }
+}
+
+impl Constraint for ParityCell {
+    fn affected_cells(&self, _shape: &GridShape) -> Vec<GridReference> {
+        vec![self.cell.clone()]
+    }
+
+    fn check(&self, grid: &Grid, changed: &GridReference) -> Result<(), Box<dyn Error>> {
+        let Some(value) = *grid.cell(changed) else {
+            return Ok(());
+        };
+
+        if self.values(grid.shape()) & (1 << (value - 1)) == 0 {
+            return Err(ParityError::new(changed.row(), changed.column(), value, self.parity).into());
+        }
+
+        Ok(())
+    }
+
+    fn prune(&self, grid: &Grid, candidates: &mut [CandidateSet]) {
+        if grid.cell(&self.cell).is_none() {
+            candidates[self.cell.to_index(grid.shape())] &= self.values(grid.shape());
+        }
+    }
+
+    fn encode(&self, shape: &GridShape, cnf: &mut Cnf) -> bool {
+        let values = self.values(shape);
+        for value in (1 ..= shape.max_value()).filter(|value| values & (1 << (value - 1)) == 0) {
+            cnf.add_clause(vec![-cnf.variable(&self.cell, value)]);
+        }
+
+        true
+    }
+
+    fn formulas(&self, shape: &GridShape) -> Option<Vec<Formula>> {
+        let values = self.values(shape);
+        let allowed = (1 ..= shape.max_value())
+            .filter(|value| values & (1 << (value - 1)) != 0)
+            .map(|value| Formula::Equal(Term::Cell(self.cell.clone()), Term::Number(value as usize)))
+            .collect();
+
+        Some(vec![Formula::Or(allowed)])
+    }
+
+    fn cell_marks(&self, _shape: &GridShape) -> Vec<(GridReference, char)> {
+        let mark = match self.parity {
+            Parity::Odd => '○',
+            Parity::Even => '□',
+        };
+
+        vec![(self.cell.clone(), mark)]
+    }
+
+    /// Kept as long as the relabelled digits keep their parity
+    fn transformed(&self, _shape: &GridShape, transform: &Transform) -> Option<Arc<dyn Constraint>> {
+        let moved = Self { parity: self.parity, cell: transform.move_cell(&self.cell) };
+
+        transform.keeps_parity().then(|| Arc::new(moved) as Arc<dyn Constraint>)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sudoku::grid::CellValue;
+
+    #[test]
+    fn only_takes_values_of_its_parity() {
+        let mut grid = Grid::new();
+        let (odd, even) = (GridReference::from_numbers(0, 0).unwrap(), GridReference::from_numbers(0, 1).unwrap());
+        grid.add_constraint(ParityCell::odd(odd.clone())).unwrap();
+        grid.add_constraint(ParityCell::even(even.clone())).unwrap();
+
+        assert!(grid.clone().set_cell(&odd, &CellValue::new(4).unwrap()).unwrap_err().is::<ParityError>());
+        assert!(grid.clone().set_cell(&even, &CellValue::new(7).unwrap()).unwrap_err().is::<ParityError>());
+
+        let mut candidates = vec![0b1_1111_1111; 81];
+        grid.constraints().iter().for_each(|constraint| constraint.prune(&grid, &mut candidates));
+        assert_eq!((candidates[0], candidates[1]), (0b1_0101_0101, 0b0_1010_1010));
+    }
+
+    #[test]
+    fn survives_relabelling_that_keeps_parity() {
+        let shape = GridShape::standard();
+        let odd = ParityCell::odd(GridReference::from_numbers(0, 0).unwrap());
+
+        assert!(odd.transformed(&shape, &Transform::relabel(&[3, 4, 1, 2, 5, 6, 7, 8, 9]).unwrap()).is_some());
+        assert!(odd.transformed(&shape, &Transform::relabel(&[2, 1, 3, 4, 5, 6, 7, 8, 9]).unwrap()).is_none());
+    }
+}
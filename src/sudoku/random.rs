@@ -0,0 +1,49 @@
+/*
+ * A small seedable pseudo-random number generator (SplitMix64).
+ *
+ * We deliberately don't pull in an external RNG crate here: the whole point of seeding the
+ * generator is that a given seed always produces the same puzzle, and that guarantee needs to hold
+ * across dependency upgrades as well as across runs.  Owning the algorithm means the sequence for a
+ * seed can never change underneath us.
+ */
+#[derive(Debug, Clone)]
+pub struct SeededRng {
+    state: u64,
+}
+
+impl SeededRng {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a value in the range 0 .. bound.  The bound must be greater than zero.
+    pub fn below(&mut self, bound: usize) -> usize {
+        // Rejection sampling avoids the slight bias towards low values that a plain modulo gives
+        let bound = bound as u64;
+        let zone = u64::MAX - (u64::MAX % bound);
+
+        loop {
+            let value = self.next_u64();
+            if value < zone {
+                return (value % bound) as usize;
+            }
+        }
+    }
+
+    /// Fisher-Yates shuffle of the given slice
+    pub fn shuffle<T>(&mut self, items: &mut [T]) {
+        for index in (1 .. items.len()).rev() {
+            let swap_with = self.below(index + 1);
+            items.swap(index, swap_with);
+        }
+    }
+}
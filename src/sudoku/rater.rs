@@ -0,0 +1,628 @@
+use crate::sudoku::constraint::{CandidateSet, Constraint};
+use crate::sudoku::grid::{CellValue, Grid};
+use crate::sudoku::reference::GridReference;
+use crate::sudoku::shape::GridShape;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::sync::Arc;
+
+/// The logical solving techniques that the rater knows about, in the order that a human solver
+/// would normally reach for them
+#[derive(Debug, Clone, Copy, Eq, Hash, PartialEq, PartialOrd, Ord)]
+pub enum Technique {
+    NakedSingle,
+    HiddenSingle,
+    LockedCandidates,
+    NakedPair,
+    HiddenPair,
+    NakedTriple,
+    HiddenTriple,
+    XWing,
+    Swordfish,
+}
+
+#[derive(Debug, Clone, Copy, Eq, Hash, PartialEq, PartialOrd, Ord)]
+pub enum Difficulty {
+    Easy,
+    Medium,
+    Hard,
+    Expert,
+    /// The puzzle can't be finished with any of the techniques the rater knows, so some amount of
+    /// trial and error is needed
+    Extreme,
+}
+
+impl Technique {
+    pub const ALL: [Technique; 9] = [
+        Technique::NakedSingle,
+        Technique::HiddenSingle,
+        Technique::LockedCandidates,
+        Technique::NakedPair,
+        Technique::HiddenPair,
+        Technique::NakedTriple,
+        Technique::HiddenTriple,
+        Technique::XWing,
+        Technique::Swordfish,
+    ];
+
+    pub fn difficulty(&self) -> Difficulty {
+        match self {
+            Technique::NakedSingle | Technique::HiddenSingle => Difficulty::Easy,
+            Technique::LockedCandidates => Difficulty::Medium,
+            Technique::NakedPair | Technique::HiddenPair
+                | Technique::NakedTriple | Technique::HiddenTriple => Difficulty::Hard,
+            Technique::XWing | Technique::Swordfish => Difficulty::Expert,
+        }
+    }
+
+    /// How much each use of the technique adds to a puzzle's score
+    pub fn weight(&self) -> usize {
+        match self {
+            Technique::NakedSingle => 1,
+            Technique::HiddenSingle => 2,
+            Technique::LockedCandidates => 5,
+            Technique::NakedPair => 10,
+            Technique::HiddenPair => 15,
+            Technique::NakedTriple => 20,
+            Technique::HiddenTriple => 25,
+            Technique::XWing => 40,
+            Technique::Swordfish => 60,
+        }
+    }
+}
+
+impl Display for Technique {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        let name = match self {
+            Technique::NakedSingle => "naked single",
+            Technique::HiddenSingle => "hidden single",
+            Technique::LockedCandidates => "locked candidates",
+            Technique::NakedPair => "naked pair",
+            Technique::HiddenPair => "hidden pair",
+            Technique::NakedTriple => "naked triple",
+            Technique::HiddenTriple => "hidden triple",
+            Technique::XWing => "X-Wing",
+            Technique::Swordfish => "swordfish",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+impl Display for Difficulty {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        let name = match self {
+            Difficulty::Easy => "Easy",
+            Difficulty::Medium => "Medium",
+            Difficulty::Hard => "Hard",
+            Difficulty::Expert => "Expert",
+            Difficulty::Extreme => "Extreme",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Rating {
+    difficulty: Difficulty,
+    // How many times each technique was applied, indexed in the same order as Technique::ALL
+    technique_counts: [usize; Technique::ALL.len()],
+    solved_logically: bool,
+}
+
+impl Rating {
+    pub fn difficulty(&self) -> Difficulty {
+        self.difficulty
+    }
+
+    pub fn uses(&self, technique: Technique) -> bool {
+        self.technique_count(technique) > 0
+    }
+
+    pub fn technique_count(&self, technique: Technique) -> usize {
+        self.technique_counts[technique as usize]
+    }
+
+    pub fn hardest_technique(&self) -> Option<Technique> {
+        Technique::ALL.iter().rev().find(|technique| self.uses(**technique)).copied()
+    }
+
+    pub fn is_solved_logically(&self) -> bool {
+        self.solved_logically
+    }
+
+    /*
+     * A finer grained measure than the difficulty band, for comparing puzzles within the same
+     * band.  Puzzles that need trial and error get a large fixed penalty so they always score
+     * higher than anything that can be solved logically
+     */
+    pub fn score(&self) -> usize {
+        let technique_score: usize = Technique::ALL.iter()
+            .map(|technique| technique.weight() * self.technique_count(*technique))
+            .sum();
+
+        if self.solved_logically {
+            technique_score
+        } else {
+            technique_score + 1000
+        }
+    }
+}
+
+impl Display for Rating {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        match self.hardest_technique() {
+            Some(technique) => write!(f, "{} (score {}, hardest technique: {})", self.difficulty, self.score(), technique),
+            None => write!(f, "{} (score {})", self.difficulty, self.score()),
+        }
+    }
+}
+
+/// Selects puzzles by difficulty, optionally insisting that a particular technique is needed
+#[derive(Debug, Clone)]
+pub struct DifficultyBand {
+    min: Difficulty,
+    max: Difficulty,
+    required_technique: Option<Technique>,
+}
+
+impl DifficultyBand {
+    pub fn new(min: Difficulty, max: Difficulty) -> Self {
+        Self { min, max, required_technique: None }
+    }
+
+    pub fn exactly(difficulty: Difficulty) -> Self {
+        Self::new(difficulty, difficulty)
+    }
+
+    pub fn set_required_technique(&mut self, technique: Option<Technique>) -> &mut Self {
+        self.required_technique = technique;
+        self
+    }
+
+    pub fn required_technique(&self) -> Option<Technique> {
+        self.required_technique
+    }
+
+    pub fn contains(&self, rating: &Rating) -> bool {
+        rating.difficulty() >= self.min
+            && rating.difficulty() <= self.max
+            && self.required_technique.is_none_or(|technique| rating.uses(technique))
+    }
+}
+
+/*
+ * Rates a puzzle by solving it the way a person would, keeping track of the techniques needed.
+ *
+ * At every step we try the techniques in order from simplest to hardest and apply the first one
+ * that makes any progress, then start again from the simplest.  That way a hard technique only
+ * shows up in the rating if nothing easier would have worked at that point, which is what "the
+ * puzzle needs an X-Wing" actually means to a solver.
+ *
+ * The techniques work on the grid's houses, so they carry over to variants that change which
+ * groups of cells hold every value.  Any other constraints the grid has are applied each time a
+ * value is placed, without counting towards the rating
+ */
+#[derive(Debug)]
+pub struct Rater {
+    shape: GridShape,
+    grid: Grid,
+    values: Vec<Option<u8>>,
+    candidates: Vec<CandidateSet>,
+    units: Vec<Vec<usize>>,
+    // Rows followed by columns, for the fish techniques.  Left empty if the grid's rows and
+    // columns aren't houses, as fish don't work without them
+    lines: Vec<Vec<usize>>,
+    peers: Vec<Vec<usize>>,
+    other_constraints: Vec<Arc<dyn Constraint>>,
+    contradiction: bool,
+}
+
+impl Rater {
+    pub fn new(problem: &Grid) -> Self {
+        let shape = *problem.shape();
+        let cell_count = shape.cell_count();
+
+        let mut units: Vec<Vec<usize>> = Vec::new();
+        let mut other_constraints: Vec<Arc<dyn Constraint>> = Vec::new();
+        for constraint in problem.constraints() {
+            if constraint.is_house(&shape) {
+                let mut unit: Vec<usize> = constraint.affected_cells(&shape)
+                    .iter()
+                    .map(|grid_ref| grid_ref.to_index(&shape))
+                    .collect();
+                unit.sort();
+                units.push(unit);
+            } else {
+                other_constraints.push(constraint.clone());
+            }
+        }
+
+        let mut lines: Vec<Vec<usize>> = (0 .. shape.rows())
+            .map(|row| (0 .. shape.columns()).map(|column| shape.index_of(row, column)).collect())
+            .collect();
+        lines.extend((0 .. shape.columns()).map(|column| (0 .. shape.rows()).map(|row| shape.index_of(row, column)).collect()));
+        if !lines.iter().all(|line| units.contains(line)) {
+            lines.clear();
+        }
+
+        let mut peers: Vec<Vec<usize>> = vec![Vec::new(); cell_count];
+        for unit in units.iter() {
+            for cell in unit.iter() {
+                peers[*cell].extend(unit.iter().filter(|peer| *peer != cell));
+            }
+        }
+        for cell_peers in peers.iter_mut() {
+            cell_peers.sort();
+            cell_peers.dedup();
+        }
+
+        let mut rater = Self {
+            shape,
+            grid: Grid::with_constraints(shape, problem.constraints().to_vec()),
+            values: vec![None; cell_count],
+            candidates: vec![(1 << shape.size()) - 1; cell_count],
+            units,
+            lines,
+            peers,
+            other_constraints,
+            contradiction: false,
+        };
+
+        for grid_ref in shape.grid_refs() {
+            if let Some(value) = problem.cell(&grid_ref) {
+                rater.place(grid_ref.to_index(&shape), *value);
+            }
+        }
+
+        // The other constraints can rule values out before anything is placed, such as a Killer
+        // cage that only has the one cell
+        for constraint in rater.other_constraints.iter() {
+            constraint.prune(&rater.grid, &mut rater.candidates);
+        }
+
+        rater
+    }
+
+    pub fn rate(&mut self) -> Rating {
+        let mut technique_counts = [0; Technique::ALL.len()];
+
+        while !self.is_solved() && !self.is_broken() {
+            let applied = Technique::ALL.iter().find(|technique| self.apply(**technique));
+
+            match applied {
+                Some(technique) => technique_counts[*technique as usize] += 1,
+                None => break,
+            }
+        }
+
+        let solved_logically = self.is_solved() && !self.contradiction;
+        let difficulty = if solved_logically {
+            Technique::ALL.iter()
+                .filter(|technique| technique_counts[**technique as usize] > 0)
+                .map(|technique| technique.difficulty())
+                .max()
+                .unwrap_or(Difficulty::Easy)
+        } else {
+            Difficulty::Extreme
+        };
+
+        Rating { difficulty, technique_counts, solved_logically }
+    }
+
+    fn is_solved(&self) -> bool {
+        self.values.iter().all(|value| value.is_some())
+    }
+
+    /// An empty cell with nothing left that it could be means the puzzle has no solution, as does
+    /// a placed value that breaks one of the grid's constraints
+    fn is_broken(&self) -> bool {
+        self.contradiction || self.values.iter()
+            .zip(self.candidates.iter())
+            .any(|(value, candidates)| value.is_none() && *candidates == 0)
+    }
+
+    fn place(&mut self, cell: usize, value: u8) {
+        let bit = Self::bit(value);
+
+        self.values[cell] = Some(value);
+        self.candidates[cell] = 0;
+        for peer in self.peers[cell].iter() {
+            self.candidates[*peer] &= !bit;
+        }
+
+        let grid_ref = GridReference::for_shape(&self.shape, cell / self.shape.columns(), cell % self.shape.columns())
+            .expect("Cell index is always within the grid");
+        let placed = CellValue::new(value)
+            .map_err(|err| err.into())
+            .and_then(|value| self.grid.set_cell(&grid_ref, &value).map(|_| ()));
+        if placed.is_err() {
+            self.contradiction = true;
+            return;
+        }
+
+        for constraint in self.other_constraints.iter() {
+            constraint.prune(&self.grid, &mut self.candidates);
+        }
+    }
+
+    /// Removes the candidates in the mask from the given cells, reporting whether anything changed
+    fn eliminate(&mut self, cells: &[usize], mask: CandidateSet) -> bool {
+        let mut changed = false;
+
+        for cell in cells {
+            if self.candidates[*cell] & mask != 0 {
+                self.candidates[*cell] &= !mask;
+                changed = true;
+            }
+        }
+
+        changed
+    }
+
+    fn apply(&mut self, technique: Technique) -> bool {
+        match technique {
+            Technique::NakedSingle => self.naked_single(),
+            Technique::HiddenSingle => self.hidden_single(),
+            Technique::LockedCandidates => self.locked_candidates(),
+            Technique::NakedPair => self.naked_subset(2),
+            Technique::HiddenPair => self.hidden_subset(2),
+            Technique::NakedTriple => self.naked_subset(3),
+            Technique::HiddenTriple => self.hidden_subset(3),
+            Technique::XWing => self.fish(2),
+            Technique::Swordfish => self.fish(3),
+        }
+    }
+
+    /// A cell with only one candidate left must take that value
+    fn naked_single(&mut self) -> bool {
+        let single = (0 .. self.values.len())
+            .find(|cell| self.values[*cell].is_none() && self.candidates[*cell].count_ones() == 1);
+
+        match single {
+            Some(cell) => {
+                self.place(cell, Self::value_of(self.candidates[cell]));
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// A value that only has one possible place in a unit must go there
+    fn hidden_single(&mut self) -> bool {
+        for unit_id in 0 .. self.units.len() {
+            for value in 1 ..= self.shape.max_value() {
+                let places = self.places_in(&self.units[unit_id], value);
+                if places.len() == 1 {
+                    self.place(places[0], value);
+                    return true;
+                }
+            }
+        }
+
+        false
+    }
+
+    /*
+     * If a value's only places within one house all lie in another house as well, then it can't go
+     * anywhere else in that other house.  With the standard houses that covers both pointing (a
+     * subgrid's places all in one row or column) and claiming (a row or column's places all in
+     * one subgrid)
+     */
+    fn locked_candidates(&mut self) -> bool {
+        for unit_id in 0 .. self.units.len() {
+            for value in 1 ..= self.shape.max_value() {
+                let places = self.places_in(&self.units[unit_id], value);
+                if places.len() < 2 {
+                    continue;
+                }
+
+                let containing_units: Vec<usize> = (0 .. self.units.len())
+                    .filter(|other_unit| *other_unit != unit_id && places.iter().all(|cell| self.units[*other_unit].contains(cell)))
+                    .collect();
+
+                for other_unit in containing_units {
+                    let targets: Vec<usize> = self.units[other_unit].iter()
+                        .filter(|cell| !self.units[unit_id].contains(cell))
+                        .copied()
+                        .collect();
+                    if self.eliminate(&targets, Self::bit(value)) {
+                        return true;
+                    }
+                }
+            }
+        }
+
+        false
+    }
+
+    /// If n cells in a unit only have n candidates between them, those values can't go anywhere
+    /// else in the unit
+    fn naked_subset(&mut self, size: usize) -> bool {
+        for unit_id in 0 .. self.units.len() {
+            let open_cells: Vec<usize> = self.units[unit_id].iter()
+                .filter(|cell| self.values[**cell].is_none())
+                .copied()
+                .collect();
+            let small_cells: Vec<usize> = open_cells.iter()
+                .filter(|cell| (2 ..= size as u32).contains(&self.candidates[**cell].count_ones()))
+                .copied()
+                .collect();
+
+            for subset in Self::combinations(&small_cells, size) {
+                let mask = subset.iter().fold(0, |mask, cell| mask | self.candidates[*cell]);
+                if mask.count_ones() as usize != size {
+                    continue;
+                }
+
+                let targets: Vec<usize> = open_cells.iter()
+                    .filter(|cell| !subset.contains(cell))
+                    .copied()
+                    .collect();
+                if self.eliminate(&targets, mask) {
+                    return true;
+                }
+            }
+        }
+
+        false
+    }
+
+    /// If n values in a unit only have n places between them, those cells can't hold anything
+    /// else
+    fn hidden_subset(&mut self, size: usize) -> bool {
+        for unit_id in 0 .. self.units.len() {
+            let values: Vec<usize> = (1 ..= self.shape.size())
+                .filter(|value| (2 ..= size).contains(&self.places_in(&self.units[unit_id], *value as u8).len()))
+                .collect();
+
+            for subset in Self::combinations(&values, size) {
+                let mut places: Vec<usize> = subset.iter()
+                    .flat_map(|value| self.places_in(&self.units[unit_id], *value as u8))
+                    .collect();
+                places.sort();
+                places.dedup();
+                if places.len() != size {
+                    continue;
+                }
+
+                let keep = subset.iter().fold(0, |mask, value| mask | Self::bit(*value as u8));
+                if self.eliminate(&places, !keep) {
+                    return true;
+                }
+            }
+        }
+
+        false
+    }
+
+    /*
+     * X-Wing (size 2) and Swordfish (size 3).  If a value's places in n rows all lie within the same
+     * n columns, then the value must occupy those columns in those rows, so it can be removed from
+     * the rest of each column.  The same applies with rows and columns swapped
+     */
+    fn fish(&mut self, size: usize) -> bool {
+        if self.lines.is_empty() {
+            return false;
+        }
+
+        for value in 1 ..= self.shape.max_value() {
+            for by_rows in [true, false] {
+                let width = self.shape.columns();
+                let line_of = |cell: usize| if by_rows { cell / width } else { cell % width };
+                let cross_of = |cell: usize| if by_rows { cell % width } else { cell / width };
+                let first_unit = if by_rows { 0 } else { self.shape.rows() };
+                let cover_first_unit = if by_rows { self.shape.rows() } else { 0 };
+
+                let base_lines: Vec<usize> = (0 .. self.shape.rows())
+                    .filter(|line| (2 ..= size).contains(&self.places_in(&self.lines[first_unit + line], value).len()))
+                    .collect();
+
+                for subset in Self::combinations(&base_lines, size) {
+                    let mut covers: Vec<usize> = subset.iter()
+                        .flat_map(|line| self.places_in(&self.lines[first_unit + line], value))
+                        .map(cross_of)
+                        .collect();
+                    covers.sort();
+                    covers.dedup();
+                    if covers.len() != size {
+                        continue;
+                    }
+
+                    let targets: Vec<usize> = covers.iter()
+                        .flat_map(|cover| self.lines[cover_first_unit + cover].iter())
+                        .filter(|cell| !subset.contains(&line_of(**cell)))
+                        .copied()
+                        .collect();
+                    if self.eliminate(&targets, Self::bit(value)) {
+                        return true;
+                    }
+                }
+            }
+        }
+
+        false
+    }
+
+    /// The open cells among the given ones that could hold the given value
+    fn places_in(&self, cells: &[usize], value: u8) -> Vec<usize> {
+        let bit = Self::bit(value);
+
+        cells.iter()
+            .filter(|cell| self.candidates[**cell] & bit != 0)
+            .copied()
+            .collect()
+    }
+
+    fn combinations(items: &[usize], size: usize) -> Vec<Vec<usize>> {
+        if size == 0 {
+            return vec![Vec::new()];
+        }
+
+        let mut combinations = Vec::new();
+        for (index, item) in items.iter().enumerate() {
+            for mut rest in Self::combinations(&items[index + 1 ..], size - 1) {
+                rest.insert(0, *item);
+                combinations.push(rest);
+            }
+        }
+
+        combinations
+    }
+
+    fn bit(value: u8) -> CandidateSet {
+        1 << (value - 1)
+    }
+
+    fn value_of(candidates: CandidateSet) -> u8 {
+        candidates.trailing_zeros() as u8 + 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rate(puzzle: &str) -> Rating {
+        Rater::new(&puzzle.parse().unwrap()).rate()
+    }
+
+    #[test]
+    fn known_puzzles_rate_into_known_bands() {
+        let puzzles = [
+            ("53..7....6..195....98....6.8...6...34..8.3..17...2...6.6....28....419..5....8..79", Difficulty::Easy),
+            ("3.1.76..........5.....31...81..47......9.5..6.2.....9...4.8...9.......3.9.6...8.4", Difficulty::Medium),
+            ("4..92.5...78......5.......38.15..94..24.....89.....6.......42.1...6.9.....5......", Difficulty::Hard),
+            ("..52..8.7.7......62.6.9.......6.4.............37..9..2.2.1....8....6..1.....35.4.", Difficulty::Expert),
+            ("8..........36......7..9.2...5...7.......457.....1...3...1....68..85...1..9....4..", Difficulty::Extreme),
+        ];
+
+        for (puzzle, difficulty) in puzzles {
+            let rating = rate(puzzle);
+            assert_eq!(rating.difficulty(), difficulty, "{}", puzzle);
+            assert_eq!(rating.is_solved_logically(), difficulty != Difficulty::Extreme, "{}", puzzle);
+            assert!(DifficultyBand::exactly(difficulty).contains(&rating));
+        }
+    }
+
+    #[test]
+    fn the_hardest_technique_sets_the_difficulty() {
+        let rating = rate("..52..8.7.7......62.6.9.......6.4.............37..9..2.2.1....8....6..1.....35.4.");
+
+        assert_eq!(rating.hardest_technique(), Some(Technique::XWing));
+        assert!(rating.uses(Technique::NakedSingle));
+        assert!(rating.technique_count(Technique::XWing) > 0);
+    }
+
+    #[test]
+    fn bands_can_require_a_technique() {
+        let rating = rate("4..92.5...78......5.......38.15..94..24.....89.....6.......42.1...6.9.....5......");
+        let mut band = DifficultyBand::new(Difficulty::Medium, Difficulty::Expert);
+        assert!(band.contains(&rating));
+
+        band.set_required_technique(Some(Technique::NakedPair));
+        assert!(band.contains(&rating));
+        band.set_required_technique(Some(Technique::Swordfish));
+        assert!(!band.contains(&rating));
+    }
+}
@@ -1,43 +1,58 @@
-use crate::sudoku::error::{InvalidColumn, InvalidRow, InvalidSubGrid};
-use crate::sudoku::grid::Grid;
+use crate::sudoku::error::{InvalidCellName, InvalidColumn, InvalidRegion, InvalidRow, InvalidSubGrid};
+use crate::sudoku::region_map::RegionMap;
+use crate::sudoku::shape::GridShape;
 use std::error::Error;
+use std::str::FromStr;
 
 trait RowRefValidator {
-    fn validate_row_id(row_id: usize) -> Result<usize, InvalidRow> {
+    fn validate_row_id(shape: &GridShape, row_id: usize) -> Result<usize, InvalidRow> {
         match row_id {
-            0..Grid::GRID_COLUMNS => Ok(row_id),
+            id if id < shape.rows() => Ok(row_id),
             _ => Err(InvalidRow::new(row_id)),
         }
     }
 }
 
 trait ColumnRefValidator {
-    fn validate_column_id(column_id: usize) -> Result<usize, InvalidColumn> {
+    fn validate_column_id(shape: &GridShape, column_id: usize) -> Result<usize, InvalidColumn> {
         match column_id {
-            0..Grid::GRID_ROWS => Ok(column_id),
+            id if id < shape.columns() => Ok(column_id),
             _ => Err(InvalidColumn::new(column_id)),
         }
     }
 }
 
 trait SubgridRefValidator {
-
-    fn validate_subgrid_id(subgrid_id: usize) -> Result<usize, InvalidSubGrid> {
+    fn validate_subgrid_id(shape: &GridShape, subgrid_id: usize) -> Result<usize, InvalidSubGrid> {
         match subgrid_id {
-            0..SubgridReference::SUBGRID_ID_LIMIT => Ok(subgrid_id),
+            id if id < shape.subgrid_count() => Ok(subgrid_id),
             _ => Err(InvalidSubGrid::new(subgrid_id)),
         }
     }
 }
 
+trait RegionRefValidator {
+    fn validate_region_id(regions: &RegionMap, region_id: usize) -> Result<usize, InvalidRegion> {
+        match region_id {
+            id if id < regions.region_count() => Ok(region_id),
+            _ => Err(InvalidRegion::new(region_id)),
+        }
+    }
+}
+
 #[derive(Debug, Eq, Hash, PartialEq, Clone)]
 pub struct RowReference {
     row: usize,
 }
 
 impl RowReference {
+    /// A row of the standard 9 x 9 grid
     pub fn new(row: usize) -> Result<Self, InvalidRow> {
-        let row = Self::validate_row_id(row)?;
+        Self::for_shape(&GridShape::standard(), row)
+    }
+
+    pub fn for_shape(shape: &GridShape, row: usize) -> Result<Self, InvalidRow> {
+        let row = Self::validate_row_id(shape, row)?;
         Ok(RowReference { row })
     }
 
@@ -54,8 +69,13 @@ pub struct ColumnReference {
 }
 
 impl ColumnReference {
+    /// A column of the standard 9 x 9 grid
     pub fn new(column: usize) -> Result<Self, InvalidColumn> {
-        let column = Self::validate_column_id(column)?;
+        Self::for_shape(&GridShape::standard(), column)
+    }
+
+    pub fn for_shape(shape: &GridShape, column: usize) -> Result<Self, InvalidColumn> {
+        let column = Self::validate_column_id(shape, column)?;
         Ok(ColumnReference { column })
     }
 
@@ -77,10 +97,15 @@ impl GridReference {
         GridReference { row_ref: row, column_ref: column }
     }
 
+    /// A cell of the standard 9 x 9 grid
     pub fn from_numbers(row_id: usize, column_id: usize) -> Result<GridReference, Box<dyn Error>> {
+        Self::for_shape(&GridShape::standard(), row_id, column_id)
+    }
+
+    pub fn for_shape(shape: &GridShape, row_id: usize, column_id: usize) -> Result<GridReference, Box<dyn Error>> {
         Ok(Self::new(
-            RowReference::new(row_id)?,
-            ColumnReference::new(column_id)?,
+            RowReference::for_shape(shape, row_id)?,
+            ColumnReference::for_shape(shape, column_id)?,
         ))
     }
 
@@ -92,8 +117,42 @@ impl GridReference {
         &self.column_ref
     }
 
-    pub fn to_index(&self) -> usize {
-        self.row_ref.row * Grid::GRID_COLUMNS + self.column_ref.column
+    pub fn row(&self) -> usize {
+        self.row_ref.row
+    }
+
+    pub fn column(&self) -> usize {
+        self.column_ref.column
+    }
+
+    pub fn to_index(&self, shape: &GridShape) -> usize {
+        shape.index_of(self.row_ref.row, self.column_ref.column)
+    }
+
+    /// Whether the two cells are next to each other in a row or column
+    pub fn shares_side_with(&self, other: &GridReference) -> bool {
+        self.row().abs_diff(other.row()) + self.column().abs_diff(other.column()) == 1
+    }
+}
+
+/*
+ * Reads a cell written the way puzzle setters usually do, as r then the row and c then the column,
+ * counting from 1 (so "r1c1" is the top left cell).  There's no grid to check the cell against, so
+ * it only has to fit the largest grid we support, and grids check that cells are in range when
+ * they're given constraints
+ */
+impl FromStr for GridReference {
+    type Err = Box<dyn Error>;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        let lower = name.trim().to_ascii_lowercase();
+        let (row, column) = lower.strip_prefix('r')
+            .and_then(|rest| rest.split_once('c'))
+            .and_then(|(row, column)| Some((row.parse::<usize>().ok()?, column.parse::<usize>().ok()?)))
+            .filter(|(row, column)| *row > 0 && *column > 0)
+            .ok_or_else(|| InvalidCellName::new(name))?;
+
+        Self::for_shape(&GridShape::largest(), row - 1, column - 1)
     }
 }
 
@@ -103,20 +162,19 @@ pub struct SubgridReference {
 }
 
 impl SubgridReference {
-    const SUBGRID_ID_LIMIT: usize = (Grid::GRID_ROWS * Grid::GRID_COLUMNS)
-        / (Grid::SUBGRID_ROWS * Grid::SUBGRID_COLUMNS);
-
+    /// A subgrid of the standard 9 x 9 grid
     pub fn new(subgrid: usize) -> Result<Self, InvalidSubGrid> {
-        let subgrid = Self::validate_subgrid_id(subgrid)?;
-        Ok(SubgridReference { subgrid })
+        Self::for_shape(&GridShape::standard(), subgrid)
     }
 
-    pub fn from_grid_ref(grid_ref: &GridReference) -> SubgridReference {
-        let subgrid = ((grid_ref.row_ref().row() / Grid::SUBGRID_COLUMNS) * Grid::SUBGRID_COLUMNS)
-            + (grid_ref.column_ref().column() / Grid::SUBGRID_ROWS);
+    pub fn for_shape(shape: &GridShape, subgrid: usize) -> Result<Self, InvalidSubGrid> {
+        let subgrid = Self::validate_subgrid_id(shape, subgrid)?;
+        Ok(SubgridReference { subgrid })
+    }
 
-        // As it's impossible to pass an invalid grid reference there's no need for further validation
-        Self { subgrid }
+    pub fn from_grid_ref(shape: &GridShape, grid_ref: &GridReference) -> SubgridReference {
+        // As long as the grid reference is valid for the shape there's no need for further validation
+        Self { subgrid: shape.subgrid_of(grid_ref.row(), grid_ref.column()) }
     }
 
     pub fn subgrid(&self) -> usize {
@@ -125,3 +183,26 @@ impl SubgridReference {
 }
 
 impl SubgridRefValidator for SubgridReference {}
+
+/// A region of a grid: one of the subgrids in standard Sudoku, or an irregular region in jigsaw
+#[derive(Debug, Eq, Hash, PartialEq, Clone)]
+pub struct RegionReference {
+    region: usize,
+}
+
+impl RegionReference {
+    pub fn new(regions: &RegionMap, region: usize) -> Result<Self, InvalidRegion> {
+        let region = Self::validate_region_id(regions, region)?;
+        Ok(RegionReference { region })
+    }
+
+    pub fn from_grid_ref(regions: &RegionMap, grid_ref: &GridReference) -> RegionReference {
+        Self { region: regions.region_of(grid_ref) }
+    }
+
+    pub fn region(&self) -> usize {
+        self.region
+    }
+}
+
+impl RegionRefValidator for RegionReference {}
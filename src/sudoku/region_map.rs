@@ -0,0 +1,179 @@
+use crate::sudoku::error::{DisconnectedRegion, InvalidRegion, InvalidRegionMapLength, InvalidRegionSize};
+use crate::sudoku::reference::GridReference;
+use crate::sudoku::shape::GridShape;
+use std::error::Error;
+use std::str::FromStr;
+
+/*
+ * Says which region each cell of the grid belongs to.  In standard Sudoku the regions are the
+ * subgrids, while jigsaw Sudoku replaces them with irregular regions.  Either way there are as many
+ * regions as there are values, each region holds that many cells, and every cell of a region can be
+ * reached from the others without leaving it
+ */
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct RegionMap {
+    shape: GridShape,
+    // The region of each cell, in row order
+    regions: Vec<usize>,
+}
+
+impl RegionMap {
+    /// Region ids are given for each cell in row order, and must be below the size of the grid
+    pub fn new(shape: GridShape, regions: Vec<usize>) -> Result<Self, Box<dyn Error>> {
+        if regions.len() != shape.cell_count() {
+            return Err(InvalidRegionMapLength::new(regions.len()).into());
+        }
+
+        let mut sizes = vec![0; shape.size()];
+        for region in regions.iter() {
+            match sizes.get_mut(*region) {
+                Some(size) => *size += 1,
+                None => return Err(InvalidRegion::new(*region).into()),
+            }
+        }
+
+        if let Some((region, size)) = sizes.iter().enumerate().find(|(_, size)| **size != shape.size()) {
+            return Err(InvalidRegionSize::new(region, *size, shape.size()).into());
+        }
+
+        let map = Self { shape, regions };
+        if let Some(region) = (0 .. shape.size()).find(|region| !map.is_connected(*region)) {
+            return Err(DisconnectedRegion::new(region).into());
+        }
+
+        Ok(map)
+    }
+
+    /// The subgrids of the given shape, numbered the same way as SubgridReference
+    pub fn standard(shape: GridShape) -> Self {
+        let regions = shape.grid_refs()
+            .map(|grid_ref| shape.subgrid_of(grid_ref.row(), grid_ref.column()))
+            .collect();
+
+        Self { shape, regions }
+    }
+
+    pub fn shape(&self) -> &GridShape {
+        &self.shape
+    }
+
+    pub fn region_count(&self) -> usize {
+        self.shape.size()
+    }
+
+    /// Whether the regions are just the subgrids
+    pub fn is_standard(&self) -> bool {
+        *self == Self::standard(self.shape)
+    }
+
+    pub fn region_of(&self, grid_ref: &GridReference) -> usize {
+        self.regions[grid_ref.to_index(&self.shape)]
+    }
+
+    /// The cells of the given region, in row order
+    pub fn cells_of(&self, region: usize) -> Vec<GridReference> {
+        self.shape.grid_refs()
+            .filter(|grid_ref| self.region_of(grid_ref) == region)
+            .collect()
+    }
+
+    /// Whether the two cells lie in the same region.  Used for drawing the borders between regions
+    pub(crate) fn same_region(&self, first: (usize, usize), second: (usize, usize)) -> bool {
+        self.regions[self.shape.index_of(first.0, first.1)] == self.regions[self.shape.index_of(second.0, second.1)]
+    }
+
+    fn is_connected(&self, region: usize) -> bool {
+        let columns = self.shape.columns();
+        let Some(start) = self.regions.iter().position(|cell| *cell == region) else {
+            return false;
+        };
+
+        let mut reached = vec![false; self.regions.len()];
+        let mut pending = vec![start];
+        reached[start] = true;
+        let mut reached_count = 1;
+
+        while let Some(index) = pending.pop() {
+            let (row, column) = (index / columns, index % columns);
+            let neighbours = [
+                (row > 0).then(|| index - columns),
+                (row < self.shape.rows() - 1).then(|| index + columns),
+                (column > 0).then(|| index - 1),
+                (column < columns - 1).then(|| index + 1),
+            ];
+
+            for neighbour in neighbours.into_iter().flatten() {
+                if !reached[neighbour] && self.regions[neighbour] == region {
+                    reached[neighbour] = true;
+                    reached_count += 1;
+                    pending.push(neighbour);
+                }
+            }
+        }
+
+        reached_count == self.shape.size()
+    }
+}
+
+/*
+ * Reads a region map with one character per cell, in row order.  Any characters can be used to
+ * label the regions as long as each region uses its own, so a jigsaw can be written out as a block
+ * of digits or letters.  Whitespace is ignored, and regions are numbered in the order they first
+ * appear
+ */
+impl FromStr for RegionMap {
+    type Err = Box<dyn Error>;
+
+    fn from_str(map: &str) -> Result<Self, Self::Err> {
+        let cells: Vec<char> = map.chars().filter(|character| !character.is_whitespace()).collect();
+        let shape = GridShape::from_cell_count(cells.len())
+            .ok_or_else(|| InvalidRegionMapLength::new(cells.len()))?;
+
+        let mut labels: Vec<char> = Vec::with_capacity(shape.size());
+        let regions = cells.into_iter()
+            .map(|label| match labels.iter().position(|known| *known == label) {
+                Some(region) => region,
+                None => {
+                    labels.push(label);
+                    labels.len() - 1
+                },
+            })
+            .collect();
+
+        Self::new(shape, regions)
+    }
+}
+
+impl Default for RegionMap {
+    fn default() -> Self {
+        Self::standard(GridShape::standard())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shape() -> GridShape {
+        GridShape::new(2, 2).unwrap()
+    }
+
+    #[test]
+    fn accepts_irregular_regions() {
+        let map = RegionMap::new(shape(), vec![0, 0, 0, 1, 0, 2, 1, 1, 2, 2, 3, 1, 2, 3, 3, 3]).unwrap();
+
+        assert!(!map.is_standard());
+        assert_eq!(map.cells_of(2).len(), 4);
+        assert!(RegionMap::new(shape(), vec![0, 0, 1, 1, 0, 0, 1, 1, 2, 2, 3, 3, 2, 2, 3, 3]).unwrap().is_standard());
+    }
+
+    #[test]
+    fn rejects_malformed_regions() {
+        let error = |regions: Vec<usize>| RegionMap::new(shape(), regions).unwrap_err();
+
+        assert!(error(vec![0; 15]).is::<InvalidRegionMapLength>());
+        assert!(error(vec![0, 0, 1, 1, 0, 0, 1, 1, 2, 2, 3, 3, 2, 2, 3, 4]).is::<InvalidRegion>());
+        assert!(error(vec![0, 0, 1, 1, 0, 0, 1, 1, 2, 2, 3, 3, 2, 2, 3, 2]).is::<InvalidRegionSize>());
+        assert!(error(vec![0, 0, 1, 1, 0, 1, 0, 1, 2, 2, 3, 3, 2, 2, 3, 3]).is::<DisconnectedRegion>());
+    }
+}
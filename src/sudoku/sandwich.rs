@@ -0,0 +1,303 @@
+use crate::sudoku::constraint::{cell_options, narrow_sum, prune_cells, CandidateSet, Constraint};
+use crate::sudoku::error::{ClueConstraint, ClueError, InvalidClue};
+use crate::sudoku::formula::{Formula, Term};
+use crate::sudoku::grid::Grid;
+use crate::sudoku::reference::{ColumnReference, GridReference, RowReference};
+use crate::sudoku::sat::{Cnf, Literal};
+use crate::sudoku::shape::GridShape;
+use crate::sudoku::transform::Transform;
+use std::error::Error;
+use std::str::FromStr;
+use std::sync::Arc;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum SandwichLine {
+    Row(usize),
+    Column(usize),
+}
+
+/*
+ * A sandwich clue, given outside a row or column: the values between the lowest and highest values
+ * in the line (1 and 9 in the standard grid) must add up to the total.  A total of 0 means the two
+ * sit right next to each other
+ */
+#[derive(Debug, Clone)]
+pub struct Sandwich {
+    line: SandwichLine,
+    total: usize,
+}
+
+impl Sandwich {
+    pub fn row(row: usize, total: usize) -> Result<Self, Box<dyn Error>> {
+        RowReference::for_shape(&GridShape::largest(), row)?;
+
+        Ok(Self { line: SandwichLine::Row(row), total })
+    }
+
+    pub fn column(column: usize, total: usize) -> Result<Self, Box<dyn Error>> {
+        ColumnReference::for_shape(&GridShape::largest(), column)?;
+
+        Ok(Self { line: SandwichLine::Column(column), total })
+    }
+
+    pub fn line(&self) -> SandwichLine {
+        self.line
+    }
+
+    pub fn total(&self) -> usize {
+        self.total
+    }
+
+    /// The cells of the line, in order.  These are only made out for the largest grid, so it's up
+    /// to the grid to check they fit when the clue is added
+    fn cells(&self, shape: &GridShape) -> Vec<GridReference> {
+        let largest = GridShape::largest();
+
+        (0 .. shape.size())
+            .map(|position| match self.line {
+                SandwichLine::Row(row) => GridReference::for_shape(&largest, row, position),
+                SandwichLine::Column(column) => GridReference::for_shape(&largest, position, column),
+            })
+            .map(|grid_ref| grid_ref.expect("Lines are checked against the largest grid when they're made"))
+            .collect()
+    }
+
+    /*
+     * Narrows the options for each cell of the line down to the ones that fit some placing of the
+     * lowest and highest values (the crusts) with a filling that can add up to the total, or
+     * returns None if there's no such placing.  Cells outside the crusts can hold anything but the
+     * crust values
+     */
+    fn narrow(&self, shape: &GridShape, options: &[CandidateSet]) -> Option<Vec<CandidateSet>> {
+        let lowest_crust: CandidateSet = 1;
+        let highest_crust: CandidateSet = 1 << (shape.size() - 1);
+        let crusts = lowest_crust | highest_crust;
+        let total = self.total as i32;
+
+        let mut narrowed = vec![0; options.len()];
+        let mut placed = false;
+
+        for start in 0 .. options.len() {
+            for end in start + 1 .. options.len() {
+                let outside_fits = (0 .. start).chain(end + 1 .. options.len())
+                    .all(|position| options[position] & !crusts != 0);
+                if !outside_fits {
+                    continue;
+                }
+
+                let filling: Vec<CandidateSet> = options[start + 1 .. end].iter().map(|option| option & !crusts).collect();
+                let Some(filling) = narrow_sum(shape, &filling, total, total) else {
+                    continue;
+                };
+
+                for (first, second) in [(lowest_crust, highest_crust), (highest_crust, lowest_crust)] {
+                    if options[start] & first == 0 || options[end] & second == 0 {
+                        continue;
+                    }
+
+                    placed = true;
+                    narrowed[start] |= first;
+                    narrowed[end] |= second;
+                    for (offset, option) in filling.iter().enumerate() {
+                        narrowed[start + 1 + offset] |= option;
+                    }
+                    for position in (0 .. start).chain(end + 1 .. options.len()) {
+                        narrowed[position] |= options[position] & !crusts;
+                    }
+                }
+            }
+        }
+
+        (placed && !narrowed.contains(&0)).then_some(narrowed)
+    }
+}
+
+impl Constraint for Sandwich {
+    fn affected_cells(&self, shape: &GridShape) -> Vec<GridReference> {
+        self.cells(shape)
+    }
+
+    fn check(&self, grid: &Grid, changed: &GridReference) -> Result<(), Box<dyn Error>> {
+        let Some(value) = *grid.cell(changed) else {
+            return Ok(());
+        };
+
+        let shape = grid.shape();
+        match self.narrow(shape, &cell_options(grid, &self.cells(shape), None)) {
+            Some(_) => Ok(()),
+            None => Err(ClueError::new(changed.row(), changed.column(), value, ClueConstraint::Sandwich).into()),
+        }
+    }
+
+    fn prune(&self, grid: &Grid, candidates: &mut [CandidateSet]) {
+        let cells = self.cells(grid.shape());
+        let narrowed = self.narrow(grid.shape(), &cell_options(grid, &cells, Some(candidates)));
+        prune_cells(grid, &cells, narrowed, candidates);
+    }
+
+    /*
+     * The lowest and highest values each appear once in the line, and for every pair of cells
+     * that could hold them, the running totals from the first of the pair have to reach the total
+     * by the cell before the second
+     */
+    fn encode(&self, shape: &GridShape, cnf: &mut Cnf) -> bool {
+        let cells = self.cells(shape);
+        let crusts = [1, shape.max_value()];
+
+        for crust in crusts {
+            let holding: Vec<Literal> = cells.iter().map(|grid_ref| cnf.variable(grid_ref, crust)).collect();
+            cnf.exactly_one(&holding);
+        }
+
+        for start in 0 .. cells.len() {
+            let totals = cnf.running_totals(&cells[start + 1 ..]);
+
+            for end in start + 1 .. cells.len() {
+                let wrong_totals = totals[end - start - 1].iter()
+                    .enumerate()
+                    .filter(|(total, _)| *total != self.total)
+                    .filter_map(|(_, variable)| *variable);
+
+                for variable in wrong_totals {
+                    for (first, second) in [(crusts[0], crusts[1]), (crusts[1], crusts[0])] {
+                        cnf.add_clause(vec![-cnf.variable(&cells[start], first), -cnf.variable(&cells[end], second), -variable]);
+                    }
+                }
+            }
+        }
+
+        true
+    }
+
+    /// Like the encoding, each crust appears once in the line, and wherever the two of them land
+    /// the cells between add up to the total
+    fn formulas(&self, shape: &GridShape) -> Option<Vec<Formula>> {
+        let cells = self.cells(shape);
+        let crusts = [1, shape.max_value() as usize];
+        let holds = |position: usize, crust: usize| Formula::Equal(Term::Cell(cells[position].clone()), Term::Number(crust));
+        let mut formulas = Vec::new();
+
+        for crust in crusts {
+            formulas.push(Formula::Or((0 .. cells.len()).map(|position| holds(position, crust)).collect()));
+        }
+
+        for start in 0 .. cells.len() {
+            for end in start + 1 .. cells.len() {
+                for crust in crusts {
+                    formulas.push(Formula::Or(vec![
+                        Formula::NotEqual(Term::Cell(cells[start].clone()), Term::Number(crust)),
+                        Formula::NotEqual(Term::Cell(cells[end].clone()), Term::Number(crust)),
+                    ]));
+                }
+
+                let crusted = Formula::Or(vec![
+                    Formula::And(vec![holds(start, crusts[0]), holds(end, crusts[1])]),
+                    Formula::And(vec![holds(start, crusts[1]), holds(end, crusts[0])]),
+                ]);
+                let filling = Formula::Equal(Term::sum_of(&cells[start + 1 .. end]), Term::Number(self.total));
+                formulas.push(Formula::Implies(Box::new(crusted), Box::new(filling)));
+            }
+        }
+
+        Some(formulas)
+    }
+
+    fn outside_clue(&self, _shape: &GridShape) -> Option<(isize, isize, String)> {
+        let (row, column) = match self.line {
+            SandwichLine::Row(row) => (row as isize, -1),
+            SandwichLine::Column(column) => (-1, column as isize),
+        };
+
+        Some((row, column, self.total.to_string()))
+    }
+
+    /// The line can end up running the other way, which makes no difference to what's between the
+    /// lowest and highest values, but a row may now be a column
+    fn transformed(&self, shape: &GridShape, transform: &Transform) -> Option<Arc<dyn Constraint>> {
+        if !transform.keeps_digits() {
+            return None;
+        }
+
+        let cells = transform.move_cells(&self.cells(shape));
+        let sandwich = if cells.iter().all(|grid_ref| grid_ref.row() == cells[0].row()) {
+            Self::row(cells[0].row(), self.total)
+        } else if cells.iter().all(|grid_ref| grid_ref.column() == cells[0].column()) {
+            Self::column(cells[0].column(), self.total)
+        } else {
+            return None;
+        };
+
+        Some(Arc::new(sandwich.ok()?))
+    }
+}
+
+/// Sandwich clues are written as r for a row or c for a column, its number counting from 1, then
+/// an equals sign and the total, such as "r3=15" or "c5 = 0"
+impl FromStr for Sandwich {
+    type Err = Box<dyn Error>;
+
+    fn from_str(clue: &str) -> Result<Self, Self::Err> {
+        let invalid = || InvalidClue::new(clue, "r or c, the row or column counting from 1, an equals sign and the total, such as r3=15");
+
+        let (line, total) = clue.split_once('=').ok_or_else(invalid)?;
+        let total: usize = total.trim().parse().map_err(|_| invalid())?;
+        let line = line.trim().to_ascii_lowercase();
+        let number = |digits: &str| digits.parse::<usize>().ok().filter(|number| *number > 0);
+
+        match (line.strip_prefix('r'), line.strip_prefix('c')) {
+            (Some(row), _) => Self::row(number(row).ok_or_else(invalid)? - 1, total),
+            (_, Some(column)) => Self::column(number(column).ok_or_else(invalid)? - 1, total),
+            _ => Err(invalid().into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sudoku::grid::CellValue;
+
+    fn set(grid: &mut Grid, row: usize, column: usize, value: u8) -> Result<(), Box<dyn Error>> {
+        grid.set_cell(&GridReference::from_numbers(row, column).unwrap(), &CellValue::new(value).unwrap()).map(|_| ())
+    }
+
+    #[test]
+    fn a_total_of_zero_puts_the_crusts_side_by_side() {
+        let mut grid = Grid::new();
+        grid.add_constraint("r1=0".parse::<Sandwich>().unwrap()).unwrap();
+        set(&mut grid, 0, 4, 1).unwrap();
+
+        assert!(set(&mut grid.clone(), 0, 6, 9).unwrap_err().is::<ClueError>());
+
+        let mut candidates = vec![0b1_1111_1111; 81];
+        grid.constraints().iter().for_each(|constraint| constraint.prune(&grid, &mut candidates));
+        let nines: Vec<usize> = (0 .. 9)
+            .filter(|column| grid.cell(&GridReference::from_numbers(0, *column).unwrap()).is_none())
+            .filter(|column| candidates[*column] & (1 << 8) != 0)
+            .collect();
+        assert_eq!(nines, vec![3, 5]);
+
+        assert!(set(&mut grid, 0, 5, 9).is_ok());
+    }
+
+    #[test]
+    fn the_filling_adds_up_to_the_total() {
+        let mut grid = Grid::new();
+        grid.add_constraint("c1 = 5".parse::<Sandwich>().unwrap()).unwrap();
+        set(&mut grid, 0, 0, 9).unwrap();
+        set(&mut grid, 1, 0, 2).unwrap();
+        set(&mut grid, 3, 0, 1).unwrap();
+
+        assert!(set(&mut grid.clone(), 2, 0, 4).unwrap_err().is::<ClueError>());
+        assert!(set(&mut grid, 2, 0, 3).is_ok());
+    }
+
+    #[test]
+    fn reads_clues_for_rows_and_columns() {
+        assert_eq!("r3=15".parse::<Sandwich>().unwrap().line(), SandwichLine::Row(2));
+        assert_eq!("C5 = 0".parse::<Sandwich>().unwrap().line(), SandwichLine::Column(4));
+        assert!("r0=3".parse::<Sandwich>().is_err());
+        assert!("x3=3".parse::<Sandwich>().is_err());
+        assert!("r3".parse::<Sandwich>().is_err());
+    }
+}
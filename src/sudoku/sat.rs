@@ -0,0 +1,424 @@
+use crate::sudoku::error::{IncompleteModel, UnencodableConstraint, UnreadableModel, UnsatisfiableModel};
+use crate::sudoku::grid::{CellValue, Grid};
+use crate::sudoku::reference::GridReference;
+use crate::sudoku::shape::GridShape;
+use std::error::Error;
+
+/// A variable, or its negation when negative, in the way DIMACS numbers them from 1
+pub type Literal = i32;
+
+/// The ways of saying that at most one of a group of literals is true.  Pairwise needs no extra
+/// variables but grows with the square of the group, while the others grow more slowly at the cost
+/// of some extra variables
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum CardinalityEncoding {
+    /// A clause for every pair, saying they aren't both true
+    Pairwise,
+    /// Sinz's sequential counter, with an extra variable for each literal recording whether any
+    /// literal up to that one is true
+    Sequential,
+    /// Each literal is given a number, and being true forces a set of extra variables to spell out
+    /// that number in binary
+    Binary,
+}
+
+/*
+ * A puzzle as a formula in conjunctive normal form, ready for an external SAT solver.  There's a
+ * variable for each value of each cell, true when the cell holds that value, numbered in row order
+ * of the cells and then by value.  Constraints add any extra variables they need after those.
+ *
+ * Solvers report back which variables are true in a model, which can be read into a solved grid
+ * and compared with the puzzle using GridDiff
+ */
+#[derive(Debug, Clone)]
+pub struct Cnf {
+    shape: GridShape,
+    encoding: CardinalityEncoding,
+    variable_count: usize,
+    clauses: Vec<Vec<Literal>>,
+}
+
+impl Cnf {
+    /// Encodes the grid's values and every one of its constraints
+    pub fn from_grid(grid: &Grid, encoding: CardinalityEncoding) -> Result<Self, Box<dyn Error>> {
+        let shape = *grid.shape();
+        let mut cnf = Self { shape, encoding, variable_count: shape.cell_count() * shape.size(), clauses: Vec::new() };
+
+        for grid_ref in shape.grid_refs() {
+            let values: Vec<Literal> = (1 ..= shape.max_value()).map(|value| cnf.variable(&grid_ref, value)).collect();
+            cnf.exactly_one(&values);
+
+            if let Some(value) = grid.cell(&grid_ref) {
+                cnf.add_clause(vec![cnf.variable(&grid_ref, *value)]);
+            }
+        }
+
+        for constraint in grid.constraints() {
+            if !constraint.encode(&shape, &mut cnf) {
+                return Err(UnencodableConstraint::new(format!("{:?}", constraint)).into());
+            }
+        }
+
+        Ok(cnf)
+    }
+
+    pub fn shape(&self) -> &GridShape {
+        &self.shape
+    }
+
+    pub fn encoding(&self) -> CardinalityEncoding {
+        self.encoding
+    }
+
+    pub fn variable_count(&self) -> usize {
+        self.variable_count
+    }
+
+    pub fn clauses(&self) -> &[Vec<Literal>] {
+        &self.clauses
+    }
+
+    /// The variable that's true when the cell holds the value
+    pub fn variable(&self, grid_ref: &GridReference, value: u8) -> Literal {
+        (grid_ref.to_index(&self.shape) * self.shape.size() + value as usize) as Literal
+    }
+
+    /// A fresh variable, for constraints that need more than the cells' values to say what they mean
+    pub fn new_variable(&mut self) -> Literal {
+        self.variable_count += 1;
+        self.variable_count as Literal
+    }
+
+    pub fn add_clause(&mut self, clause: Vec<Literal>) {
+        self.clauses.push(clause);
+    }
+
+    /// At most one of the literals is true, using the chosen encoding
+    pub fn at_most_one(&mut self, literals: &[Literal]) {
+        if literals.len() < 2 {
+            return;
+        }
+
+        match self.encoding {
+            CardinalityEncoding::Pairwise => {
+                for (position, first) in literals.iter().enumerate() {
+                    for second in literals[position + 1 ..].iter() {
+                        self.add_clause(vec![-first, -second]);
+                    }
+                }
+            },
+            CardinalityEncoding::Sequential => {
+                // Each counter variable is true if any literal up to and including its own is
+                let counters: Vec<Literal> = (0 .. literals.len() - 1).map(|_| self.new_variable()).collect();
+
+                self.add_clause(vec![-literals[0], counters[0]]);
+                for position in 1 .. literals.len() - 1 {
+                    self.add_clause(vec![-literals[position], counters[position]]);
+                    self.add_clause(vec![-counters[position - 1], counters[position]]);
+                    self.add_clause(vec![-literals[position], -counters[position - 1]]);
+                }
+                self.add_clause(vec![-literals[literals.len() - 1], -counters[literals.len() - 2]]);
+            },
+            CardinalityEncoding::Binary => {
+                let bit_count = (usize::BITS - (literals.len() - 1).leading_zeros()) as usize;
+                let bits: Vec<Literal> = (0 .. bit_count).map(|_| self.new_variable()).collect();
+
+                for (position, literal) in literals.iter().enumerate() {
+                    for (bit, variable) in bits.iter().enumerate() {
+                        let set = position & (1 << bit) != 0;
+                        self.add_clause(vec![-literal, if set { *variable } else { -variable }]);
+                    }
+                }
+            },
+        }
+    }
+
+    /// Exactly one of the literals is true
+    pub fn exactly_one(&mut self, literals: &[Literal]) {
+        self.add_clause(literals.to_vec());
+        self.at_most_one(literals);
+    }
+
+    /// Rules out every pair of values for the two cells that allows turns down, with the first
+    /// cell's value given first
+    pub fn forbid_pairs(&mut self, first: &GridReference, second: &GridReference, allows: impl Fn(u8, u8) -> bool) {
+        for value in 1 ..= self.shape.max_value() {
+            for other in (1 ..= self.shape.max_value()).filter(|other| !allows(value, *other)) {
+                self.add_clause(vec![-self.variable(first, value), -self.variable(second, other)]);
+            }
+        }
+    }
+
+    /*
+     * Variables for the running totals of the cells' values, for constraints about sums.  For
+     * every number of cells from none up to all of them there's a list indexed by total, with a
+     * variable for each total those cells can reach.  A variable is forced to be true when the
+     * cells do add up to its total, so ruling out the variables for the wrong totals rules out
+     * every way of reaching them.  Nothing stops other variables being true as well, so they can't
+     * be relied on the other way round
+     */
+    pub fn running_totals(&mut self, cells: &[GridReference]) -> Vec<Vec<Option<Literal>>> {
+        let max_value = self.shape.max_value() as usize;
+        let start = self.new_variable();
+        self.add_clause(vec![start]);
+
+        let mut totals: Vec<Vec<Option<Literal>>> = vec![vec![Some(start)]];
+        for grid_ref in cells {
+            let previous = totals.last().expect("There's always a list for no cells").clone();
+            let mut next: Vec<Option<Literal>> = vec![None; previous.len() + max_value];
+
+            for (total, reached) in previous.iter().enumerate() {
+                let Some(reached) = reached else {
+                    continue;
+                };
+
+                for value in 1 ..= max_value {
+                    let target = match next[total + value] {
+                        Some(target) => target,
+                        None => *next[total + value].insert(self.new_variable()),
+                    };
+                    self.add_clause(vec![-reached, -self.variable(grid_ref, value as u8), target]);
+                }
+            }
+
+            totals.push(next);
+        }
+
+        totals
+    }
+
+    /// The cells' values add up to the total
+    pub fn require_total(&mut self, cells: &[GridReference], total: usize) {
+        let totals = self.running_totals(cells);
+        let reached = totals.last().expect("There's always a list for no cells");
+
+        for (other, variable) in reached.iter().enumerate() {
+            if let Some(variable) = variable && other != total {
+                self.add_clause(vec![-variable]);
+            }
+        }
+    }
+
+    /// The formula in DIMACS CNF format, which SAT solvers take as input
+    pub fn to_dimacs(&self) -> String {
+        let mut output = format!(
+            "c {} x {} grid, one variable per value of each cell in row order\np cnf {} {}\n",
+            self.shape.rows(),
+            self.shape.columns(),
+            self.variable_count,
+            self.clauses.len(),
+        );
+
+        for clause in self.clauses.iter() {
+            for literal in clause {
+                output.push_str(&format!("{} ", literal));
+            }
+            output.push_str("0\n");
+        }
+
+        output
+    }
+
+    /*
+     * Reads the model a SAT solver found back into the grid, filling in its empty cells.  Either
+     * the competition format ("s SATISFIABLE" then "v" lines of literals) or a bare list of
+     * literals will do, and comment lines are skipped.  The values are set in the usual way, so a
+     * model that doesn't fit the grid's constraints is turned away
+     */
+    pub fn solution_from_model(&self, grid: &Grid, model: &str) -> Result<Grid, Box<dyn Error>> {
+        let mut true_variables = vec![false; self.variable_count + 1];
+
+        for line in model.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('c')) {
+            let line = line.strip_prefix('v').unwrap_or(line);
+            if line.starts_with('s') || line.starts_with("SAT") || line.starts_with("UNSAT") {
+                if line.contains("UNSAT") {
+                    return Err(UnsatisfiableModel::new().into());
+                }
+                continue;
+            }
+
+            for token in line.split_whitespace() {
+                let literal: Literal = token.parse().map_err(|_| UnreadableModel::new(token))?;
+                if let Some(variable) = usize::try_from(literal).ok().filter(|variable| *variable <= self.variable_count) {
+                    true_variables[variable] = true;
+                }
+            }
+        }
+
+        self.solution_from_assignment(grid, &true_variables)
+    }
+
+    /// Fills in the grid's empty cells from which variables are true, indexed by variable with
+    /// index 0 unused, as the solver in the cdcl module reports them
+    pub fn solution_from_assignment(&self, grid: &Grid, true_variables: &[bool]) -> Result<Grid, Box<dyn Error>> {
+        let mut solution = grid.clone();
+        for grid_ref in self.shape.grid_refs().filter(|grid_ref| grid.cell(grid_ref).is_none()) {
+            let value = (1 ..= self.shape.max_value())
+                .find(|value| true_variables.get(self.variable(&grid_ref, *value) as usize) == Some(&true))
+                .ok_or_else(|| IncompleteModel::new(grid_ref.row(), grid_ref.column()))?;
+
+            solution.set_cell(&grid_ref, &CellValue::new(value)?)?;
+        }
+
+        Ok(solution)
+    }
+}
+
+#[cfg(test)]
+impl Cnf {
+    /*
+     * Whether the formula can be satisfied with the cells holding the values of the solved grid,
+     * for checking an encoding against the constraints themselves.  The cells fix most of the
+     * extra variables through unit propagation, and a plain backtracking search finds the rest
+     */
+    pub(crate) fn holds_for(&self, solved: &Grid) -> bool {
+        let mut assigned: Vec<Option<bool>> = vec![None; self.variable_count + 1];
+        for grid_ref in self.shape.grid_refs() {
+            for value in 1 ..= self.shape.max_value() {
+                assigned[self.variable(&grid_ref, value) as usize] = Some(*solved.cell(&grid_ref) == Some(value));
+            }
+        }
+
+        self.search(assigned)
+    }
+
+    fn search(&self, mut assigned: Vec<Option<bool>>) -> bool {
+        let value_of = |assigned: &[Option<bool>], literal: Literal| {
+            assigned[literal.unsigned_abs() as usize].map(|value| value == (literal > 0))
+        };
+
+        let mut branch = None;
+        let mut propagated = true;
+        while propagated {
+            (branch, propagated) = (None, false);
+
+            for clause in self.clauses.iter() {
+                if clause.iter().any(|literal| value_of(&assigned, *literal) == Some(true)) {
+                    continue;
+                }
+
+                let open: Vec<Literal> = clause.iter().copied().filter(|literal| value_of(&assigned, *literal).is_none()).collect();
+                match open.len() {
+                    0 => return false,
+                    1 => {
+                        assigned[open[0].unsigned_abs() as usize] = Some(open[0] > 0);
+                        propagated = true;
+                    },
+                    _ => branch = branch.or(Some(open[0])),
+                }
+            }
+        }
+
+        let Some(literal) = branch else {
+            return true;
+        };
+
+        [true, false].into_iter().any(|value| {
+            let mut tried = assigned.clone();
+            tried[literal.unsigned_abs() as usize] = Some(value);
+            self.search(tried)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sudoku::cage::Cage;
+    use crate::sudoku::constraint::{diagonal_constraints, CandidateSet, Constraint};
+    use crate::sudoku::error::UnencodableConstraint;
+    use crate::sudoku::line::Thermometer;
+
+    const ENCODINGS: [CardinalityEncoding; 3] = [
+        CardinalityEncoding::Pairwise,
+        CardinalityEncoding::Sequential,
+        CardinalityEncoding::Binary,
+    ];
+
+    const SOLVED: &str = "1234342121434312";
+
+    fn cell(row: usize, column: usize) -> GridReference {
+        GridReference::for_shape(&GridShape::new(2, 2).unwrap(), row, column).unwrap()
+    }
+
+    #[test]
+    fn writes_a_variable_for_each_value_of_each_cell() {
+        let puzzle: Grid = "1...........4..2".parse().unwrap();
+        let cnf = Cnf::from_grid(&puzzle, CardinalityEncoding::Pairwise).unwrap();
+
+        assert_eq!(cnf.variable(&cell(0, 0), 1), 1);
+        assert_eq!(cnf.variable(&cell(0, 1), 1), 5);
+        assert_eq!(cnf.variable(&cell(3, 3), 4), 64);
+        assert_eq!(cnf.variable_count(), 64);
+        assert!(cnf.clauses().contains(&vec![1]));
+        assert!(cnf.clauses().contains(&vec![64 - 2]));
+
+        let dimacs = cnf.to_dimacs();
+        assert!(dimacs.lines().nth(1).unwrap().starts_with(&format!("p cnf 64 {}", cnf.clauses().len())));
+        assert_eq!(dimacs.lines().count(), cnf.clauses().len() + 2);
+        assert!(dimacs.lines().skip(2).all(|line| line.ends_with(" 0")));
+    }
+
+    #[test]
+    fn every_encoding_agrees_with_the_constraints() {
+        let shape = GridShape::new(2, 2).unwrap();
+        let mut rules = Grid::with_constraints(shape, diagonal_constraints(&shape));
+        rules.add_constraint(Cage::new(3, vec![cell(0, 0), cell(0, 1)]).unwrap()).unwrap();
+        rules.add_constraint(Thermometer::new(vec![cell(1, 2), cell(1, 3)]).unwrap()).unwrap();
+
+        // The solution with its 1s and 2s swapped is still a Latin square with sound diagonals,
+        // but it breaks the thermometer
+        let solved: Grid = "1234341243212143".parse().unwrap();
+        let swapped: Grid = "2134342143121243".parse().unwrap();
+
+        for encoding in ENCODINGS {
+            let cnf = Cnf::from_grid(&rules, encoding).unwrap();
+            assert!(cnf.holds_for(&solved), "{:?}", encoding);
+            assert!(!cnf.holds_for(&swapped), "{:?}", encoding);
+        }
+
+        let mut filled = rules.clone();
+        assert!(swapped.shape().grid_refs().any(|grid_ref| {
+            filled.set_cell(&grid_ref, &CellValue::new(swapped.cell(&grid_ref).unwrap()).unwrap()).is_err()
+        }));
+    }
+
+    #[test]
+    fn reads_back_a_model() {
+        let puzzle: Grid = "1...........4..2".parse().unwrap();
+        let solved: Grid = SOLVED.parse().unwrap();
+        let cnf = Cnf::from_grid(&puzzle, CardinalityEncoding::Sequential).unwrap();
+
+        let model: Vec<String> = puzzle.shape().grid_refs()
+            .map(|grid_ref| cnf.variable(&grid_ref, solved.cell(&grid_ref).unwrap()).to_string())
+            .collect();
+        let read = cnf.solution_from_model(&puzzle, &format!("c found one\ns SATISFIABLE\nv {} 0", model.join(" "))).unwrap();
+        assert_eq!(read.to_line(), SOLVED);
+
+        assert!(cnf.solution_from_model(&puzzle, "s UNSATISFIABLE").unwrap_err().is::<UnsatisfiableModel>());
+        assert!(cnf.solution_from_model(&puzzle, "v 1 x 0").unwrap_err().is::<UnreadableModel>());
+        assert!(cnf.solution_from_model(&puzzle, "v 1 0").unwrap_err().is::<IncompleteModel>());
+    }
+
+    #[test]
+    fn turns_away_constraints_it_cant_encode() {
+        #[derive(Debug)]
+        struct Unencodable;
+
+        impl Constraint for Unencodable {
+            fn affected_cells(&self, _shape: &GridShape) -> Vec<GridReference> {
+                Vec::new()
+            }
+
+            fn check(&self, _grid: &Grid, _changed: &GridReference) -> Result<(), Box<dyn Error>> {
+                Ok(())
+            }
+
+            fn prune(&self, _grid: &Grid, _candidates: &mut [CandidateSet]) {}
+        }
+
+        let mut grid = Grid::new();
+        grid.add_constraint(Unencodable).unwrap();
+        assert!(Cnf::from_grid(&grid, CardinalityEncoding::Binary).unwrap_err().is::<UnencodableConstraint>());
+    }
+}
@@ -0,0 +1,187 @@
+use crate::sudoku::error::InvalidShape;
+use crate::sudoku::reference::GridReference;
+
+/*
+ * The dimensions of a grid, given by the size of its subgrids.  A grid always has as many rows and
+ * columns as there are cells in a subgrid, and uses the values 1 up to that number, so a 2 x 3
+ * subgrid gives a 6 x 6 grid using the values 1 - 6 and a 4 x 4 subgrid gives the 16 x 16 grid
+ * using 1 - 16.
+ *
+ * Subgrids are stacked in bands (a row of subgrids) and stacks (a column of subgrids).  With
+ * rectangular subgrids there are as many bands as each subgrid has columns, and as many stacks as
+ * it has rows
+ */
+#[derive(Debug, Clone, Copy, Eq, Hash, PartialEq)]
+pub struct GridShape {
+    subgrid_rows: usize,
+    subgrid_columns: usize,
+}
+
+impl GridShape {
+    /// The largest grid we support, as values beyond 25 run out of letters to display them with
+    pub const MAX_SIZE: usize = 25;
+
+    /// The smallest a subgrid can be in either direction.  A subgrid one cell high would just be
+    /// a row, which the rules already cover, so the smallest grid is the 4 x 4 one
+    pub const MIN_SUBGRID_SIDE: usize = 2;
+
+    pub fn new(subgrid_rows: usize, subgrid_columns: usize) -> Result<Self, InvalidShape> {
+        let size = subgrid_rows * subgrid_columns;
+        if subgrid_rows < Self::MIN_SUBGRID_SIDE || subgrid_columns < Self::MIN_SUBGRID_SIDE || size > Self::MAX_SIZE {
+            return Err(InvalidShape::new(subgrid_rows, subgrid_columns));
+        }
+
+        Ok(Self { subgrid_rows, subgrid_columns })
+    }
+
+    /// The standard 9 x 9 grid with 3 x 3 subgrids
+    pub const fn standard() -> Self {
+        Self { subgrid_rows: 3, subgrid_columns: 3 }
+    }
+
+    /// The 25 x 25 grid, for checking things that aren't tied to a grid yet (such as a cell read
+    /// from text) against every grid they could belong to
+    pub const fn largest() -> Self {
+        Self { subgrid_rows: 5, subgrid_columns: 5 }
+    }
+
+    /// Works out the shape from the total number of cells, for reading puzzles from text.  Where
+    /// there's a choice of subgrid shape (such as 2 x 3 or 3 x 2) the wider subgrid is used, as
+    /// that's by far the more common way to lay them out.  Grids whose size is prime, such as
+    /// 7 x 7, can't be split into subgrids, so they don't have a shape
+    pub fn from_cell_count(cell_count: usize) -> Option<Self> {
+        let size = (1 ..= Self::MAX_SIZE).find(|size| size * size == cell_count)?;
+        let subgrid_rows = (Self::MIN_SUBGRID_SIDE ..= size).rev()
+            .find(|rows| size % rows == 0 && rows * rows <= size)?;
+
+        Self::new(subgrid_rows, size / subgrid_rows).ok()
+    }
+
+    /// The number of rows, columns and distinct values in the grid
+    pub fn size(&self) -> usize {
+        self.subgrid_rows * self.subgrid_columns
+    }
+
+    pub fn rows(&self) -> usize {
+        self.size()
+    }
+
+    pub fn columns(&self) -> usize {
+        self.size()
+    }
+
+    pub fn subgrid_rows(&self) -> usize {
+        self.subgrid_rows
+    }
+
+    pub fn subgrid_columns(&self) -> usize {
+        self.subgrid_columns
+    }
+
+    pub fn subgrid_count(&self) -> usize {
+        self.size()
+    }
+
+    pub fn cell_count(&self) -> usize {
+        self.size() * self.size()
+    }
+
+    pub fn max_value(&self) -> u8 {
+        self.size() as u8
+    }
+
+    /// The number of bands (rows of subgrids)
+    pub fn band_count(&self) -> usize {
+        self.rows() / self.subgrid_rows
+    }
+
+    /// The number of stacks (columns of subgrids)
+    pub fn stack_count(&self) -> usize {
+        self.columns() / self.subgrid_columns
+    }
+
+    /// Every cell of the grid, row by row
+    pub fn grid_refs(&self) -> impl Iterator<Item = GridReference> + use<> {
+        let shape = *self;
+
+        (0 .. shape.cell_count()).map(move |index| {
+            GridReference::for_shape(&shape, index / shape.columns(), index % shape.columns())
+                .expect("Index is always within the grid")
+        })
+    }
+
+    pub fn has_square_subgrids(&self) -> bool {
+        self.subgrid_rows == self.subgrid_columns
+    }
+
+    pub fn index_of(&self, row: usize, column: usize) -> usize {
+        row * self.columns() + column
+    }
+
+    /// Subgrids are numbered left to right, then top to bottom
+    pub fn subgrid_of(&self, row: usize, column: usize) -> usize {
+        (row / self.subgrid_rows) * self.stack_count() + (column / self.subgrid_columns)
+    }
+
+    /// The top left cell of the given subgrid
+    pub fn subgrid_origin(&self, subgrid: usize) -> (usize, usize) {
+        (
+            (subgrid / self.stack_count()) * self.subgrid_rows,
+            (subgrid % self.stack_count()) * self.subgrid_columns,
+        )
+    }
+
+    /// The character used to show a value: the digits 1 - 9, then A for 10, B for 11, and so on
+    pub fn symbol_for(value: u8) -> char {
+        match value {
+            0 ..= 9 => char::from(b'0' + value),
+            _ => char::from(b'A' + value - 10),
+        }
+    }
+
+    /// The reverse of symbol_for(), with letters accepted in either case
+    pub fn value_for(symbol: char) -> Option<u8> {
+        match symbol {
+            '1' ..= '9' => Some(symbol as u8 - b'0'),
+            'A' ..= 'P' => Some(symbol as u8 - b'A' + 10),
+            'a' ..= 'p' => Some(symbol as u8 - b'a' + 10),
+            _ => None,
+        }
+    }
+}
+
+impl Default for GridShape {
+    fn default() -> Self {
+        Self::standard()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn subgrids_are_at_least_two_cells_each_way() {
+        assert!(GridShape::new(1, 4).is_err());
+        assert!(GridShape::new(4, 1).is_err());
+        assert!(GridShape::new(6, 6).is_err());
+        assert!(GridShape::new(2, 3).is_ok());
+    }
+
+    #[test]
+    fn finds_the_shape_for_a_cell_count() {
+        assert_eq!(GridShape::from_cell_count(81), Some(GridShape::standard()));
+        assert_eq!(GridShape::from_cell_count(36), Some(GridShape::new(2, 3).unwrap()));
+        assert_eq!(GridShape::from_cell_count(49), None);
+        assert_eq!(GridShape::from_cell_count(80), None);
+    }
+
+    #[test]
+    fn rectangular_subgrids_split_into_bands_and_stacks() {
+        let shape = GridShape::new(2, 3).unwrap();
+
+        assert_eq!((shape.rows(), shape.max_value(), shape.cell_count()), (6, 6, 36));
+        assert_eq!((shape.band_count(), shape.stack_count()), (3, 2));
+        assert_eq!(shape.grid_refs().count(), 36);
+    }
+}
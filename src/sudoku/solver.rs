@@ -1,13 +1,48 @@
 use crate::sudoku::cached_option_finder::CachedOptionFinder;
+use crate::sudoku::constraint::CandidateSet;
 use crate::sudoku::grid::{CellValue, Grid};
 use crate::sudoku::option_finder::*;
 use crate::sudoku::reference::GridReference;
 use std::error::Error;
+use std::fmt::Debug;
+
+/*
+ * What every solver can tell us about a puzzle.  Code that checks a lot of puzzles, such as the
+ * generator and the minimiser, is given a SolverBuilder rather than picking a solver itself, so
+ * that it can use whichever solver suits the puzzles best
+ */
+pub trait Solver: Debug + Send {
+    /// Looks for a solution, which get_solution then returns
+    fn solve(&mut self) -> &mut dyn Solver;
+
+    /// Searches for up to limit solutions to the problem.  Any limit will stop the search short of
+    /// exhaustively enumerating every solution, which can take a very long time for sparse grids.
+    fn find_solutions(&mut self, limit: usize) -> Vec<Grid>;
+
+    fn count_solutions(&mut self, limit: usize) -> usize {
+        self.find_solutions(limit).len()
+    }
+
+    /// A well-formed puzzle has exactly one solution.  We only need to search as far as finding a
+    /// second solution to know that's not the case
+    fn has_unique_solution(&mut self) -> bool {
+        self.count_solutions(2) == 1
+    }
+
+    fn get_solution(&self) -> &Option<Grid>;
+}
+
+/// Makes a solver for a puzzle, or fails if that kind of solver can't take the puzzle on.  Whether
+/// it can should only come down to the puzzle's rules, so that a solver that takes a puzzle also
+/// takes it with givens added or removed
+pub type SolverBuilder = for<'problem> fn(&'problem Grid) -> Result<Box<dyn Solver + 'problem>, Box<dyn Error>>;
 
 #[derive(Debug)]
 pub struct DepthFirstSolver<'problem> {
     problem: &'problem Grid,
     option_finder: CachedOptionFinder<'problem>,
+    // The cells of every house, as indices, for spotting values with only one place left
+    houses: Vec<Vec<usize>>,
     solution: Option<Grid>,
 }
 
@@ -17,55 +52,166 @@ impl<'problem> DepthFirstSolver<'problem> {
         Self {
             problem,
             option_finder,
+            houses: Self::houses_of(problem),
             solution: None,
         }
     }
 
-    pub fn solve(&mut self) -> &mut Self {
-        let mut solution = self.problem.clone();
-
-        let solved = self.find_solution(&mut solution, 0, 0);
-        match solved {
-            Ok(true) => self.solution = Some(solution),
-            Ok(false) => self.solution = None,
-            Err(err) => panic!("{}", err),
-        }
+    /// Shorthand for the common case of solving with a cached standard option finder
+    pub fn for_problem(problem: &'problem Grid) -> Self {
+        Self::new(problem, CachedOptionFinder::new(StandardOptionFinder::new(problem)))
+    }
 
-        self
+    /// Builds depth-first solvers, which is what the generator and the like use unless told
+    /// otherwise
+    pub fn builder() -> SolverBuilder {
+        |problem| Ok(Box::new(DepthFirstSolver::for_problem(problem)))
     }
 
-    pub fn get_solution(&self) -> &Option<Grid> {
-        &self.solution
+    fn houses_of(problem: &Grid) -> Vec<Vec<usize>> {
+        let shape = problem.shape();
+
+        problem.constraints()
+            .iter()
+            .filter(|constraint| constraint.is_house(shape))
+            .map(|constraint| {
+                constraint.affected_cells(shape)
+                    .iter()
+                    .map(|grid_ref| grid_ref.to_index(shape))
+                    .collect()
+            })
+            .collect()
     }
 
-    fn find_solution(&mut self, solution: &mut Grid, row_id: usize, column_id: usize) -> Result<bool, Box<dyn Error>> {
-        if row_id > Grid::GRID_COLUMNS - 1 {
-            // If we've passed the end of the grid then we've succeeded in finding a solution
-            Ok(true)
-        } else if column_id > Grid::GRID_ROWS - 1 {
-            // If we've passed the end of this row then move to the next one
-            self.find_solution(solution, row_id + 1, 0)
-        } else if solution.cell(&GridReference::from_numbers(row_id, column_id)?).is_some() {
-            // If this cell already has a value, move on to the next one
-            self.find_solution(solution, row_id, column_id + 1)
-        } else {
-            // Try each possible value in this cell then attempt to solve the rest of the puzzle
-            let grid_ref = GridReference::from_numbers(row_id, column_id)?;
-            let options = self.option_finder.find_for_cell(&grid_ref);
-
-            for option in options {
-                if solution.set_cell(&grid_ref, &CellValue::new(option)?).is_ok()
-                    && self.find_solution(solution, row_id, column_id + 1)? {
-                    return Ok(true)
-                } else {
-                    solution.clear_cell(&grid_ref);
+    /*
+     * Pick the empty cell with the fewest remaining options given the values placed so far.  A
+     * wrong guess in a tightly constrained cell is found (and backtracked out of) far closer to the
+     * root of the search than it would be if we just walked the grid row by row.  Ties go to the
+     * first such cell in row order.
+     *
+     * A value with only one place left in a house is just as good as a cell with a single option,
+     * so if no cell is down to one option we look for one of those.  Without that the larger grids
+     * can take hours to search.
+     *
+     * This runs at every step of the search, so rather than asking an option finder about every
+     * empty cell we have each constraint prune the candidates for the whole grid in one go, and
+     * only consult the option finder for the cell we pick
+     */
+    fn most_constrained_cell(&mut self, solution: &Grid) -> Option<(GridReference, OptionList)> {
+        let shape = *solution.shape();
+        let all_values: CandidateSet = (1 << shape.size()) - 1;
+
+        // Bit n - 1 is set for a filled cell holding the value n, and for an empty cell that could
+        // still take it
+        let mut filled: Vec<CandidateSet> = vec![0; shape.cell_count()];
+        let mut open: Vec<CandidateSet> = vec![0; shape.cell_count()];
+        for grid_ref in shape.grid_refs() {
+            let index = grid_ref.to_index(&shape);
+            match solution.cell(&grid_ref) {
+                Some(value) => filled[index] = 1 << (value - 1),
+                None => open[index] = all_values,
+            }
+        }
+
+        for constraint in solution.constraints() {
+            constraint.prune(solution, &mut open);
+        }
+
+        let mut best: Option<(usize, CandidateSet)> = None;
+        for index in (0 .. shape.cell_count()).filter(|index| filled[*index] == 0) {
+            if best.is_none_or(|(_, best_open)| open[index].count_ones() < best_open.count_ones()) {
+                best = Some((index, open[index]));
+            }
+        }
+
+        let (mut index, mut candidates) = best?;
+        if candidates.count_ones() > 1 {
+            for house in self.houses.iter() {
+                let mut placed = 0;
+                let mut seen_once = 0;
+                let mut seen_twice = 0;
+                for cell in house.iter() {
+                    placed |= filled[*cell];
+                    seen_twice |= seen_once & open[*cell];
+                    seen_once |= open[*cell];
                 }
+
+                // A value the house still needs but has nowhere to put is a dead end
+                if all_values & !placed & !seen_once != 0 {
+                    candidates = 0;
+                    break;
+                }
+
+                let hidden_singles = seen_once & !seen_twice;
+                if hidden_singles != 0 {
+                    let value_bit = 1 << hidden_singles.trailing_zeros();
+                    index = *house.iter()
+                        .find(|cell| open[**cell] & value_bit != 0)
+                        .expect("The value has exactly one place in the house");
+                    candidates = value_bit;
+                    break;
+                }
+            }
+        }
+
+        // The cached options were worked out against the original problem, so narrow them down to
+        // the ones that are still open in the current state
+        let grid_ref = GridReference::for_shape(&shape, index / shape.columns(), index % shape.columns())
+            .expect("Index is always within the grid");
+        let options = self.option_finder.find_for_cell(&grid_ref)
+            .into_iter()
+            .filter(|option| candidates & (1 << (option - 1)) != 0)
+            .collect();
+
+        Some((grid_ref, options))
+    }
+
+    /*
+     * Returns true once enough solutions have been found to stop searching, and false if the search
+     * should continue (either backtracking or moving on to the next option)
+     */
+    fn find_solution(&mut self, solution: &mut Grid, found: &mut Vec<Grid>, limit: usize) -> Result<bool, Box<dyn Error>> {
+        let Some((grid_ref, options)) = self.most_constrained_cell(solution) else {
+            // If there are no empty cells left then we've succeeded in finding a solution
+            found.push(solution.clone());
+            return Ok(found.len() >= limit);
+        };
+
+        // Try each possible value in this cell then attempt to solve the rest of the puzzle
+        for option in options {
+            if solution.set_cell(&grid_ref, &CellValue::new(option)?).is_ok()
+                && self.find_solution(solution, found, limit)? {
+                return Ok(true)
+            } else {
+                solution.clear_cell(&grid_ref);
             }
+        }
 
-            // If we got here then we failed to solve the puzzle on this branch, either we'll have
-            // to backtrack and try another option, or there are no more options and the puzzle is
-            // not solvable
-            Ok(false)
+        // If we got here then we've exhausted this branch, either we'll have to backtrack and try
+        // another option, or there are no more options and the search is complete
+        Ok(false)
+    }
+}
+
+impl Solver for DepthFirstSolver<'_> {
+    fn solve(&mut self) -> &mut dyn Solver {
+        self.solution = self.find_solutions(1).pop();
+        self
+    }
+
+    fn find_solutions(&mut self, limit: usize) -> Vec<Grid> {
+        let mut solution = self.problem.clone();
+        let mut found: Vec<Grid> = Vec::new();
+
+        if limit > 0
+            && let Err(err) = self.find_solution(&mut solution, &mut found, limit) {
+            panic!("{}", err);
         }
+
+        found
+    }
+
+    fn get_solution(&self) -> &Option<Grid> {
+        &self.solution
     }
 }
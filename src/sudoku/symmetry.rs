@@ -0,0 +1,163 @@
+use crate::sudoku::grid::Grid;
+use crate::sudoku::reference::GridReference;
+use crate::sudoku::shape::GridShape;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+#[derive(Debug, Clone, Copy, Eq, Hash, PartialEq)]
+pub enum Symmetry {
+    None,
+    Rotational180,
+    Rotational90,
+    Horizontal,
+    Vertical,
+    Diagonal,
+    AntiDiagonal,
+}
+
+impl Symmetry {
+    pub const ALL: [Symmetry; 7] = [
+        Symmetry::None,
+        Symmetry::Rotational180,
+        Symmetry::Rotational90,
+        Symmetry::Horizontal,
+        Symmetry::Vertical,
+        Symmetry::Diagonal,
+        Symmetry::AntiDiagonal,
+    ];
+
+    /// Where the given cell ends up after applying the symmetry's transformation once
+    pub fn image(&self, shape: &GridShape, row: usize, column: usize) -> (usize, usize) {
+        let last = shape.size() - 1;
+
+        match self {
+            Symmetry::None => (row, column),
+            Symmetry::Rotational180 => (last - row, last - column),
+            // A quarter turn clockwise
+            Symmetry::Rotational90 => (column, last - row),
+            // Mirrored across the horizontal centre line, so the top row swaps with the bottom row
+            Symmetry::Horizontal => (last - row, column),
+            // Mirrored across the vertical centre line, so the left column swaps with the right one
+            Symmetry::Vertical => (row, last - column),
+            // Mirrored across the top-left to bottom-right diagonal
+            Symmetry::Diagonal => (column, row),
+            // Mirrored across the top-right to bottom-left diagonal
+            Symmetry::AntiDiagonal => (last - column, last - row),
+        }
+    }
+
+    /*
+     * The group of cells that the symmetry maps onto one another.  For the reflections and the half
+     * turn that's a pair of cells (or just the one for cells on the axis or at the centre), and for
+     * the quarter turn it's up to four cells.  A clue pattern has the symmetry if every group is
+     * either entirely given or entirely empty
+     */
+    pub fn orbit(&self, shape: &GridShape, row: usize, column: usize) -> Vec<(usize, usize)> {
+        let mut orbit = vec![(row, column)];
+        let mut next = self.image(shape, row, column);
+
+        while next != (row, column) {
+            orbit.push(next);
+            next = self.image(shape, next.0, next.1);
+        }
+
+        orbit
+    }
+
+    /// Splits the grid into orbits, with each cell appearing in exactly one of them
+    pub fn orbits(&self, shape: &GridShape) -> Vec<Vec<(usize, usize)>> {
+        let mut seen = vec![false; shape.cell_count()];
+        let mut orbits = Vec::new();
+
+        for row in 0 .. shape.rows() {
+            for column in 0 .. shape.columns() {
+                if seen[shape.index_of(row, column)] {
+                    continue;
+                }
+
+                let orbit = self.orbit(shape, row, column);
+                for (orbit_row, orbit_column) in orbit.iter() {
+                    seen[shape.index_of(*orbit_row, *orbit_column)] = true;
+                }
+                orbits.push(orbit);
+            }
+        }
+
+        orbits
+    }
+
+    pub fn is_present_in(&self, grid: &Grid) -> bool {
+        let shape = grid.shape();
+        let is_clue = |row: usize, column: usize| {
+            GridReference::for_shape(shape, row, column)
+                .map(|grid_ref| grid.cell(&grid_ref).is_some())
+                .unwrap_or(false)
+        };
+
+        (0 .. shape.rows()).all(|row| {
+            (0 .. shape.columns()).all(|column| {
+                let (image_row, image_column) = self.image(shape, row, column);
+                is_clue(row, column) == is_clue(image_row, image_column)
+            })
+        })
+    }
+
+    /// Reports every symmetry that the grid's clue pattern has.  Every grid trivially has
+    /// Symmetry::None so it's left out, meaning an empty list indicates an asymmetric pattern
+    pub fn detect(grid: &Grid) -> Vec<Symmetry> {
+        Self::ALL.iter()
+            .filter(|symmetry| **symmetry != Symmetry::None && symmetry.is_present_in(grid))
+            .copied()
+            .collect()
+    }
+}
+
+impl Display for Symmetry {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        let name = match self {
+            Symmetry::None => "none",
+            Symmetry::Rotational180 => "180° rotational",
+            Symmetry::Rotational90 => "90° rotational",
+            Symmetry::Horizontal => "horizontal",
+            Symmetry::Vertical => "vertical",
+            Symmetry::Diagonal => "diagonal",
+            Symmetry::AntiDiagonal => "anti-diagonal",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sudoku::grid::CellValue;
+
+    fn with_clues(cells: &[(usize, usize)]) -> Grid {
+        let mut grid = Grid::new();
+        for (index, (row, column)) in cells.iter().enumerate() {
+            grid.set_cell(&GridReference::from_numbers(*row, *column).unwrap(), &CellValue::new(index as u8 + 1).unwrap())
+                .unwrap();
+        }
+
+        grid
+    }
+
+    #[test]
+    fn orbits_cover_every_cell_once() {
+        for symmetry in Symmetry::ALL {
+            let orbits = symmetry.orbits(&GridShape::standard());
+            assert_eq!(orbits.iter().map(Vec::len).sum::<usize>(), 81, "{}", symmetry);
+        }
+
+        assert_eq!(Symmetry::Rotational90.orbit(&GridShape::standard(), 0, 0), vec![(0, 0), (0, 8), (8, 8), (8, 0)]);
+        assert_eq!(Symmetry::Horizontal.orbit(&GridShape::standard(), 4, 2), vec![(4, 2)]);
+    }
+
+    #[test]
+    fn detects_the_symmetries_of_a_clue_pattern() {
+        assert_eq!(Symmetry::detect(&with_clues(&[(4, 4)])).len(), Symmetry::ALL.len() - 1);
+        assert_eq!(Symmetry::detect(&with_clues(&[(0, 0)])), vec![Symmetry::Diagonal]);
+        assert_eq!(Symmetry::detect(&with_clues(&[(0, 3), (8, 5)])), vec![Symmetry::Rotational180]);
+        assert!(Symmetry::detect(&with_clues(&[(0, 1)])).is_empty());
+    }
+}
@@ -0,0 +1,503 @@
+use crate::sudoku::error::{InvalidBand, InvalidDigitMapping, InvalidLineSwap, InvalidStack, NonStandardShape, UntransformableConstraint};
+use crate::sudoku::grid::{CellValue, Grid};
+use crate::sudoku::random::SeededRng;
+use crate::sudoku::reference::{ColumnReference, GridReference, RowReference};
+use crate::sudoku::region_map::RegionMap;
+use crate::sudoku::shape::GridShape;
+use std::error::Error;
+
+/// Transforms are defined on the standard grid.  Transposing and quarter turns would turn
+/// rectangular subgrids on their side, so they don't carry over to every shape
+const SHAPE: GridShape = GridShape::standard();
+
+/*
+ * A transformation that turns one valid Sudoku grid into another.
+ *
+ * Every combination of the validity-preserving operations (rotations, reflections, transposition,
+ * band/stack swaps, row/column swaps within a band/stack and relabelling the digits) boils down to
+ * the same shape: an optional transposition, followed by reordering the rows and the columns,
+ * followed by relabelling the digits.  So rather than keep a list of operations we keep that
+ * normalised form, which makes composing and inverting transforms cheap.
+ *
+ * Cell (row, column) of the transformed grid takes its value from cell (rows[row], columns[column])
+ * of the original, or from cell (columns[column], rows[row]) if the transform transposes, with the
+ * value then relabelled via the digit map
+ */
+#[derive(Debug, Clone, Eq, Hash, PartialEq)]
+pub struct Transform {
+    transpose: bool,
+    rows: Vec<usize>,
+    columns: Vec<usize>,
+    // digits[value - 1] is what value becomes
+    digits: Vec<u8>,
+}
+
+impl Transform {
+    pub fn identity() -> Self {
+        Self {
+            transpose: false,
+            rows: (0 .. SHAPE.rows()).collect(),
+            columns: (0 .. SHAPE.columns()).collect(),
+            digits: (CellValue::MIN_VALID_VAL ..= SHAPE.max_value()).collect(),
+        }
+    }
+
+    /// Reflects the grid across the top-left to bottom-right diagonal
+    pub fn transpose() -> Self {
+        Self { transpose: true, ..Self::identity() }
+    }
+
+    /// Reflects the grid across the top-right to bottom-left diagonal
+    pub fn anti_transpose() -> Self {
+        Self {
+            transpose: true,
+            rows: Self::reversed(SHAPE.rows()),
+            columns: Self::reversed(SHAPE.columns()),
+            ..Self::identity()
+        }
+    }
+
+    pub fn rotate_clockwise() -> Self {
+        Self {
+            transpose: true,
+            columns: Self::reversed(SHAPE.columns()),
+            ..Self::identity()
+        }
+    }
+
+    pub fn rotate_180() -> Self {
+        Self {
+            rows: Self::reversed(SHAPE.rows()),
+            columns: Self::reversed(SHAPE.columns()),
+            ..Self::identity()
+        }
+    }
+
+    pub fn rotate_anticlockwise() -> Self {
+        Self {
+            transpose: true,
+            rows: Self::reversed(SHAPE.rows()),
+            ..Self::identity()
+        }
+    }
+
+    /// Mirrors the grid across its horizontal centre line, swapping the top and bottom rows
+    pub fn reflect_horizontal() -> Self {
+        Self {
+            rows: Self::reversed(SHAPE.rows()),
+            ..Self::identity()
+        }
+    }
+
+    /// Mirrors the grid across its vertical centre line, swapping the left and right columns
+    pub fn reflect_vertical() -> Self {
+        Self {
+            columns: Self::reversed(SHAPE.columns()),
+            ..Self::identity()
+        }
+    }
+
+    pub fn swap_bands(first: usize, second: usize) -> Result<Self, Box<dyn Error>> {
+        let band_count = SHAPE.band_count();
+        for band in [first, second] {
+            if band >= band_count {
+                return Err(InvalidBand::new(band).into());
+            }
+        }
+
+        Ok(Self {
+            rows: Self::swapped_blocks(SHAPE.rows(), SHAPE.subgrid_rows(), first, second),
+            ..Self::identity()
+        })
+    }
+
+    pub fn swap_stacks(first: usize, second: usize) -> Result<Self, Box<dyn Error>> {
+        let stack_count = SHAPE.stack_count();
+        for stack in [first, second] {
+            if stack >= stack_count {
+                return Err(InvalidStack::new(stack).into());
+            }
+        }
+
+        Ok(Self {
+            columns: Self::swapped_blocks(SHAPE.columns(), SHAPE.subgrid_columns(), first, second),
+            ..Self::identity()
+        })
+    }
+
+    /// Swaps two rows, which must both be in the same band
+    pub fn swap_rows(first: usize, second: usize) -> Result<Self, Box<dyn Error>> {
+        let first = RowReference::new(first)?.row();
+        let second = RowReference::new(second)?.row();
+        if first / SHAPE.subgrid_rows() != second / SHAPE.subgrid_rows() {
+            return Err(InvalidLineSwap::new(first, second).into());
+        }
+
+        let mut rows: Vec<usize> = (0 .. SHAPE.rows()).collect();
+        rows.swap(first, second);
+
+        Ok(Self { rows, ..Self::identity() })
+    }
+
+    /// Swaps two columns, which must both be in the same stack
+    pub fn swap_columns(first: usize, second: usize) -> Result<Self, Box<dyn Error>> {
+        let first = ColumnReference::new(first)?.column();
+        let second = ColumnReference::new(second)?.column();
+        if first / SHAPE.subgrid_columns() != second / SHAPE.subgrid_columns() {
+            return Err(InvalidLineSwap::new(first, second).into());
+        }
+
+        let mut columns: Vec<usize> = (0 .. SHAPE.columns()).collect();
+        columns.swap(first, second);
+
+        Ok(Self { columns, ..Self::identity() })
+    }
+
+    /// Relabels the digits, with mapping[0] being what 1 becomes, mapping[1] what 2 becomes, etc
+    pub fn relabel(mapping: &[u8]) -> Result<Self, Box<dyn Error>> {
+        let mut sorted = mapping.to_vec();
+        sorted.sort();
+        if sorted != Self::identity().digits {
+            return Err(InvalidDigitMapping::new(mapping.to_vec()).into());
+        }
+
+        Ok(Self { digits: mapping.to_vec(), ..Self::identity() })
+    }
+
+    /// Builds a transform directly from its normalised form.  The caller is responsible for the
+    /// line orders keeping bands and stacks together
+    pub(crate) fn from_parts(transpose: bool, rows: Vec<usize>, columns: Vec<usize>, digits: Vec<u8>) -> Self {
+        Self { transpose, rows, columns, digits }
+    }
+
+    /// A random transform drawn evenly from the whole group of validity-preserving transforms
+    pub fn random(rng: &mut SeededRng) -> Self {
+        let rows = Self::random_line_order(rng, SHAPE.rows(), SHAPE.subgrid_rows());
+        let columns = Self::random_line_order(rng, SHAPE.columns(), SHAPE.subgrid_columns());
+        let mut digits = Self::identity().digits;
+        rng.shuffle(&mut digits);
+
+        Self { transpose: rng.below(2) == 1, rows, columns, digits }
+    }
+
+    /// A transform that applies this one and then the next one
+    pub fn then(&self, next: &Transform) -> Transform {
+        /*
+         * The transformed grid's cell (row, column) comes from next's source cell, which in turn
+         * comes from our source cell for that.  When next transposes, its rows index into our
+         * columns and vice versa, which also means the combined transform ends up with our
+         * line orders the other way round
+         */
+        let (row_source, column_source) = if next.transpose {
+            (&self.columns, &self.rows)
+        } else {
+            (&self.rows, &self.columns)
+        };
+
+        Transform {
+            transpose: self.transpose != next.transpose,
+            rows: next.rows.iter().map(|row| row_source[*row]).collect(),
+            columns: next.columns.iter().map(|column| column_source[*column]).collect(),
+            digits: self.digits.iter().map(|digit| next.digits[*digit as usize - 1]).collect(),
+        }
+    }
+
+    /// The transform that undoes this one
+    pub fn inverse(&self) -> Transform {
+        let inverse_rows = Self::invert(&self.rows);
+        let inverse_columns = Self::invert(&self.columns);
+        let mut digits = vec![0; self.digits.len()];
+        for (index, digit) in self.digits.iter().enumerate() {
+            digits[*digit as usize - 1] = index as u8 + 1;
+        }
+
+        // A transposing transform moves rows into columns, so undoing it moves them back
+        let (rows, columns) = if self.transpose {
+            (inverse_columns, inverse_rows)
+        } else {
+            (inverse_rows, inverse_columns)
+        };
+
+        Transform { transpose: self.transpose, rows, columns, digits }
+    }
+
+    /// The cell of the original grid that the given cell of the transformed grid comes from
+    pub fn source_of(&self, row: usize, column: usize) -> (usize, usize) {
+        if self.transpose {
+            (self.columns[column], self.rows[row])
+        } else {
+            (self.rows[row], self.columns[column])
+        }
+    }
+
+    pub fn map_digit(&self, value: u8) -> u8 {
+        self.digits[value as usize - 1]
+    }
+
+    /// The cell of the transformed grid that the given cell of the original ends up in
+    pub fn destination_of(&self, row: usize, column: usize) -> (usize, usize) {
+        let position = |lines: &[usize], line: usize| {
+            lines.iter().position(|source| *source == line).expect("Line orders cover every line")
+        };
+
+        if self.transpose {
+            (position(&self.rows, column), position(&self.columns, row))
+        } else {
+            (position(&self.rows, row), position(&self.columns, column))
+        }
+    }
+
+    /// Where the given cell of the original grid ends up, for moving constraints across
+    pub fn move_cell(&self, grid_ref: &GridReference) -> GridReference {
+        let (row, column) = self.destination_of(grid_ref.row(), grid_ref.column());
+
+        GridReference::from_numbers(row, column).expect("Transforms only refer to cells within the grid")
+    }
+
+    pub fn move_cells(&self, cells: &[GridReference]) -> Vec<GridReference> {
+        cells.iter().map(|grid_ref| self.move_cell(grid_ref)).collect()
+    }
+
+    /// Whether every digit stays as it is, which constraints that depend on the values themselves,
+    /// such as cage totals, need in order to carry across
+    pub fn keeps_digits(&self) -> bool {
+        self.digits.iter().enumerate().all(|(index, digit)| *digit as usize == index + 1)
+    }
+
+    /// Whether every odd digit stays odd and every even digit stays even
+    pub fn keeps_parity(&self) -> bool {
+        self.digits.iter().enumerate().all(|(index, digit)| *digit as usize % 2 == (index + 1) % 2)
+    }
+
+    /// Whether cells related in some way, such as being a knight's move apart, are still related
+    /// in the same way once they've been moved
+    pub fn keeps_relation(&self, related: impl Fn(&GridReference) -> Vec<GridReference>) -> bool {
+        SHAPE.grid_refs().all(|grid_ref| {
+            let moved_related = related(&self.move_cell(&grid_ref));
+            related(&grid_ref).iter().all(|other| moved_related.contains(&self.move_cell(other)))
+        })
+    }
+
+    /*
+     * Transforms the grid, moving its regions and constraints along with the values so that the
+     * result is the same puzzle seen a different way.  Only the standard 9 x 9 grid can be
+     * transformed, and a grid whose regions or constraints don't survive the transform, such as a
+     * jigsaw region that gets split up or a cage whose total no longer holds once the digits are
+     * relabelled, is turned away rather than turned into a different puzzle
+     */
+    pub fn apply(&self, grid: &Grid) -> Result<Grid, Box<dyn Error>> {
+        if *grid.shape() != SHAPE {
+            return Err(NonStandardShape::new(grid.shape()).into());
+        }
+
+        // Regions are numbered in the order they're first seen, which keeps the standard subgrids
+        // numbered the standard way however they've been moved around
+        let mut region_ids: Vec<Option<usize>> = vec![None; grid.regions().region_count()];
+        let mut regions = vec![0; SHAPE.cell_count()];
+        for grid_ref in SHAPE.grid_refs() {
+            let (source_row, source_column) = self.source_of(grid_ref.row(), grid_ref.column());
+            let source_ref = GridReference::from_numbers(source_row, source_column)
+                .expect("Transforms only refer to cells within the grid");
+            let region = grid.regions().region_of(&source_ref);
+
+            let next_id = region_ids.iter().flatten().count();
+            regions[grid_ref.to_index(&SHAPE)] = *region_ids[region].get_or_insert(next_id);
+        }
+        let regions = RegionMap::new(SHAPE, regions)?;
+
+        let constraints = grid.constraints()
+            .iter()
+            .map(|constraint| {
+                constraint.transformed(&SHAPE, self)
+                    .ok_or_else(|| UntransformableConstraint::new(format!("{:?}", constraint)))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut transformed = Grid::with_rules(regions, constraints);
+        for grid_ref in SHAPE.grid_refs() {
+            if let Some(value) = grid.cell(&grid_ref) {
+                transformed.set_cell(&self.move_cell(&grid_ref), &CellValue::new(self.map_digit(*value))?)?;
+            }
+        }
+
+        Ok(transformed)
+    }
+
+    fn reversed(count: usize) -> Vec<usize> {
+        (0 .. count).rev().collect()
+    }
+
+    fn swapped_blocks(count: usize, block_size: usize, first: usize, second: usize) -> Vec<usize> {
+        let mut lines: Vec<usize> = (0 .. count).collect();
+        for offset in 0 .. block_size {
+            lines.swap(first * block_size + offset, second * block_size + offset);
+        }
+
+        lines
+    }
+
+    fn random_line_order(rng: &mut SeededRng, count: usize, block_size: usize) -> Vec<usize> {
+        let mut blocks: Vec<usize> = (0 .. count / block_size).collect();
+        rng.shuffle(&mut blocks);
+
+        let mut lines = Vec::with_capacity(count);
+        for block in blocks {
+            let mut block_lines: Vec<usize> = (block * block_size .. (block + 1) * block_size).collect();
+            rng.shuffle(&mut block_lines);
+            lines.extend(block_lines);
+        }
+
+        lines
+    }
+
+    fn invert(permutation: &[usize]) -> Vec<usize> {
+        let mut inverse = vec![0; permutation.len()];
+        for (index, target) in permutation.iter().enumerate() {
+            inverse[*target] = index;
+        }
+
+        inverse
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sudoku::cage::Cage;
+    use crate::sudoku::constraint::{CandidateSet, Constraint};
+    use crate::sudoku::solver::{DepthFirstSolver, Solver};
+
+    const PUZZLE: [&str; 9] = [
+        "53..7....", "6..195...", ".98....6.", "8...6...3", "4..8.3..1", "7...2...6", ".6....28.", "...419..5", "....8..79",
+    ];
+
+    fn puzzle() -> Grid {
+        let mut grid = Grid::new();
+        for (row, line) in PUZZLE.iter().enumerate() {
+            for (column, value) in line.chars().enumerate() {
+                if let Some(value) = value.to_digit(10) {
+                    grid.set_cell(&GridReference::from_numbers(row, column).unwrap(), &CellValue::new(value as u8).unwrap())
+                        .unwrap();
+                }
+            }
+        }
+
+        grid
+    }
+
+    fn values(grid: &Grid) -> Vec<Option<u8>> {
+        (0 .. 81)
+            .map(|index| *grid.cell(&GridReference::from_numbers(index / 9, index % 9).unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn the_inverse_undoes_a_transform() {
+        let puzzle = puzzle();
+        let mut rng = SeededRng::new(7);
+
+        for _ in 0 .. 20 {
+            let transform = Transform::random(&mut rng);
+            let transformed = transform.apply(&puzzle).unwrap();
+            assert_eq!(values(&transform.inverse().apply(&transformed).unwrap()), values(&puzzle));
+
+            let expected = DepthFirstSolver::for_problem(&puzzle).solve().get_solution().clone().unwrap();
+            let solution = DepthFirstSolver::for_problem(&transformed).solve().get_solution().clone().unwrap();
+            assert_eq!(values(&transform.apply(&expected).unwrap()), values(&solution));
+        }
+    }
+
+    #[test]
+    fn composes_transforms_in_order() {
+        let puzzle = puzzle();
+        let first = Transform::swap_bands(0, 2).unwrap();
+        let second = Transform::relabel(&[9, 8, 7, 6, 5, 4, 3, 2, 1]).unwrap();
+
+        assert_eq!(values(&first.then(&second).apply(&puzzle).unwrap()), values(&second.apply(&first.apply(&puzzle).unwrap()).unwrap()));
+        assert_eq!(values(&Transform::rotate_clockwise().then(&Transform::rotate_anticlockwise()).apply(&puzzle).unwrap()), values(&puzzle));
+    }
+
+    #[test]
+    fn moves_cells_where_expected() {
+        let rotated = Transform::rotate_clockwise().apply(&puzzle()).unwrap();
+
+        // The top left 5 ends up in the top right corner, and the 3 beside it just below
+        assert_eq!(*rotated.cell(&GridReference::from_numbers(0, 8).unwrap()), Some(5));
+        assert_eq!(*rotated.cell(&GridReference::from_numbers(1, 8).unwrap()), Some(3));
+    }
+
+    #[test]
+    fn rejects_swaps_that_would_break_the_grid() {
+        assert!(Transform::swap_bands(0, 3).unwrap_err().is::<InvalidBand>());
+        assert!(Transform::swap_stacks(3, 1).unwrap_err().is::<InvalidStack>());
+        assert!(Transform::swap_rows(2, 3).unwrap_err().is::<InvalidLineSwap>());
+        assert!(Transform::swap_columns(0, 4).unwrap_err().is::<InvalidLineSwap>());
+        assert!(Transform::relabel(&[1, 1, 3, 4, 5, 6, 7, 8, 9]).unwrap_err().is::<InvalidDigitMapping>());
+    }
+
+    #[test]
+    fn only_transforms_the_standard_grid() {
+        let small = Grid::with_shape(GridShape::new(2, 3).unwrap());
+
+        assert!(Transform::transpose().apply(&small).unwrap_err().is::<NonStandardShape>());
+    }
+
+    #[test]
+    fn carries_the_rules_across_or_turns_the_grid_away() {
+        let transposed = Transform::transpose().apply(&puzzle()).unwrap();
+        assert_eq!(transposed.constraints().len(), 27);
+
+        // The first row of the transposed grid was the first column, so it now holds 5, 6, 8, 4 and 7
+        let clash = GridReference::from_numbers(0, 8).unwrap();
+        assert!(transposed.clone().set_cell(&clash, &CellValue::new(6).unwrap()).is_err());
+
+        let mut grid = puzzle();
+        grid.add_constraint(Untransformable).unwrap();
+        assert!(Transform::transpose().apply(&grid).unwrap_err().is::<UntransformableConstraint>());
+    }
+
+    #[test]
+    fn moves_jigsaw_regions_with_the_cells() {
+        // The standard subgrids, except that the top left one has swapped a corner cell for the cell
+        // beside it in the top middle one
+        let mut regions: Vec<usize> = SHAPE.grid_refs().map(|grid_ref| SHAPE.subgrid_of(grid_ref.row(), grid_ref.column())).collect();
+        regions[SHAPE.index_of(2, 2)] = 1;
+        regions[SHAPE.index_of(0, 3)] = 0;
+        let grid = Grid::with_regions(RegionMap::new(SHAPE, regions).unwrap());
+
+        let transposed = Transform::transpose().apply(&grid).unwrap();
+        let region_of = |row, column| transposed.regions().region_of(&GridReference::from_numbers(row, column).unwrap());
+        assert_eq!(region_of(3, 0), region_of(0, 0));
+        assert_eq!(region_of(2, 2), region_of(3, 2));
+        assert_ne!(region_of(2, 2), region_of(0, 0));
+    }
+
+    #[test]
+    fn cages_move_with_their_cells_but_not_under_relabelling() {
+        let mut grid = Grid::new();
+        let cells = vec![GridReference::from_numbers(0, 0).unwrap(), GridReference::from_numbers(0, 1).unwrap()];
+        grid.add_constraint(Cage::new(3, cells).unwrap()).unwrap();
+
+        let rotated = Transform::rotate_clockwise().apply(&grid).unwrap();
+        let (cells, _) = rotated.constraints().last().unwrap().outline(&SHAPE).unwrap();
+        assert_eq!(cells, vec![GridReference::from_numbers(0, 8).unwrap(), GridReference::from_numbers(1, 8).unwrap()]);
+
+        let relabelled = Transform::relabel(&[2, 1, 3, 4, 5, 6, 7, 8, 9]).unwrap().apply(&grid);
+        assert!(relabelled.unwrap_err().is::<UntransformableConstraint>());
+    }
+
+    #[derive(Debug)]
+    struct Untransformable;
+
+    impl Constraint for Untransformable {
+        fn affected_cells(&self, _shape: &GridShape) -> Vec<GridReference> {
+            Vec::new()
+        }
+
+        fn check(&self, _grid: &Grid, _changed: &GridReference) -> Result<(), Box<dyn Error>> {
+            Ok(())
+        }
+
+        fn prune(&self, _grid: &Grid, _candidates: &mut [CandidateSet]) {}
+    }
+}
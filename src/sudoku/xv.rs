@@ -0,0 +1,112 @@
+use crate::sudoku::constraint::{CandidateSet, Constraint};
+use crate::sudoku::error::{InvalidBorderMark, PairConstraint};
+use crate::sudoku::formula::Formula;
+use crate::sudoku::grid::Grid;
+use crate::sudoku::pair::{check_pair, encode_pair, pair_formula, prune_pair};
+use crate::sudoku::reference::GridReference;
+use crate::sudoku::sat::Cnf;
+use crate::sudoku::shape::GridShape;
+use crate::sudoku::transform::Transform;
+use std::error::Error;
+use std::sync::Arc;
+
+/// An X or a V on the border between two neighbouring cells: their values must add up to 10 for an
+/// X, or 5 for a V
+#[derive(Debug, Clone)]
+pub struct XvMarker {
+    kind: PairConstraint,
+    cells: [GridReference; 2],
+}
+
+impl XvMarker {
+    pub fn x(first: GridReference, second: GridReference) -> Result<Self, InvalidBorderMark> {
+        Self::new(PairConstraint::X, first, second)
+    }
+
+    pub fn v(first: GridReference, second: GridReference) -> Result<Self, InvalidBorderMark> {
+        Self::new(PairConstraint::V, first, second)
+    }
+
+    pub fn kind(&self) -> &PairConstraint {
+        &self.kind
+    }
+
+    pub fn cells(&self) -> &[GridReference; 2] {
+        &self.cells
+    }
+
+    fn new(kind: PairConstraint, first: GridReference, second: GridReference) -> Result<Self, InvalidBorderMark> {
+        if !first.shares_side_with(&second) {
+            return Err(InvalidBorderMark::new(&first, &second));
+        }
+
+        Ok(Self { kind, cells: [first, second] })
+    }
+}
+
+impl Constraint for XvMarker {
+    fn affected_cells(&self, _shape: &GridShape) -> Vec<GridReference> {
+        self.cells.to_vec()
+    }
+
+    fn check(&self, grid: &Grid, changed: &GridReference) -> Result<(), Box<dyn Error>> {
+        check_pair(&self.kind, &self.cells, grid, changed)
+    }
+
+    fn prune(&self, grid: &Grid, candidates: &mut [CandidateSet]) {
+        prune_pair(&self.kind, &self.cells, grid, candidates);
+    }
+
+    fn encode(&self, _shape: &GridShape, cnf: &mut Cnf) -> bool {
+        encode_pair(&self.kind, &self.cells, cnf);
+
+        true
+    }
+
+    fn formulas(&self, _shape: &GridShape) -> Option<Vec<Formula>> {
+        Some(vec![pair_formula(&self.kind, &self.cells)])
+    }
+
+    fn border_marks(&self, _shape: &GridShape) -> Vec<(GridReference, GridReference, char)> {
+        let mark = match self.kind {
+            PairConstraint::V => 'V',
+            _ => 'X',
+        };
+
+        vec![(self.cells[0].clone(), self.cells[1].clone(), mark)]
+    }
+
+    fn transformed(&self, _shape: &GridShape, transform: &Transform) -> Option<Arc<dyn Constraint>> {
+        if !transform.keeps_digits() {
+            return None;
+        }
+
+        let [first, second] = &self.cells;
+        let marker = Self::new(self.kind.clone(), transform.move_cell(first), transform.move_cell(second)).ok()?;
+        Some(Arc::new(marker))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sudoku::error::PairError;
+    use crate::sudoku::grid::CellValue;
+
+    #[test]
+    fn markers_fix_the_total_of_their_pair() {
+        let (first, second) = (GridReference::from_numbers(0, 0).unwrap(), GridReference::from_numbers(0, 1).unwrap());
+        let mut grid = Grid::new();
+        grid.add_constraint(XvMarker::x(first.clone(), second.clone()).unwrap()).unwrap();
+        grid.set_cell(&first, &CellValue::new(3).unwrap()).unwrap();
+
+        assert!(grid.clone().set_cell(&second, &CellValue::new(2).unwrap()).unwrap_err().is::<PairError>());
+        assert!(grid.set_cell(&second, &CellValue::new(7).unwrap()).is_ok());
+
+        // Nothing pairs with 5 to make 5, so it can't go beside a V
+        let v = XvMarker::v(first.clone(), second).unwrap();
+        let mut candidates = vec![0b1_1111_1111; 81];
+        v.prune(&Grid::new(), &mut candidates);
+        assert_eq!(candidates[0], 0b1111);
+    }
+}